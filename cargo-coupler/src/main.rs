@@ -82,8 +82,19 @@ struct PackageMetadata {
 struct CouplerMetadata {
     name: Option<String>,
 
+    identifier: Option<String>,
+
     #[serde(default)]
     formats: Vec<String>,
+
+    #[serde(default)]
+    plugins: Vec<PluginMetadata>,
+}
+
+#[derive(Deserialize, Clone)]
+struct PluginMetadata {
+    name: String,
+    id: String,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -187,7 +198,10 @@ struct PackageInfo {
     package_name: String,
     lib_name: String,
     name: String,
+    identifier: String,
+    version: String,
     formats: Vec<Format>,
+    plugins: Vec<PluginMetadata>,
 }
 
 fn main() {
@@ -437,6 +451,43 @@ fn bundle(cmd: &Bundle) {
                 }
             }
 
+            let mut plugin_ids = HashSet::new();
+            for plugin in &coupler_metadata.plugins {
+                if plugin.name.is_empty() || plugin.id.is_empty() {
+                    eprintln!(
+                        "error: package `{}` specifies a plugin with an empty name or id",
+                        &package.name
+                    );
+                    process::exit(1);
+                }
+
+                if !plugin_ids.insert(&plugin.id) {
+                    eprintln!(
+                        "error: package `{}` specifies plugin id `{}` more than once",
+                        &package.name, &plugin.id
+                    );
+                    process::exit(1);
+                }
+            }
+
+            let identifier = if let Some(identifier) = &coupler_metadata.identifier {
+                identifier.clone()
+            } else if let [plugin] = &coupler_metadata.plugins[..] {
+                plugin.id.clone()
+            } else if coupler_metadata.plugins.is_empty() {
+                eprintln!(
+                    "warning: package `{}` does not specify a bundle identifier",
+                    &package.name
+                );
+                package.name.clone()
+            } else {
+                eprintln!(
+                    "error: package `{}` contains multiple plugins but does not specify a bundle identifier",
+                    &package.name
+                );
+                process::exit(1);
+            };
+
             let has_cdylib =
                 package.targets.iter().any(|t| t.crate_types.iter().any(|c| c == "cdylib"));
             if !has_cdylib {
@@ -458,7 +509,10 @@ fn bundle(cmd: &Bundle) {
                 package_name: package.name.to_owned(),
                 lib_name,
                 name: coupler_metadata.name.as_ref().unwrap_or(&package.name).clone(),
+                identifier,
+                version: package.version.to_string(),
                 formats,
+                plugins: coupler_metadata.plugins,
             });
         }
     }
@@ -488,14 +542,12 @@ fn bundle(cmd: &Bundle) {
 
     for package_info in &packages_to_build {
         for format in &package_info.formats {
-            match format {
-                Format::Clap => {
-                    bundle_clap(package_info, &out_dir, &target);
-                }
-                Format::Vst3 => {
-                    bundle_vst3(package_info, &out_dir, &target);
-                }
-            }
+            let bundle_path = match format {
+                Format::Clap => bundle_clap(package_info, &out_dir, &target),
+                Format::Vst3 => bundle_vst3(package_info, &out_dir, &target),
+            };
+
+            print_bundle(package_info, &bundle_path);
         }
     }
 }
@@ -600,7 +652,7 @@ fn build(cmd: &Bundle, target: Option<&str>, packages: &[PackageInfo]) {
     }
 }
 
-fn bundle_clap(package_info: &PackageInfo, out_dir: &Path, target: &Target) {
+fn bundle_clap(package_info: &PackageInfo, out_dir: &Path, target: &Target) -> PathBuf {
     let src = out_dir.join(&package_info.lib_name);
 
     let name = &package_info.name;
@@ -608,10 +660,10 @@ fn bundle_clap(package_info: &PackageInfo, out_dir: &Path, target: &Target) {
 
     match target.os {
         Os::Linux | Os::Windows => {
-            let dst = bundle_path;
+            let dst = &bundle_path;
 
             fs::create_dir_all(dst.parent().unwrap()).unwrap();
-            fs::copy(&src, &dst).unwrap();
+            fs::copy(&src, dst).unwrap();
         }
         Os::MacOs => {
             if bundle_path.exists() {
@@ -626,9 +678,11 @@ fn bundle_clap(package_info: &PackageInfo, out_dir: &Path, target: &Target) {
             macos_bundle_info(package_info, &bundle_path);
         }
     }
+
+    bundle_path
 }
 
-fn bundle_vst3(package_info: &PackageInfo, out_dir: &Path, target: &Target) {
+fn bundle_vst3(package_info: &PackageInfo, out_dir: &Path, target: &Target) -> PathBuf {
     let src = out_dir.join(&package_info.lib_name);
 
     let name = &package_info.name;
@@ -669,10 +723,22 @@ fn bundle_vst3(package_info: &PackageInfo, out_dir: &Path, target: &Target) {
     if target.os == Os::MacOs {
         macos_bundle_info(package_info, &bundle_path);
     }
+
+    bundle_path
+}
+
+fn print_bundle(package_info: &PackageInfo, bundle_path: &Path) {
+    eprintln!("{:>12} {}", "Bundled", bundle_path.display());
+
+    for plugin in &package_info.plugins {
+        eprintln!("{:>12} {} ({})", "", plugin.name, plugin.id);
+    }
 }
 
 fn macos_bundle_info(package_info: &PackageInfo, bundle_path: &Path) {
     let name = &package_info.name;
+    let identifier = &package_info.identifier;
+    let version = &package_info.version;
 
     let plist = format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -684,7 +750,7 @@ fn macos_bundle_info(package_info: &PackageInfo, bundle_path: &Path) {
     <key>CFBundleExecutable</key>
     <string>{name}</string>
     <key>CFBundleIdentifier</key>
-    <string></string>
+    <string>{identifier}</string>
     <key>CFBundleInfoDictionaryVersion</key>
     <string>6.0</string>
     <key>CFBundlePackageType</key>
@@ -692,9 +758,9 @@ fn macos_bundle_info(package_info: &PackageInfo, bundle_path: &Path) {
     <key>CFBundleSignature</key>
     <string>????</string>
     <key>CFBundleVersion</key>
-    <string>1.0.0</string>
+    <string>{version}</string>
     <key>CFBundleShortVersionString</key>
-    <string>1.0.0</string>
+    <string>{version}</string>
 </dict>
 </plist>"#
     );
@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{Data, DeriveInput, Error, Expr, Field, Fields, LitInt, LitStr};
+use syn::{Data, DeriveInput, Error, Expr, Field, Fields, LitInt, LitStr, Type};
 
 pub struct ParamAttr {
     pub id: LitInt,
@@ -9,6 +9,8 @@ pub struct ParamAttr {
     pub parse: Option<Expr>,
     pub display: Option<Expr>,
     pub format: Option<LitStr>,
+    pub smooth: Option<LitStr>,
+    pub time_ms: Option<Expr>,
 }
 
 pub fn parse_param(field: &Field) -> Result<Option<ParamAttr>, Error> {
@@ -20,6 +22,8 @@ pub fn parse_param(field: &Field) -> Result<Option<ParamAttr>, Error> {
     let mut parse = None;
     let mut display = None;
     let mut format = None;
+    let mut smooth = None;
+    let mut time_ms = None;
 
     for attr in &field.attrs {
         if !attr.path().is_ident("param") {
@@ -100,6 +104,32 @@ pub fn parse_param(field: &Field) -> Result<Option<ParamAttr>, Error> {
                 }
 
                 format = Some(meta.value()?.parse::<LitStr>()?);
+            } else if ident == "smooth" {
+                if smooth.is_some() {
+                    return Err(Error::new_spanned(
+                        &meta.path,
+                        "duplicate param attribute `smooth`",
+                    ));
+                }
+
+                let style = meta.value()?.parse::<LitStr>()?;
+                if !matches!(&*style.value(), "linear" | "exp" | "log") {
+                    return Err(Error::new_spanned(
+                        style,
+                        "expected one of \"linear\", \"exp\" or \"log\"",
+                    ));
+                }
+
+                smooth = Some(style);
+            } else if ident == "time_ms" {
+                if time_ms.is_some() {
+                    return Err(Error::new_spanned(
+                        &meta.path,
+                        "duplicate param attribute `time_ms`",
+                    ));
+                }
+
+                time_ms = Some(meta.value()?.parse::<Expr>()?);
             } else {
                 return Err(Error::new_spanned(
                     &meta.path,
@@ -121,6 +151,21 @@ pub fn parse_param(field: &Field) -> Result<Option<ParamAttr>, Error> {
         return Err(Error::new_spanned(field, "missing `id` attribute"));
     };
 
+    // Smoothers operate on plain values, which are only meaningful for floating-point fields.
+    if smooth.is_some() && !is_float(&field.ty) {
+        return Err(Error::new_spanned(
+            &field.ty,
+            "`smooth` attribute can only be used on `f32` or `f64` fields",
+        ));
+    }
+
+    if let (Some(time_ms), None) = (&time_ms, &smooth) {
+        return Err(Error::new_spanned(
+            time_ms,
+            "`time_ms` attribute cannot be used without `smooth`",
+        ));
+    }
+
     Ok(Some(ParamAttr {
         id,
         name,
@@ -128,9 +173,22 @@ pub fn parse_param(field: &Field) -> Result<Option<ParamAttr>, Error> {
         parse,
         display,
         format,
+        smooth,
+        time_ms,
     }))
 }
 
+fn is_float(ty: &Type) -> bool {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.last() {
+            return segment.arguments.is_none()
+                && (segment.ident == "f32" || segment.ident == "f64");
+        }
+    }
+
+    false
+}
+
 pub fn gen_encode(field: &Field, param: &ParamAttr, value: impl ToTokens) -> TokenStream {
     let ty = &field.ty;
     if let Some(range) = &param.range {
@@ -186,7 +244,44 @@ fn parse_fields(input: &DeriveInput) -> Result<Vec<ParamField>, Error> {
     Ok(param_fields)
 }
 
+struct ParamsAttr {
+    serde: bool,
+}
+
+fn parse_params_attr(input: &DeriveInput) -> Result<ParamsAttr, Error> {
+    let mut serde = false;
+
+    for attr in &input.attrs {
+        if !attr.path().is_ident("params") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("serde") {
+                if serde {
+                    return Err(Error::new_spanned(
+                        &meta.path,
+                        "duplicate params attribute `serde`",
+                    ));
+                }
+
+                serde = true;
+            } else {
+                return Err(Error::new_spanned(
+                    &meta.path,
+                    "unknown params attribute, expected `serde`",
+                ));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(ParamsAttr { serde })
+}
+
 pub fn expand_params(input: &DeriveInput) -> Result<TokenStream, Error> {
+    let attr = parse_params_attr(input)?;
     let fields = parse_fields(input)?;
 
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
@@ -295,6 +390,103 @@ pub fn expand_params(input: &DeriveInput) -> Result<TokenStream, Error> {
         }
     });
 
+    let smoothed = fields.iter().filter(|field| field.param.smooth.is_some()).collect::<Vec<_>>();
+
+    let smoother_inits = smoothed.iter().map(|field| {
+        let ident = &field.field.ident;
+        let id = &field.param.id;
+
+        let style = match &*field.param.smooth.as_ref().unwrap().value() {
+            "linear" => quote! { Linear },
+            "exp" => quote! { Exponential },
+            _ => quote! { Logarithmic },
+        };
+        let time_ms = if let Some(time_ms) = &field.param.time_ms {
+            quote! { (#time_ms) as f64 }
+        } else {
+            quote! { ::coupler::params::DEFAULT_TIME_MS }
+        };
+
+        quote! {
+            __smoothers.insert(
+                #id,
+                ::coupler::params::Smoother::new(
+                    ::coupler::params::SmoothStyle::#style,
+                    #time_ms,
+                    __sample_rate,
+                    self.#ident as f32,
+                ),
+            );
+        }
+    });
+
+    let update_cases = smoothed.iter().map(|field| {
+        let ident = &field.field.ident;
+        let id = &field.param.id;
+
+        quote! {
+            #id => {
+                if let ::std::option::Option::Some(__smoother) = __smoothers.get_mut(#id) {
+                    __smoother.set_target(self.#ident as f32);
+                }
+            }
+        }
+    });
+
+    let ramp_cases = smoothed.iter().map(|field| {
+        let id = &field.param.id;
+
+        let decode = gen_decode(field.field, &field.param, quote! { __value });
+
+        quote! {
+            #id => {
+                if let ::std::option::Option::Some(__smoother) = __smoothers.get_mut(#id) {
+                    __smoother.ramp((#decode) as f32, __len);
+                }
+            }
+        }
+    });
+
+    let smooth_impl = if smoothed.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! {
+            impl #impl_generics ::coupler::params::SmoothParams for #ident #ty_generics #where_clause {
+                fn smoothers(&self, __sample_rate: f64) -> ::coupler::params::Smoothers {
+                    let mut __smoothers = ::coupler::params::Smoothers::new();
+                    #(#smoother_inits)*
+                    __smoothers
+                }
+
+                fn update_smoother(&self, __id: ::coupler::params::ParamId, __smoothers: &mut ::coupler::params::Smoothers) {
+                    match __id {
+                        #(#update_cases)*
+                        _ => {}
+                    }
+                }
+
+                fn ramp_smoother(
+                    &self,
+                    __id: ::coupler::params::ParamId,
+                    __value: ::coupler::params::ParamValue,
+                    __len: u32,
+                    __smoothers: &mut ::coupler::params::Smoothers,
+                ) {
+                    match __id {
+                        #(#ramp_cases)*
+                        _ => {}
+                    }
+                }
+            }
+        }
+    };
+
+    let serde_impls = if attr.serde {
+        gen_serde_impls(input)
+    } else {
+        TokenStream::new()
+    };
+
     Ok(quote! {
         impl #impl_generics ::coupler::params::Params for #ident #ty_generics #where_clause {
             fn params() -> ::std::vec::Vec<::coupler::params::ParamInfo> {
@@ -338,5 +530,37 @@ pub fn expand_params(input: &DeriveInput) -> Result<TokenStream, Error> {
                 }
             }
         }
+
+        #smooth_impl
+
+        #serde_impls
     })
 }
+
+fn gen_serde_impls(input: &DeriveInput) -> TokenStream {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut de_generics = input.generics.clone();
+    de_generics.params.insert(0, syn::parse_quote! { '__de });
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::coupler::__serde::Serialize for #ident #ty_generics #where_clause {
+            fn serialize<__S: ::coupler::__serde::Serializer>(
+                &self,
+                __serializer: __S,
+            ) -> ::std::result::Result<__S::Ok, __S::Error> {
+                ::coupler::params::serialize::serialize(self, __serializer)
+            }
+        }
+
+        impl #de_impl_generics ::coupler::__serde::Deserialize<'__de> for #ident #ty_generics #where_clause {
+            fn deserialize<__D: ::coupler::__serde::Deserializer<'__de>>(
+                __deserializer: __D,
+            ) -> ::std::result::Result<Self, __D::Error> {
+                ::coupler::params::serialize::deserialize(__deserializer)
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{lerp, AudioConfig, Float};
+
+// Delay line backed by a power-of-two ring buffer. Delay times are in seconds. The interpolating
+// reads measure them from the next sample to be pushed, so they should happen before `push`, while
+// `go_back_int` and `go_back_int_s` measure from the most recently pushed sample.
+#[derive(Clone, Debug)]
+pub struct DelayLine<T = f32> {
+    buf: Vec<T>,
+    mask: usize,
+    sample_rate: T,
+    pos: usize,
+    h: [T; 4],
+    frac_prev: T,
+}
+
+impl<T: Float> DelayLine<T> {
+    pub fn new(sample_rate: T, len: T) -> Self {
+        // + 4 is so that we have a bit of room for doing cubic interpolation etc.
+        let samples = (len * sample_rate).to_f64() as usize + 4;
+        Self::new_absolute(sample_rate, samples)
+    }
+
+    pub fn from_config(config: &impl AudioConfig, len: T) -> Self {
+        Self::new(T::from_f64(config.sample_rate()), len)
+    }
+
+    // Allocates room for at least `len` samples.
+    pub fn new_absolute(sample_rate: T, len: usize) -> Self {
+        let len = len.max(4).next_power_of_two();
+        Self {
+            buf: vec![T::ZERO; len],
+            mask: len - 1,
+            sample_rate,
+            pos: 0,
+            h: [T::ZERO, T::ONE, T::ZERO, T::ZERO],
+            frac_prev: T::ZERO,
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    #[inline]
+    fn get(&self, offset: usize) -> T {
+        self.buf[self.pos.wrapping_sub(offset) & self.mask]
+    }
+
+    #[inline]
+    pub fn push(&mut self, s: T) {
+        self.pos = (self.pos + 1) & self.mask;
+        self.buf[self.pos] = s;
+    }
+
+    fn delay_samples(&self, time: T) -> T {
+        let delay = (time * self.sample_rate).max(T::ONE);
+        assert!(delay.to_f64() < self.buf.len() as f64);
+        delay
+    }
+
+    #[must_use]
+    pub fn go_back_int(&self, time: T) -> T {
+        let delay = self.delay_samples(time);
+        self.get(delay.to_f64() as usize)
+    }
+
+    #[must_use]
+    pub fn go_back_int_s(&self, samples: usize) -> T {
+        self.get(samples)
+    }
+
+    #[must_use]
+    pub fn go_back_linear(&self, time: T) -> T {
+        let delay = self.delay_samples(time);
+        let d_int = delay.floor();
+        let frac = delay - d_int;
+        let d_int = d_int.to_f64() as usize;
+
+        lerp(self.get(d_int - 1), self.get(d_int), frac)
+    }
+
+    // lagrange polynomial
+    fn calc_coeff(&mut self, dm1: T) {
+        let half = T::from_f64(0.5);
+        let sixth = T::from_f64(1.0 / 6.0);
+
+        let d = dm1 + T::ONE;
+        let dm2 = dm1 - T::ONE;
+        let dm3 = dm1 - T::from_f64(2.0);
+        self.h[0] = -sixth * dm1 * dm2 * dm3;
+        self.h[1] = half * d * dm2 * dm3;
+        self.h[2] = -half * d * dm1 * dm3;
+        self.h[3] = sixth * d * dm1 * dm2;
+    }
+
+    #[must_use]
+    pub fn go_back_cubic(&mut self, time: T) -> T {
+        let delay = self.delay_samples(time);
+        let d_int = delay.floor();
+        let dm1 = delay - d_int;
+        let d_int = d_int.to_f64() as usize;
+
+        if self.frac_prev != dm1 {
+            self.calc_coeff(dm1);
+        }
+        self.frac_prev = dm1;
+
+        let mut sum = T::ZERO;
+        for (i, h) in self.h.iter().enumerate() {
+            sum += self.get((d_int + i).wrapping_sub(2)) * *h;
+        }
+
+        sum
+    }
+
+    // Schroeder allpass around the delay line.
+    #[must_use]
+    pub fn allpass(&mut self, s: T, k_ap: T, time: T) -> T {
+        let d = self.go_back_int(time);
+        let v = s - k_ap * d;
+        self.push(v);
+        k_ap * v + d
+    }
+
+    pub fn flush(&mut self) {
+        self.h = [T::ZERO, T::ONE, T::ZERO, T::ZERO];
+        self.frac_prev = T::ZERO;
+        self.buf.fill(T::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_stable, gain_db};
+
+    #[test]
+    fn integer_delay() {
+        let mut delay = DelayLine::<f32>::new_absolute(1.0, 10);
+        assert_eq!(delay.len(), 16);
+
+        for i in 0..20 {
+            delay.push(i as f32);
+            assert_eq!(delay.go_back_int_s(0), i as f32);
+            if i >= 3 {
+                assert_eq!(delay.go_back_int(3.0), (i - 3) as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn fractional_delay() {
+        let mut delay = DelayLine::<f64>::new(1000.0, 0.1);
+        for i in 0..100 {
+            delay.push(i as f64);
+        }
+
+        // interpolation is exact for a ramp
+        assert!((delay.go_back_linear(0.0025) - 97.5).abs() < 1e-9);
+        assert!((delay.go_back_cubic(0.0025) - 97.5).abs() < 1e-9);
+        assert!((delay.go_back_cubic(0.0105) - 89.5).abs() < 1e-9);
+
+        delay.flush();
+        assert_eq!(delay.go_back_cubic(0.005), 0.0);
+    }
+
+    #[test]
+    fn allpass() {
+        let sample_rate = 48000.0;
+        for freq in [50.0, 1000.0, 10000.0] {
+            let mut delay = DelayLine::<f64>::new(sample_rate, 0.01);
+            let gain = gain_db(sample_rate, freq, |x| delay.allpass(x, 0.6, 0.0037));
+            assert!(gain.abs() < 0.01, "{freq} Hz: {gain} dB");
+        }
+
+        let mut delay = DelayLine::<f64>::new(sample_rate, 0.01);
+        assert_stable(|x| delay.allpass(x, 0.9, 0.001));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{lerp, time_constant, time_constant_linear, AudioConfig, Float};
+
+// Exponential envelope follower with separate attack and release times (in milliseconds).
+#[derive(Clone, Debug)]
+pub struct AttackRelease<T = f32> {
+    value: T,
+    target: T,
+    attack: T,
+    release: T,
+    sample_rate: T,
+}
+
+impl<T: Float> AttackRelease<T> {
+    pub fn new(attack: T, release: T, sample_rate: T) -> Self {
+        Self {
+            target: T::ZERO,
+            value: T::ZERO,
+            attack: time_constant(attack, sample_rate),
+            release: time_constant(release, sample_rate),
+            sample_rate,
+        }
+    }
+
+    pub fn from_config(attack: T, release: T, config: &impl AudioConfig) -> Self {
+        Self::new(attack, release, T::from_f64(config.sample_rate()))
+    }
+
+    // Takes the per-sample coefficients directly.
+    pub fn new_direct(attack: T, release: T) -> Self {
+        Self {
+            target: T::ZERO,
+            value: T::ZERO,
+            attack,
+            release,
+            sample_rate: T::ONE,
+        }
+    }
+
+    #[must_use]
+    pub fn process(&mut self) -> T {
+        let t = if self.target > self.value {
+            self.attack
+        } else {
+            self.release
+        };
+        self.value = lerp(self.value, self.target, t);
+
+        self.value
+    }
+
+    pub fn set(&mut self, v: T) {
+        self.target = v;
+    }
+
+    pub fn set_immediate(&mut self, v: T) {
+        self.target = v;
+        self.value = v;
+    }
+
+    #[must_use]
+    pub fn get(&self) -> T {
+        self.value
+    }
+
+    #[must_use]
+    pub fn target(&self) -> T {
+        self.target
+    }
+
+    pub fn set_attack(&mut self, attack: T) {
+        self.attack = time_constant(attack, self.sample_rate);
+    }
+
+    pub fn set_release(&mut self, release: T) {
+        self.release = time_constant(release, self.sample_rate);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AdsrStage {
+    Attack,
+    Sustain,
+    Release,
+}
+
+// classic ADSR envelope
+// Times are in milliseconds. The attack is linear and gets faster with velocity, decay and
+// release are exponential.
+#[derive(Clone, Debug)]
+pub struct Adsr<T = f32> {
+    attack: T,
+    decay: T,
+    sustain: T,
+    release: T,
+    value: T,
+    stage: AdsrStage,
+    sample_rate: T,
+    attack_step: T,
+    vel: T,
+}
+
+impl<T: Float> Adsr<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            attack: T::ONE,
+            attack_step: T::ONE,
+            decay: T::ONE,
+            sustain: T::ONE,
+            release: T::ZERO,
+            value: T::ZERO,
+            vel: T::ONE,
+            stage: AdsrStage::Release,
+            sample_rate,
+        }
+    }
+
+    pub fn from_config(config: &impl AudioConfig) -> Self {
+        Self::new(T::from_f64(config.sample_rate()))
+    }
+
+    #[must_use]
+    pub fn process(&mut self) -> T {
+        match self.stage {
+            AdsrStage::Attack => {
+                self.value += self.attack_step;
+                if self.value >= self.vel {
+                    self.value = self.vel;
+                    self.stage = AdsrStage::Sustain;
+                }
+            }
+            AdsrStage::Sustain => {
+                self.value = lerp(self.value, self.sustain * self.vel, self.decay);
+            }
+            AdsrStage::Release => self.value *= self.release,
+        }
+        self.value
+    }
+
+    #[must_use]
+    pub fn get(&self) -> T {
+        self.value
+    }
+
+    #[must_use]
+    pub fn stage(&self) -> AdsrStage {
+        self.stage
+    }
+
+    pub fn note_on(&mut self, vel: T) {
+        self.stage = AdsrStage::Attack;
+        self.vel = vel;
+
+        // attack automatically gets faster with velocity
+        let vel_scale = T::ONE + T::from_f64(6.0) * self.vel;
+        self.attack_step = self.attack * self.vel * vel_scale;
+    }
+
+    pub fn note_off(&mut self) {
+        self.stage = AdsrStage::Release;
+    }
+
+    pub fn set_vel(&mut self, vel: T) {
+        self.vel = vel;
+    }
+
+    pub fn set_attack(&mut self, attack: T) {
+        self.attack = time_constant_linear(attack, self.sample_rate);
+    }
+
+    pub fn set_decay(&mut self, decay: T) {
+        self.decay = time_constant(decay, self.sample_rate);
+    }
+
+    pub fn set_sustain(&mut self, sustain: T) {
+        self.sustain = sustain;
+    }
+
+    pub fn set_release(&mut self, release: T) {
+        self.release = T::ONE - time_constant(release, self.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attack_release() {
+        // 10 ms at 1 kHz is 10 samples to reach 1% of the distance
+        let mut env = AttackRelease::<f64>::new(10.0, 100.0, 1000.0);
+        env.set(1.0);
+        for _ in 0..10 {
+            let _ = env.process();
+        }
+        assert!((env.get() - 0.99).abs() < 1e-6);
+
+        env.set(0.0);
+        for _ in 0..100 {
+            let _ = env.process();
+        }
+        assert!((env.get() - 0.0099).abs() < 1e-6);
+    }
+
+    #[test]
+    fn adsr() {
+        let mut env = Adsr::<f64>::new(1000.0);
+        env.set_attack(10.0);
+        env.set_decay(10.0);
+        env.set_sustain(0.5);
+        env.set_release(10.0);
+
+        env.note_on(1.0);
+        let mut peak_at = None;
+        for i in 0..100 {
+            let value = env.process();
+            assert!((0.0..=1.0).contains(&value));
+            if peak_at.is_none() && env.stage() == AdsrStage::Sustain {
+                peak_at = Some(i);
+            }
+        }
+        // full velocity attack is 7x faster than the attack time
+        assert_eq!(peak_at, Some(1));
+        assert!((env.get() - 0.5).abs() < 1e-6);
+
+        env.note_off();
+        for _ in 0..10 {
+            let _ = env.process();
+        }
+        assert!((env.get() - 0.005).abs() < 1e-6);
+    }
+}
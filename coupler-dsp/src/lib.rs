@@ -0,0 +1,100 @@
+mod float;
+mod smooth;
+
+pub mod delayline;
+pub mod env;
+pub mod onepole;
+pub mod oversample;
+pub mod resample;
+pub mod simper;
+pub mod skf;
+
+pub use float::Float;
+
+pub trait AudioConfig {
+    fn sample_rate(&self) -> f64;
+    fn max_buffer_size(&self) -> usize;
+}
+
+#[inline]
+pub fn lerp<T: Float>(a: T, b: T, t: T) -> T {
+    a + (b - a) * t
+}
+
+#[inline]
+pub fn from_db<T: Float>(x: T) -> T {
+    (x * T::from_f64(std::f64::consts::LN_10 / 20.0)).exp()
+}
+
+#[inline]
+pub fn to_db<T: Float>(x: T) -> T {
+    x.ln() * T::from_f64(20.0 / std::f64::consts::LN_10)
+}
+
+// Bilinear transform prewarping: tan(pi * f), where f is the frequency relative to the sample
+// rate. Clamped just below Nyquist.
+#[inline]
+pub fn prewarp<T: Float>(f: T) -> T {
+    (T::PI * f.min(T::from_f64(0.49))).tan()
+}
+
+// milliseconds (time to reach 10^-2) to time constant
+pub fn time_constant<T: Float>(t: T, sample_rate: T) -> T {
+    // 1000 * ln(0.01)
+    const T_LN: f64 = -4605.1704;
+
+    debug_assert!(t > T::ZERO);
+
+    T::ONE - (T::from_f64(T_LN) / (sample_rate * t)).exp()
+}
+
+pub fn time_constant_linear<T: Float>(t: T, sample_rate: T) -> T {
+    debug_assert!(t > T::ZERO);
+
+    T::from_f64(1000.0) / (sample_rate * t)
+}
+
+#[cfg(test)]
+mod test_util {
+    use std::f64::consts::TAU;
+
+    // Feeds a sine at `freq` through `process` and returns the gain of the steady-state output.
+    pub fn measure_gain(sample_rate: f64, freq: f64, mut process: impl FnMut(f64) -> f64) -> f64 {
+        let settle = sample_rate as usize;
+        let len = sample_rate as usize;
+
+        let mut sin_sum = 0.0;
+        let mut cos_sum = 0.0;
+        for i in 0..settle + len {
+            let phase = TAU * freq * i as f64 / sample_rate;
+            let y = process(phase.sin());
+            if i >= settle {
+                sin_sum += y * phase.sin();
+                cos_sum += y * phase.cos();
+            }
+        }
+
+        2.0 * (sin_sum * sin_sum + cos_sum * cos_sum).sqrt() / len as f64
+    }
+
+    pub fn gain_db(sample_rate: f64, freq: f64, process: impl FnMut(f64) -> f64) -> f64 {
+        20.0 * measure_gain(sample_rate, freq, process).log10()
+    }
+
+    // Runs an impulse through `process` and checks that the response decays to silence.
+    pub fn assert_stable(mut process: impl FnMut(f64) -> f64) {
+        let mut peak: f64 = 0.0;
+        let mut tail: f64 = 0.0;
+        for i in 0..200_000 {
+            let y = process(if i == 0 { 1.0 } else { 0.0 });
+            assert!(y.is_finite());
+            peak = peak.max(y.abs());
+            if i >= 190_000 {
+                tail = tail.max(y.abs());
+            }
+        }
+
+        assert!(peak < 100.0, "peak = {peak}");
+        assert!(tail < 1e-6, "tail = {tail}");
+    }
+}
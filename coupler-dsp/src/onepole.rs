@@ -0,0 +1,184 @@
+use crate::smooth::SmoothLinear;
+use crate::{from_db, prewarp, AudioConfig, Float};
+
+// One-pole TPT filter. Coefficient changes are smoothed over 64 samples, call `immediate` to
+// apply them right away.
+#[derive(Clone, Debug)]
+pub struct OnePole<T = f32> {
+    sample_rate: T,
+    s: T,
+
+    g: SmoothLinear<T>,
+    my: SmoothLinear<T>,
+    mx: SmoothLinear<T>,
+}
+
+impl<T: Float> OnePole<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            sample_rate,
+            g: SmoothLinear::new_steps(64),
+            my: SmoothLinear::new_steps(64),
+            mx: SmoothLinear::new_steps(64),
+
+            s: T::ZERO,
+        }
+    }
+
+    pub fn from_config(config: &impl AudioConfig) -> Self {
+        Self::new(T::from_f64(config.sample_rate()))
+    }
+
+    pub fn reset_state(&mut self) {
+        self.s = T::ZERO;
+    }
+
+    pub fn immediate(&mut self) {
+        self.g.immediate();
+        self.my.immediate();
+        self.mx.immediate();
+    }
+
+    fn set_coef(&mut self, f: T) {
+        let g = f / (T::ONE + f);
+        self.g.set(g);
+    }
+
+    fn set_mix(&mut self, my: f64, mx: f64) {
+        self.my.set(T::from_f64(my));
+        self.mx.set(T::from_f64(mx));
+    }
+
+    pub fn set_lowpass(&mut self, cutoff: T) {
+        let f = prewarp(cutoff / self.sample_rate);
+        self.set_coef(f);
+        self.set_mix(1.0, 0.0);
+    }
+
+    pub fn set_highpass(&mut self, cutoff: T) {
+        let f = prewarp(cutoff / self.sample_rate);
+        self.set_coef(f);
+        self.set_mix(-1.0, 1.0);
+    }
+
+    pub fn set_allpass(&mut self, cutoff: T) {
+        let f = prewarp(cutoff / self.sample_rate);
+        self.set_coef(f);
+        self.set_mix(2.0, -1.0);
+    }
+
+    pub fn set_lowshelf(&mut self, cutoff: T, gain: T) {
+        let a = from_db(T::from_f64(0.5) * gain);
+        let f = prewarp(cutoff / self.sample_rate) / a;
+        self.set_coef(f);
+        self.my.set(a * a - T::ONE);
+        self.mx.set(T::ONE);
+    }
+
+    pub fn set_highshelf(&mut self, cutoff: T, gain: T) {
+        let a = from_db(T::from_f64(0.5) * gain);
+        let f = prewarp(cutoff / self.sample_rate) * a;
+        self.set_coef(f);
+        self.my.set(T::ONE - a * a);
+        self.mx.set(a * a);
+    }
+
+    pub fn set_tilt(&mut self, cutoff: T, gain: T) {
+        let a = from_db(T::from_f64(0.5) * gain);
+        let f = prewarp(cutoff / self.sample_rate) * a;
+        self.set_coef(f);
+        self.my.set(T::ONE / a - a);
+        self.mx.set(a);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn process(&mut self, x: T) -> T {
+        let g = self.g.process();
+        let my = self.my.process();
+        let mx = self.mx.process();
+
+        let v = (x - self.s) * g;
+        let y = v + self.s;
+        self.s = y + v;
+
+        mx * x + my * y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_stable, gain_db};
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn filter(set: impl FnOnce(&mut OnePole<f64>)) -> OnePole<f64> {
+        let mut filter = OnePole::new(SAMPLE_RATE);
+        set(&mut filter);
+        filter.immediate();
+        filter
+    }
+
+    #[test]
+    fn lowpass() {
+        let mut f = filter(|f| f.set_lowpass(1000.0));
+        assert!(gain_db(SAMPLE_RATE, 20.0, |x| f.process(x)).abs() < 0.01);
+
+        let mut f = filter(|f| f.set_lowpass(1000.0));
+        assert!((gain_db(SAMPLE_RATE, 1000.0, |x| f.process(x)) + 3.01).abs() < 0.01);
+
+        let mut f = filter(|f| f.set_lowpass(1000.0));
+        assert!(gain_db(SAMPLE_RATE, 20000.0, |x| f.process(x)) < -20.0);
+    }
+
+    #[test]
+    fn highpass() {
+        let mut f = filter(|f| f.set_highpass(1000.0));
+        assert!(gain_db(SAMPLE_RATE, 20.0, |x| f.process(x)) < -30.0);
+
+        let mut f = filter(|f| f.set_highpass(1000.0));
+        assert!((gain_db(SAMPLE_RATE, 1000.0, |x| f.process(x)) + 3.01).abs() < 0.01);
+
+        let mut f = filter(|f| f.set_highpass(1000.0));
+        assert!(gain_db(SAMPLE_RATE, 20000.0, |x| f.process(x)).abs() < 0.01);
+    }
+
+    #[test]
+    fn allpass() {
+        for freq in [20.0, 1000.0, 20000.0] {
+            let mut f = filter(|f| f.set_allpass(1000.0));
+            assert!(gain_db(SAMPLE_RATE, freq, |x| f.process(x)).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn shelves() {
+        let mut f = filter(|f| f.set_lowshelf(1000.0, 6.0));
+        assert!((gain_db(SAMPLE_RATE, 10.0, |x| f.process(x)) - 6.0).abs() < 0.05);
+
+        let mut f = filter(|f| f.set_lowshelf(1000.0, 6.0));
+        assert!(gain_db(SAMPLE_RATE, 20000.0, |x| f.process(x)).abs() < 0.05);
+
+        let mut f = filter(|f| f.set_highshelf(1000.0, -6.0));
+        assert!(gain_db(SAMPLE_RATE, 10.0, |x| f.process(x)).abs() < 0.05);
+
+        let mut f = filter(|f| f.set_highshelf(1000.0, -6.0));
+        assert!((gain_db(SAMPLE_RATE, 23000.0, |x| f.process(x)) + 6.0).abs() < 0.1);
+
+        // Tilt is symmetric around the cutoff.
+        let mut f = filter(|f| f.set_tilt(1000.0, 6.0));
+        let low = gain_db(SAMPLE_RATE, 10.0, |x| f.process(x));
+        let mut f = filter(|f| f.set_tilt(1000.0, 6.0));
+        let high = gain_db(SAMPLE_RATE, 23000.0, |x| f.process(x));
+        assert!((low + 3.0).abs() < 0.1 && (high - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn stability() {
+        for cutoff in [1.0, 100.0, 10000.0, 23000.0, 30000.0] {
+            let mut f = filter(|f| f.set_lowpass(cutoff));
+            assert_stable(|x| f.process(x));
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::resample::*;
+use crate::{AudioConfig, Float};
+
+// Filter quality for each 2x stage of an `Oversampler`. Only the stage at the base rate uses the
+// selected filters, later stages only have to reject content far above the base band and always
+// use the shortest ones.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Quality {
+    // 19 taps up and down
+    Low,
+    // 31 taps up and down
+    Medium,
+    // 19 taps up, 51 taps down. Rejects aliasing as well as `High` for less work, but lets more of
+    // the images above the base band through to the processing.
+    Asymmetric,
+    // 31 taps up, 51 taps down
+    High,
+}
+
+impl Quality {
+    fn stage_latency(self) -> f64 {
+        match self {
+            Quality::Low => Upsampler19::<f32>::LATENCY + Downsampler19::<f32>::LATENCY,
+            Quality::Medium => Upsampler31::<f32>::LATENCY + Downsampler31::<f32>::LATENCY,
+            Quality::Asymmetric => Upsampler19::<f32>::LATENCY + Downsampler51::<f32>::LATENCY,
+            Quality::High => Upsampler31::<f32>::LATENCY + Downsampler51::<f32>::LATENCY,
+        }
+    }
+
+    // Round trip latency of an `Oversampler` in samples at the base rate.
+    pub fn latency(self, factor: usize) -> f64 {
+        let mut latency = 0.0;
+        let mut rate = 1.0;
+        let mut quality = self;
+        while (rate as usize) < factor {
+            latency += quality.stage_latency() / rate;
+            quality = Quality::Low;
+            rate *= 2.0;
+        }
+        latency
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Stage<T> {
+    Low(Upsampler19<T>, Downsampler19<T>),
+    Medium(Upsampler31<T>, Downsampler31<T>),
+    Asymmetric(Upsampler19<T>, Downsampler51<T>),
+    High(Upsampler31<T>, Downsampler51<T>),
+}
+
+impl<T: Float> Stage<T> {
+    fn new(quality: Quality) -> Self {
+        match quality {
+            Quality::Low => Stage::Low(Upsampler19::new(), Downsampler19::new()),
+            Quality::Medium => Stage::Medium(Upsampler31::new(), Downsampler31::new()),
+            Quality::Asymmetric => Stage::Asymmetric(Upsampler19::new(), Downsampler51::new()),
+            Quality::High => Stage::High(Upsampler31::new(), Downsampler51::new()),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Stage::Low(up, down) => {
+                up.reset();
+                down.reset();
+            }
+            Stage::Medium(up, down) => {
+                up.reset();
+                down.reset();
+            }
+            Stage::Asymmetric(up, down) => {
+                up.reset();
+                down.reset();
+            }
+            Stage::High(up, down) => {
+                up.reset();
+                down.reset();
+            }
+        }
+    }
+
+    fn upsample(&mut self, input: &[T], output: &mut [T]) {
+        fn run<T>(input: &[T], output: &mut [T], mut up: impl FnMut(T) -> (T, T))
+        where
+            T: Copy,
+        {
+            for (x, y) in input.iter().zip(output.chunks_exact_mut(2)) {
+                (y[0], y[1]) = up(*x);
+            }
+        }
+
+        match self {
+            Stage::Low(up, _) => run(input, output, |x| up.process(x)),
+            Stage::Medium(up, _) => run(input, output, |x| up.process(x)),
+            Stage::Asymmetric(up, _) => run(input, output, |x| up.process(x)),
+            Stage::High(up, _) => run(input, output, |x| up.process(x)),
+        }
+    }
+
+    fn downsample(&mut self, input: &[T], output: &mut [T]) {
+        fn run<T>(input: &[T], output: &mut [T], mut down: impl FnMut(T, T) -> T)
+        where
+            T: Copy,
+        {
+            for (x, y) in input.chunks_exact(2).zip(output.iter_mut()) {
+                *y = down(x[0], x[1]);
+            }
+        }
+
+        match self {
+            Stage::Low(_, down) => run(input, output, |a, b| down.process(a, b)),
+            Stage::Medium(_, down) => run(input, output, |a, b| down.process(a, b)),
+            Stage::Asymmetric(_, down) => run(input, output, |a, b| down.process(a, b)),
+            Stage::High(_, down) => run(input, output, |a, b| down.process(a, b)),
+        }
+    }
+}
+
+// Runs a mono signal at 2x, 4x or 8x the sample rate by cascading halfband resamplers. A factor
+// of 1 passes the signal through unchanged, so the factor can be switched without changing the
+// processing code. All buffers are allocated up front, processing does not allocate.
+//
+// This wraps a processing closure, not an `Engine`. Engines keep one per channel, call `process`
+// for each channel from `Engine::process`, and call `reset` from `Engine::reset`.
+#[derive(Clone, Debug)]
+pub struct Oversampler<T = f32> {
+    factor: usize,
+    quality: Quality,
+    max_buffer_size: usize,
+    stages: Vec<Stage<T>>,
+    buffers: [Vec<T>; 2],
+}
+
+impl<T: Float> Oversampler<T> {
+    pub fn new(factor: usize, quality: Quality, max_buffer_size: usize) -> Self {
+        assert!(
+            matches!(factor, 1 | 2 | 4 | 8),
+            "oversampling factor must be 1, 2, 4 or 8"
+        );
+
+        let mut stages = Vec::new();
+        let mut stage_quality = quality;
+        for _ in 0..factor.trailing_zeros() {
+            stages.push(Stage::new(stage_quality));
+            stage_quality = Quality::Low;
+        }
+
+        let len = max_buffer_size * factor;
+
+        Self {
+            factor,
+            quality,
+            max_buffer_size,
+            stages,
+            buffers: [vec![T::ZERO; len], vec![T::ZERO; len]],
+        }
+    }
+
+    pub fn from_config(factor: usize, quality: Quality, config: &impl AudioConfig) -> Self {
+        Self::new(factor, quality, config.max_buffer_size())
+    }
+
+    #[must_use]
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    #[must_use]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    // Latency in samples at the base rate. The upsamplers delay by a fractional half sample, so
+    // this is never an integer once oversampling. Hosts only take whole samples, so reporting it
+    // means rounding, which leaves up to half a sample of misalignment against a dry signal
+    // delayed by the reported amount.
+    #[must_use]
+    pub fn latency(&self) -> f64 {
+        self.quality.latency(self.factor)
+    }
+
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    // Upsamples `input` and returns the oversampled signal, `factor` times as long. Must be
+    // followed by a call to `downsample` with a buffer of the same length as `input`.
+    pub fn upsample(&mut self, input: &[T]) -> &mut [T] {
+        assert!(input.len() <= self.max_buffer_size);
+
+        let [a, b] = &mut self.buffers;
+        let (mut src, mut dst) = (a, b);
+
+        let mut len = input.len();
+        src[..len].copy_from_slice(input);
+        for stage in &mut self.stages {
+            stage.upsample(&src[..len], &mut dst[..2 * len]);
+            std::mem::swap(&mut src, &mut dst);
+            len *= 2;
+        }
+
+        &mut src[..len]
+    }
+
+    // Downsamples the signal returned by the previous call to `upsample` into `output`.
+    pub fn downsample(&mut self, output: &mut [T]) {
+        assert!(output.len() <= self.max_buffer_size);
+
+        let [a, b] = &mut self.buffers;
+        let (mut src, mut dst) = if self.stages.len().is_multiple_of(2) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+
+        let mut len = output.len() * self.factor;
+        for stage in self.stages.iter_mut().rev() {
+            stage.downsample(&src[..len], &mut dst[..len / 2]);
+            std::mem::swap(&mut src, &mut dst);
+            len /= 2;
+        }
+
+        output.copy_from_slice(&src[..len]);
+    }
+
+    // Upsamples `samples`, runs `f` on the oversampled signal and downsamples the result back
+    // into `samples`.
+    pub fn process(&mut self, samples: &mut [T], f: impl FnOnce(&mut [T])) {
+        f(self.upsample(samples));
+        self.downsample(samples);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gain_db;
+
+    const QUALITIES: [Quality; 4] = [
+        Quality::Low,
+        Quality::Medium,
+        Quality::Asymmetric,
+        Quality::High,
+    ];
+
+    fn impulse_response(oversampler: &mut Oversampler<f64>, len: usize) -> Vec<f64> {
+        let mut output: Vec<f64> = (0..len).map(|i| if i == 0 { 1.0 } else { 0.0 }).collect();
+        for block in output.chunks_mut(7) {
+            oversampler.process(block, |_| {});
+        }
+        output
+    }
+
+    #[test]
+    fn latency() {
+        for factor in [2, 4, 8] {
+            for quality in QUALITIES {
+                let mut oversampler = Oversampler::new(factor, quality, 7);
+                let response = impulse_response(&mut oversampler, 64);
+
+                // the round trip is linear phase, so the response is centered on the latency
+                let sum: f64 = response.iter().sum();
+                let centroid: f64 =
+                    response.iter().enumerate().map(|(i, h)| i as f64 * h).sum::<f64>() / sum;
+                assert!((sum - 1.0).abs() < 0.01);
+                assert!(
+                    (centroid - oversampler.latency()).abs() < 1e-6,
+                    "{factor}x {quality:?}: {centroid} != {}",
+                    oversampler.latency()
+                );
+            }
+        }
+
+        assert_eq!(Quality::Low.latency(1), 0.0);
+        assert_eq!(Quality::Low.latency(2), 8.5);
+        assert_eq!(Quality::Asymmetric.latency(2), 16.5);
+        assert_eq!(Quality::High.latency(2), 19.5);
+        assert_eq!(Quality::High.latency(4), 19.5 + 8.5 / 2.0);
+    }
+
+    #[test]
+    fn passthrough() {
+        let mut oversampler = Oversampler::new(1, Quality::High, 4);
+        let mut samples = [1.0, 2.0, 3.0, 4.0];
+        oversampler.process(&mut samples, |os| assert_eq!(os.len(), 4));
+        assert_eq!(samples, [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn response() {
+        let sample_rate = 48000.0;
+
+        for factor in [2, 4, 8] {
+            let mut oversampler = Oversampler::new(factor, Quality::Medium, 1);
+            let mut process = |x| {
+                let mut block = [x];
+                oversampler.process(&mut block, |os| assert_eq!(os.len(), factor));
+                block[0]
+            };
+            assert!(gain_db(sample_rate, 1000.0, &mut process).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn reset() {
+        let mut oversampler = Oversampler::new(4, Quality::High, 64);
+        let first = impulse_response(&mut oversampler, 64);
+
+        oversampler.process(&mut [1.0; 16], |os| os.fill(1.0));
+        oversampler.reset();
+
+        let second = impulse_response(&mut oversampler, 64);
+        assert_eq!(first, second);
+    }
+}
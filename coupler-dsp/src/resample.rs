@@ -0,0 +1,267 @@
+// FIR 2x resamplers, windowed sinc
+// coefficients obtained from fir_calc.py
+// upsamplers have a gain of 2 to compensate average power
+//
+// 19 taps:
+// kaiser window, beta = 8
+// 31 taps:
+// kaiser window, beta = 8
+// 51 taps
+// kaiser window, beta = 12
+//
+// Only the nonzero half of each (symmetric) halfband kernel is stored, the center tap is 0.5.
+
+// TODO: simd
+// see: https://jatinchowdhury18.medium.com/fast-fir-filtering-798d5d773838
+
+use crate::Float;
+
+#[rustfmt::skip]
+const KERNEL_19: [f64; 5] = [
+    8.2719205e-5, -2.9712955e-3, 1.8200343e-2, -6.923014e-2, 3.039028e-1,
+];
+
+#[rustfmt::skip]
+const KERNEL_31: [f64; 8] = [
+    -4.9631526e-5, 6.422753e-4, -2.734532e-3, 8.02059e-3, -1.9228276e-2, 4.1538082e-2,
+    -9.122784e-2, 3.130559e-1,
+];
+
+#[rustfmt::skip]
+const KERNEL_51: [f64; 13] = [
+    6.719323e-7, -1.5275027e-5, 8.589304e-5, -3.133141e-4, 8.9382596e-4, -2.158559e-3,
+    4.6128863e-3, -8.990806e-3, 1.6391339e-2, -2.8731763e-2, 5.0480116e-2, -9.765191e-2,
+    3.1539664e-1,
+];
+
+// N must be a power of two.
+#[derive(Clone, Debug)]
+struct Ring<T, const N: usize> {
+    buf: [T; N],
+    pos: usize,
+}
+
+impl<T: Float, const N: usize> Ring<T, N> {
+    fn new() -> Self {
+        Self {
+            buf: [T::ZERO; N],
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, s: T) {
+        self.pos = (self.pos + 1) & (N - 1);
+        self.buf[self.pos] = s;
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> T {
+        self.buf[self.pos.wrapping_sub(i) & (N - 1)]
+    }
+
+    // Convolves the most recent 2 * kernel.len() samples with the symmetric kernel.
+    #[inline]
+    fn convolve(&self, kernel: &[f64], gain: f64) -> T {
+        let last = 2 * kernel.len() - 1;
+
+        let mut s = T::ZERO;
+        for (i, k) in kernel.iter().enumerate() {
+            s += T::from_f64(gain * k) * (self.get(i) + self.get(last - i));
+        }
+        s
+    }
+
+    fn reset(&mut self) {
+        self.buf = [T::ZERO; N];
+    }
+}
+
+macro_rules! upsampler {
+    ($name:ident, $kernel:ident, $len:literal) => {
+        #[derive(Clone, Debug)]
+        pub struct $name<T = f32> {
+            buf: Ring<T, $len>,
+        }
+
+        impl<T: Float> $name<T> {
+            // Latency in samples at the base rate.
+            pub const LATENCY: f64 = $kernel.len() as f64 - 0.5;
+
+            pub fn new() -> Self {
+                Self { buf: Ring::new() }
+            }
+
+            pub fn reset(&mut self) {
+                self.buf.reset();
+            }
+
+            // Returns two samples at twice the sample rate, in order.
+            #[inline]
+            #[must_use]
+            pub fn process(&mut self, s: T) -> (T, T) {
+                self.buf.push(s);
+
+                let s1 = self.buf.convolve(&$kernel, 2.0);
+                let s2 = self.buf.get($kernel.len() - 1);
+
+                (s1, s2)
+            }
+        }
+
+        impl<T: Float> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
+
+macro_rules! downsampler {
+    ($name:ident, $kernel:ident, $len:literal) => {
+        #[derive(Clone, Debug)]
+        pub struct $name<T = f32> {
+            buf1: Ring<T, $len>,
+            buf2: Ring<T, $len>,
+        }
+
+        impl<T: Float> $name<T> {
+            // Latency in samples at the base rate.
+            pub const LATENCY: f64 = $kernel.len() as f64 - 1.0;
+
+            pub fn new() -> Self {
+                Self {
+                    buf1: Ring::new(),
+                    buf2: Ring::new(),
+                }
+            }
+
+            pub fn reset(&mut self) {
+                self.buf1.reset();
+                self.buf2.reset();
+            }
+
+            // Takes two consecutive samples at twice the sample rate.
+            #[inline]
+            #[must_use]
+            pub fn process(&mut self, s1: T, s2: T) -> T {
+                self.buf1.push(s1);
+                self.buf2.push(s2);
+
+                let s = self.buf2.convolve(&$kernel, 1.0);
+                s + T::from_f64(0.5) * self.buf1.get($kernel.len() - 1)
+            }
+        }
+
+        impl<T: Float> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
+
+upsampler!(Upsampler19, KERNEL_19, 16);
+upsampler!(Upsampler31, KERNEL_31, 16);
+
+downsampler!(Downsampler19, KERNEL_19, 16);
+downsampler!(Downsampler31, KERNEL_31, 16);
+downsampler!(Downsampler51, KERNEL_51, 32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::measure_gain;
+
+    use std::f64::consts::TAU;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn sine(freq: f64, sample_rate: f64, len: usize) -> impl Iterator<Item = f64> {
+        (0..len).map(move |i| (TAU * freq * i as f64 / sample_rate).sin())
+    }
+
+    // Gain of the component at `freq` in a signal, skipping the first half to let filters settle.
+    fn gain_db(signal: &[f64], sample_rate: f64, freq: f64) -> f64 {
+        let mut samples = signal.iter().copied();
+        20.0 * measure_gain(sample_rate, freq, |_| samples.next().unwrap()).log10()
+    }
+
+    fn upsample(
+        up: &mut impl FnMut(f64) -> (f64, f64),
+        input: impl Iterator<Item = f64>,
+    ) -> Vec<f64> {
+        input.flat_map(|x| <[f64; 2]>::from(up(x))).collect()
+    }
+
+    fn downsample(down: &mut impl FnMut(f64, f64) -> f64, input: &[f64]) -> Vec<f64> {
+        input.chunks(2).map(|x| down(x[0], x[1])).collect()
+    }
+
+    #[test]
+    fn upsampler_latency() {
+        let mut up = Upsampler19::<f64>::new();
+        let mut impulse = (0..32).map(|i| if i == 0 { 1.0 } else { 0.0 });
+        let output = upsample(&mut |x| up.process(x), &mut impulse);
+
+        let latency = (2.0 * Upsampler19::<f64>::LATENCY) as usize;
+        assert_eq!(output[latency], 1.0);
+        // halfband: every other tap of the interpolation kernel is zero
+        for (i, y) in output.iter().enumerate() {
+            if i != latency && i % 2 == latency % 2 {
+                assert_eq!(*y, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn downsampler_latency() {
+        let mut down = Downsampler51::<f64>::new();
+        let impulse: Vec<f64> = (0..64).map(|i| if i == 0 { 1.0 } else { 0.0 }).collect();
+        let output = downsample(&mut |a, b| down.process(a, b), &impulse);
+
+        let latency = Downsampler51::<f64>::LATENCY as usize;
+        for (i, y) in output.iter().enumerate() {
+            assert_eq!(*y, if i == latency { 0.5 } else { 0.0 });
+        }
+    }
+
+    #[test]
+    fn upsampler_response() {
+        let len = 2 * SAMPLE_RATE as usize;
+
+        let mut up = Upsampler19::<f64>::new();
+        let output = upsample(&mut |x| up.process(x), sine(1000.0, SAMPLE_RATE, len));
+        assert!(gain_db(&output, 2.0 * SAMPLE_RATE, 1000.0).abs() < 0.01);
+
+        // images of the input frequency are rejected
+        let mut up = Upsampler19::<f64>::new();
+        let output = upsample(&mut |x| up.process(x), sine(5000.0, SAMPLE_RATE, len));
+        assert!(gain_db(&output, 2.0 * SAMPLE_RATE, 43000.0) < -50.0);
+
+        let mut up = Upsampler31::<f64>::new();
+        let output = upsample(&mut |x| up.process(x), sine(5000.0, SAMPLE_RATE, len));
+        assert!(gain_db(&output, 2.0 * SAMPLE_RATE, 43000.0) < -70.0);
+    }
+
+    #[test]
+    fn downsampler_response() {
+        let len = 4 * SAMPLE_RATE as usize;
+        let input: Vec<f64> = sine(1000.0, 2.0 * SAMPLE_RATE, len).collect();
+
+        let mut down = Downsampler19::<f64>::new();
+        let output = downsample(&mut |a, b| down.process(a, b), &input);
+        assert!(gain_db(&output, SAMPLE_RATE, 1000.0).abs() < 0.01);
+
+        // content above the new Nyquist frequency is rejected instead of aliasing
+        let input: Vec<f64> = sine(43000.0, 2.0 * SAMPLE_RATE, len).collect();
+
+        let mut down = Downsampler31::<f64>::new();
+        let output = downsample(&mut |a, b| down.process(a, b), &input);
+        assert!(gain_db(&output, SAMPLE_RATE, 5000.0) < -70.0);
+
+        let mut down = Downsampler51::<f64>::new();
+        let output = downsample(&mut |a, b| down.process(a, b), &input);
+        assert!(gain_db(&output, SAMPLE_RATE, 5000.0) < -90.0);
+    }
+}
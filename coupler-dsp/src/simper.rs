@@ -0,0 +1,311 @@
+// after Andrew Simper, Cytomic, 2013
+// see: https://cytomic.com/files/dsp/SvfLinearTrapOptimised2.pdf
+
+use crate::smooth::SmoothLinear;
+use crate::{from_db, prewarp, AudioConfig, Float};
+
+// State variable filter. Coefficient changes are smoothed over 64 samples, call `immediate` to
+// apply them right away.
+#[derive(Clone, Debug)]
+pub struct Filter<T = f32> {
+    sample_rate: T,
+    k: T,
+    g: T,
+    s1: T,
+    s2: T,
+
+    a1: SmoothLinear<T>,
+    a2: SmoothLinear<T>,
+    a3: SmoothLinear<T>,
+
+    m0: SmoothLinear<T>,
+    m1: SmoothLinear<T>,
+    m2: SmoothLinear<T>,
+}
+
+impl<T: Float> Filter<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            sample_rate,
+            a1: SmoothLinear::new_steps(64),
+            a2: SmoothLinear::new_steps(64),
+            a3: SmoothLinear::new_steps(64),
+            m0: SmoothLinear::new_steps(64),
+            m1: SmoothLinear::new_steps(64),
+            m2: SmoothLinear::new_steps(64),
+
+            s1: T::ZERO,
+            s2: T::ZERO,
+            k: T::ZERO,
+            g: T::ZERO,
+        }
+    }
+
+    pub fn from_config(config: &impl AudioConfig) -> Self {
+        Self::new(T::from_f64(config.sample_rate()))
+    }
+
+    pub fn reset_state(&mut self) {
+        self.s1 = T::ZERO;
+        self.s2 = T::ZERO;
+    }
+
+    pub fn immediate(&mut self) {
+        self.a1.immediate();
+        self.a2.immediate();
+        self.a3.immediate();
+        self.m0.immediate();
+        self.m1.immediate();
+        self.m2.immediate();
+    }
+
+    fn set_coefs(&mut self, g: T, k: T) {
+        self.g = g;
+        self.k = k;
+
+        let a1 = T::ONE / (T::ONE + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        self.a1.set(a1);
+        self.a2.set(a2);
+        self.a3.set(a3);
+    }
+
+    fn set_mix(&mut self, m0: T, m1: T, m2: T) {
+        self.m0.set(m0);
+        self.m1.set(m1);
+        self.m2.set(m2);
+    }
+
+    pub fn set_lowpass(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ZERO, T::ZERO, T::ONE);
+    }
+
+    pub fn set_bandpass(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ZERO, T::ONE, T::ZERO);
+    }
+
+    pub fn set_bandpass_norm(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ZERO, k, T::ZERO);
+    }
+
+    pub fn set_highpass(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ONE, -k, -T::ONE);
+    }
+
+    pub fn set_notch(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ONE, -k, T::ZERO);
+    }
+
+    pub fn set_allpass(&mut self, cutoff: T, q: T) {
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+        self.set_mix(T::ONE, -(k + k), T::ZERO);
+    }
+
+    pub fn set_bell(&mut self, cutoff: T, q: T, gain: T) {
+        let a = from_db(T::from_f64(0.5) * gain);
+        let g = prewarp(cutoff / self.sample_rate);
+        let k = T::ONE / (q * a);
+        self.set_coefs(g, k);
+        self.set_mix(T::ONE, k * (a * a - T::ONE), T::ZERO);
+    }
+
+    pub fn set_lowshelf(&mut self, cutoff: T, q: T, gain: T) {
+        let a = from_db(T::from_f64(0.25) * gain);
+        let g = prewarp(cutoff / self.sample_rate) / a;
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+
+        let a2 = a * a;
+        self.set_mix(T::ONE, k * (a2 - T::ONE), a2 * a2 - T::ONE);
+    }
+
+    pub fn set_highshelf(&mut self, cutoff: T, q: T, gain: T) {
+        let a = from_db(T::from_f64(0.25) * gain);
+        let g = prewarp(cutoff / self.sample_rate) * a;
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+
+        let a2 = a * a;
+        self.set_mix(a2 * a2, k * (T::ONE - a2) * a2, T::ONE - a2 * a2);
+    }
+
+    pub fn set_tilt(&mut self, cutoff: T, q: T, gain: T) {
+        let a = from_db(T::from_f64(0.25) * gain);
+        let g = prewarp(cutoff / self.sample_rate) * a;
+        let k = T::ONE / q;
+        self.set_coefs(g, k);
+
+        let a2 = a * a;
+        self.set_mix(a2, k * (T::ONE - a2), T::ONE / a2 - a2);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn process(&mut self, v0: T) -> T {
+        let a1 = self.a1.process();
+        let a2 = self.a2.process();
+        let a3 = self.a3.process();
+        let m0 = self.m0.process();
+        let m1 = self.m1.process();
+        let m2 = self.m2.process();
+
+        let v3 = v0 - self.s2;
+        let v1 = a1 * self.s1 + a2 * v3;
+        let v2 = self.s2 + a2 * self.s1 + a3 * v3;
+        self.s1 = v1 + v1 - self.s1;
+        self.s2 = v2 + v2 - self.s2;
+
+        m0 * v0 + m1 * v1 + m2 * v2
+    }
+
+    // Phase delay in samples at frequency `f`, for the target coefficients.
+    #[must_use]
+    pub fn phase_delay(&self, f: T) -> T {
+        let two = T::from_f64(2.0);
+
+        let g = self.g;
+        let k = self.k;
+        let m0 = self.m0.target();
+        let m1 = self.m1.target();
+        let m2 = self.m2.target();
+
+        let g2 = g * g;
+
+        // denominator coefs
+        let d0 = T::ONE + g2 + g * k;
+        let d1 = two * g2 - two;
+        let d2 = T::ONE + g2 - g * k;
+
+        // numerator coefs
+        let n0 = m0 * d0 + m1 * g + m2 * g2;
+        let n1 = m0 * d1 + m2 * two * g2;
+        let n2 = m0 * d2 - m1 * g + m2 * g2;
+
+        let omega = two * T::PI * f / self.sample_rate;
+
+        // evaluate the transfer function at z = e^{jw}
+        let (sin1, cos1) = (omega.sin(), omega.cos());
+        let (sin2, cos2) = ((two * omega).sin(), (two * omega).cos());
+        let n_phase = (-(n1 * sin1 + n2 * sin2)).atan2(n0 + n1 * cos1 + n2 * cos2);
+        let d_phase = (-(d1 * sin1 + d2 * sin2)).atan2(d0 + d1 * cos1 + d2 * cos2);
+
+        let mut phase = n_phase - d_phase;
+        if phase > T::PI {
+            phase -= two * T::PI;
+        } else if phase < -T::PI {
+            phase += two * T::PI;
+        }
+
+        // phase delay = -phase / w
+        -phase / omega
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_stable, gain_db};
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn filter(set: impl FnOnce(&mut Filter<f64>)) -> Filter<f64> {
+        let mut filter = Filter::new(SAMPLE_RATE);
+        set(&mut filter);
+        filter.immediate();
+        filter
+    }
+
+    fn response(set: impl Fn(&mut Filter<f64>), freq: f64) -> f64 {
+        let mut f = filter(set);
+        gain_db(SAMPLE_RATE, freq, |x| f.process(x))
+    }
+
+    #[test]
+    fn lowpass() {
+        let set = |f: &mut Filter<f64>| f.set_lowpass(1000.0, std::f64::consts::FRAC_1_SQRT_2);
+        assert!(response(set, 20.0).abs() < 0.01);
+        assert!((response(set, 1000.0) + 3.01).abs() < 0.01);
+        // 12 dB/oct
+        assert!((response(set, 8000.0) + 36.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn highpass() {
+        let set = |f: &mut Filter<f64>| f.set_highpass(1000.0, std::f64::consts::FRAC_1_SQRT_2);
+        assert!(response(set, 20000.0).abs() < 0.01);
+        assert!((response(set, 1000.0) + 3.01).abs() < 0.01);
+        assert!(response(set, 100.0) < -38.0);
+    }
+
+    #[test]
+    fn bandpass_and_notch() {
+        let set = |f: &mut Filter<f64>| f.set_bandpass_norm(1000.0, 2.0);
+        assert!(response(set, 1000.0).abs() < 0.01);
+        assert!(response(set, 100.0) < -20.0);
+
+        let set = |f: &mut Filter<f64>| f.set_notch(1000.0, 2.0);
+        assert!(response(set, 1000.0) < -60.0);
+        assert!(response(set, 20.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn allpass() {
+        let set = |f: &mut Filter<f64>| f.set_allpass(1000.0, 0.5);
+        for freq in [20.0, 1000.0, 20000.0] {
+            assert!(response(set, freq).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn bell_and_shelves() {
+        let set = |f: &mut Filter<f64>| f.set_bell(1000.0, 1.0, 6.0);
+        assert!((response(set, 1000.0) - 6.0).abs() < 0.01);
+        assert!(response(set, 20.0).abs() < 0.05);
+
+        let set = |f: &mut Filter<f64>| f.set_lowshelf(1000.0, 0.7, -6.0);
+        assert!((response(set, 10.0) + 6.0).abs() < 0.05);
+        assert!(response(set, 20000.0).abs() < 0.05);
+
+        let set = |f: &mut Filter<f64>| f.set_highshelf(1000.0, 0.7, 6.0);
+        assert!(response(set, 10.0).abs() < 0.05);
+        assert!((response(set, 20000.0) - 6.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn phase_delay() {
+        // At low frequencies, an allpass with damping k delays by 2k / w0.
+        let f = filter(|f| f.set_allpass(1000.0, 0.5));
+        let delay = f.phase_delay(1.0);
+        let expected = 2.0 * 2.0 / (std::f64::consts::TAU * 1000.0 / SAMPLE_RATE);
+        assert!(delay > 0.0 && (delay - expected).abs() / expected < 0.05);
+    }
+
+    #[test]
+    fn stability() {
+        for cutoff in [20.0, 1000.0, 10000.0, 23000.0, 30000.0] {
+            for q in [0.1, 0.707, 10.0] {
+                let mut f = filter(|f| f.set_lowpass(cutoff, q));
+                assert_stable(|x| f.process(x));
+            }
+        }
+    }
+}
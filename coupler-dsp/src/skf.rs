@@ -0,0 +1,161 @@
+// Two pole nonlinear Sallen-key filter
+
+use crate::smooth::SmoothLinear;
+use crate::{prewarp, AudioConfig, Float};
+
+#[derive(Clone, Debug)]
+pub struct Skf<T = f32> {
+    sample_rate: T,
+    f: SmoothLinear<T>,
+    r: T,
+    s1: T,
+    s2: T,
+}
+
+impl<T: Float> Skf<T> {
+    pub fn new(sample_rate: T) -> Self {
+        Self {
+            sample_rate,
+            f: SmoothLinear::new_steps(64),
+            r: T::ZERO,
+            s1: T::ZERO,
+            s2: T::ZERO,
+        }
+    }
+
+    pub fn from_config(config: &impl AudioConfig) -> Self {
+        Self::new(T::from_f64(config.sample_rate()))
+    }
+
+    pub fn reset_state(&mut self) {
+        self.s1 = T::ZERO;
+        self.s2 = T::ZERO;
+    }
+
+    // returns lp, bp, hp
+    #[must_use]
+    fn process_all(&mut self, x: T) -> (T, T, T) {
+        let one = T::ONE;
+        let two = T::from_f64(2.0);
+
+        let f = self.f.process();
+        let r = self.r;
+        let (s1, s2) = (self.s1, self.s2);
+
+        // evaluate the non-linear gains
+
+        // feedback
+        let tk = distdx(r * (s1 - s2));
+
+        // input distortion
+        let t0 = tanhdx(x + tk * r * (s1 - s2));
+
+        // integrators
+        let t1 = tanhdx(s1);
+        let t2 = tanhdx(s2);
+
+        // feedback gains
+        let g1 = one / (one + f * t1);
+        let g2 = one / (one + f * t2);
+
+        // solve for y0
+        let y0 = (t0 * x + r * t0 * tk * (g1 * s1 * (one - f * g2 * t1) - g2 * s2))
+            / (t0 * tk * r * g1 * (f * f * g2 * t1 - f) + one);
+
+        // solve remaining outputs
+        let y1 = t1 * g1 * (s1 + f * y0);
+        let y2 = t2 * g2 * (s2 + f * y1);
+
+        // update state
+        self.s1 += two * f * (y0 - y1);
+        self.s2 += two * f * (y1 - y2);
+
+        (y2, y1 - y2, y0 - two * y1 + y2)
+    }
+
+    #[must_use]
+    pub fn process_lowpass(&mut self, x: T) -> T {
+        self.process_all(x).0
+    }
+
+    #[must_use]
+    pub fn process_bandpass(&mut self, x: T) -> T {
+        self.process_all(x).1
+    }
+
+    #[must_use]
+    pub fn process_highpass(&mut self, x: T) -> T {
+        self.process_all(x).2
+    }
+
+    // `res` ranges from 0 to 1, self-oscillating near the top.
+    pub fn set(&mut self, cutoff: T, res: T) {
+        let cutoff = cutoff.min(T::from_f64(20_000.0));
+        self.f.set(prewarp(cutoff / self.sample_rate));
+        self.r = T::from_f64(2.0) * res;
+    }
+
+    pub fn immediate(&mut self) {
+        self.f.immediate();
+    }
+}
+
+// Padé approximant of tanh(x)/x
+// WolframAlpha: PadeApproximant[Tanh[x]/x,{x,0,{4,4}}]
+fn tanhdx<T: Float>(x: T) -> T {
+    let a = x * x;
+    ((a + T::from_f64(105.0)) * a + T::from_f64(945.0))
+        / ((T::from_f64(15.0) * a + T::from_f64(420.0)) * a + T::from_f64(945.0))
+}
+
+// diode clipper feedback / x
+fn distdx<T: Float>(x: T) -> T {
+    let a = T::from_f64(0.135);
+    a + (T::ONE - a) / (T::ONE + x * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_stable, gain_db};
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn filter(cutoff: f64, res: f64) -> Skf<f64> {
+        let mut skf = Skf::new(SAMPLE_RATE);
+        skf.set(cutoff, res);
+        skf.immediate();
+        skf
+    }
+
+    #[test]
+    fn small_signal_response() {
+        // at low levels the filter is close to linear
+        let level = 1e-3;
+
+        let mut skf = filter(1000.0, 0.0);
+        let lp = |x| skf.process_lowpass(x * level) / level;
+        let passband = gain_db(SAMPLE_RATE, 20.0, lp);
+        assert!(passband.abs() < 0.1, "{passband}");
+
+        let mut skf = filter(1000.0, 0.0);
+        let lp = |x| skf.process_lowpass(x * level) / level;
+        let stopband = gain_db(SAMPLE_RATE, 8000.0, lp);
+        assert!(stopband < -30.0, "{stopband}");
+
+        let mut skf = filter(1000.0, 0.0);
+        let hp = |x| skf.process_highpass(x * level) / level;
+        let stopband = gain_db(SAMPLE_RATE, 50.0, hp);
+        assert!(stopband < -30.0, "{stopband}");
+    }
+
+    #[test]
+    fn stability() {
+        for cutoff in [10.0, 1000.0, 30000.0] {
+            for res in [0.0, 0.5, 0.9] {
+                let mut skf = filter(cutoff, res);
+                assert_stable(|x| skf.process_lowpass(x));
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::Float;
+
+// Linear ramp used to de-zipper filter coefficients.
+#[derive(Clone, Debug)]
+pub struct SmoothLinear<T> {
+    value: T,
+    target: T,
+    step_size: T,
+    steps: usize,
+    timer: usize,
+}
+
+impl<T: Float> SmoothLinear<T> {
+    pub fn new_steps(steps: usize) -> Self {
+        Self {
+            target: T::ZERO,
+            value: T::ZERO,
+            step_size: T::ZERO,
+            steps,
+            timer: 0,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn process(&mut self) -> T {
+        if self.timer > 0 {
+            self.timer -= 1;
+            self.value += self.step_size;
+
+            if self.timer == 0 {
+                self.value = self.target;
+            }
+        }
+        self.value
+    }
+
+    pub fn set(&mut self, v: T) {
+        self.target = v;
+        if (self.target - self.value).abs() < T::from_f64(1e-5) {
+            self.timer = 0;
+            self.value = v;
+        } else {
+            self.timer = self.steps;
+            self.step_size = (self.target - self.value) / T::from_f64(self.steps as f64);
+        }
+    }
+
+    pub fn immediate(&mut self) {
+        self.timer = 0;
+        self.value = self.target;
+    }
+
+    #[must_use]
+    pub fn target(&self) -> T {
+        self.target
+    }
+}
@@ -1,13 +1,14 @@
 use std::cell::RefCell;
 use std::fmt::{self, Formatter};
-use std::io::{self, Read, Write};
+use std::io;
 use std::rc::Rc;
 
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 use coupler::format::clap::*;
 use coupler::format::vst3::*;
 use coupler::params::{ParamId, ParamValue};
+use coupler::state::{State, LEGACY_VERSION};
 use coupler::view::{ParentWindow, RawParent, Size, View};
 use coupler::{buffers::*, bus::*, engine::*, events::*, host::*, params::*, plugin::*, view::*};
 
@@ -18,7 +19,7 @@ use portlight::{
     WindowContext, WindowOptions,
 };
 
-#[derive(Params, Serialize, Deserialize, Clone)]
+#[derive(Params, Deserialize, Clone)]
 struct GainParams {
     #[param(id = 0, name = "Gain", range = 0.0..1.0, format = "{:.2}")]
     gain: f32,
@@ -40,11 +41,14 @@ impl Plugin for Gain {
 
     fn info() -> PluginInfo {
         PluginInfo {
+            id: "rs.coupler.gain".to_string(),
             name: "Gain".to_string(),
             version: "0.1.0".to_string(),
             vendor: "Vendor".to_string(),
             url: "https://example.com".to_string(),
             email: "example@example.com".to_string(),
+            category: Category::Effect,
+            features: vec![Feature::Utility],
             buses: vec![BusInfo {
                 name: "Main".to_string(),
                 dir: BusDir::InOut,
@@ -58,6 +62,8 @@ impl Plugin for Gain {
                 },
             ],
             params: GainParams::params(),
+            presets: Vec::new(),
+            midi_mappings: Vec::new(),
             has_view: true,
         }
     }
@@ -89,18 +95,6 @@ impl Plugin for Gain {
         self.params.display_param(id, value, fmt)
     }
 
-    fn save(&self, output: &mut impl Write) -> io::Result<()> {
-        serde_json::to_writer(output, &self.params)?;
-
-        Ok(())
-    }
-
-    fn load(&mut self, input: &mut impl Read) -> io::Result<()> {
-        self.params = serde_json::from_reader(input)?;
-
-        Ok(())
-    }
-
     fn engine(&mut self, _config: &Config) -> Self::Engine {
         GainEngine {
             params: self.params.clone(),
@@ -110,12 +104,23 @@ impl Plugin for Gain {
     fn view(&mut self, host: ViewHost, parent: &ParentWindow) -> Self::View {
         GainView::open(host, parent, &self.params).unwrap()
     }
+
+    fn migrate(&mut self, from_version: &str, state: &mut State) -> io::Result<()> {
+        // Earlier versions saved the parameters as JSON.
+        if from_version == LEGACY_VERSION {
+            self.params = serde_json::from_slice(&state.data)?;
+            *state = State::capture(self);
+        }
+
+        Ok(())
+    }
 }
 
 impl Vst3Plugin for Gain {
     fn vst3_info() -> Vst3Info {
         Vst3Info {
             class_id: Uuid::from_name("rs.coupler.gain"),
+            controller_id: None,
         }
     }
 }
@@ -128,6 +133,8 @@ impl ClapPlugin for Gain {
     }
 }
 
+const MAX_BLOCK_SIZE: usize = 32;
+
 pub struct GainEngine {
     params: GainParams,
 }
@@ -151,17 +158,18 @@ impl Engine for GainEngine {
 
     fn process(&mut self, buffers: Buffers, events: Events) {
         let mut buffers: (BufferMut,) = buffers.try_into().unwrap();
-        for (mut buffer, events) in buffers.0.split_at_events(events) {
-            for event in events {
-                self.handle_event(event);
-            }
-
-            for sample in buffer.samples() {
-                for channel in sample {
-                    *channel *= self.params.gain;
+        buffers.0.process_blocks(
+            events,
+            MAX_BLOCK_SIZE,
+            &mut self.params,
+            |mut buffer, _, params| {
+                for sample in buffer.samples() {
+                    for channel in sample {
+                        *channel *= params.gain;
+                    }
                 }
-            }
-        }
+            },
+        );
     }
 }
 
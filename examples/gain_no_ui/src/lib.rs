@@ -6,12 +6,15 @@ use serde::{Deserialize, Serialize};
 use coupler::format::clap::*;
 use coupler::format::vst3::*;
 use coupler::params::{ParamId, ParamValue};
+use coupler::state::StateContext;
 use coupler::view::ParentWindow;
 use coupler::{buffers::*, bus::*, engine::*, events::*, host::*, params::*, plugin::*, view::*};
 
+const GAIN: ParamId = 0;
+
 #[derive(Params, Serialize, Deserialize, Clone)]
 struct GainParams {
-    #[param(id = 0, name = "Gain", range = 0.0..1.0, format = "{:.2}")]
+    #[param(id = 0, name = "Gain", range = 0.0..1.0, format = "{:.2}", smooth = "exp", time_ms = 20)]
     gain: f32,
 }
 
@@ -31,11 +34,14 @@ impl Plugin for Gain {
 
     fn info() -> PluginInfo {
         PluginInfo {
+            id: "rs.coupler.gain".to_string(),
             name: "GainNoUI".to_string(),
             version: "0.1.0".to_string(),
             vendor: "Vendor".to_string(),
             url: "https://example.com".to_string(),
             email: "example@example.com".to_string(),
+            category: Category::Effect,
+            features: vec![Feature::Utility],
             buses: vec![BusInfo {
                 name: "Main".to_string(),
                 dir: BusDir::InOut,
@@ -49,6 +55,8 @@ impl Plugin for Gain {
                 },
             ],
             params: GainParams::params(),
+            presets: Vec::new(),
+            midi_mappings: Vec::new(),
             has_view: false,
         }
     }
@@ -80,21 +88,22 @@ impl Plugin for Gain {
         self.params.display_param(id, value, fmt)
     }
 
-    fn save(&self, output: &mut impl Write) -> io::Result<()> {
+    fn save(&self, output: &mut impl Write, _context: StateContext) -> io::Result<()> {
         serde_json::to_writer(output, &self.params)?;
 
         Ok(())
     }
 
-    fn load(&mut self, input: &mut impl Read) -> io::Result<()> {
+    fn load(&mut self, input: &mut impl Read, _context: StateContext) -> io::Result<()> {
         self.params = serde_json::from_reader(input)?;
 
         Ok(())
     }
 
-    fn engine(&mut self, _config: &Config) -> Self::Engine {
+    fn engine(&mut self, config: &Config) -> Self::Engine {
         GainEngine {
             params: self.params.clone(),
+            smoothers: self.params.smoothers(config.sample_rate),
         }
     }
 
@@ -107,6 +116,7 @@ impl Vst3Plugin for Gain {
     fn vst3_info() -> Vst3Info {
         Vst3Info {
             class_id: Uuid(0x276F501B, 0x1D754BAB, 0x8A5F0E8A, 0xC2B1680D),
+            controller_id: None,
         }
     }
 }
@@ -119,39 +129,40 @@ impl ClapPlugin for Gain {
     }
 }
 
+const MAX_BLOCK_SIZE: usize = 32;
+
 pub struct GainEngine {
     params: GainParams,
-}
-
-impl GainEngine {
-    fn handle_event(&mut self, event: &Event) {
-        if let Data::ParamChange { id, value } = event.data {
-            self.params.set_param(id, value);
-        }
-    }
+    smoothers: Smoothers,
 }
 
 impl Engine for GainEngine {
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.smoothers.reset();
+    }
 
     fn flush(&mut self, events: Events) {
         for event in events {
-            self.handle_event(event);
+            self.smoothers.handle_event(&mut self.params, event);
         }
     }
 
     fn process(&mut self, buffers: Buffers, events: Events) {
         let mut buffers: (BufferMut,) = buffers.try_into().unwrap();
-        for (mut buffer, events) in buffers.0.split_at_events(events) {
-            for event in events {
-                self.handle_event(event);
-            }
-
-            for sample in buffer.samples() {
-                for channel in sample {
-                    *channel *= self.params.gain;
+        buffers.0.process_blocks(
+            events,
+            MAX_BLOCK_SIZE,
+            &mut self.params,
+            |mut buffer, events, params| {
+                self.smoothers.update(params, events);
+
+                for sample in buffer.samples() {
+                    let gain = self.smoothers.process(GAIN);
+                    for channel in sample {
+                        *channel *= gain;
+                    }
                 }
-            }
-        }
+            },
+        );
     }
 }
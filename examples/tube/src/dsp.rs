@@ -1,13 +1,5 @@
 use std::f32::consts::*;
 
-pub mod delayline;
-pub mod env;
-pub mod onepole;
-pub mod resample;
-pub mod simper;
-pub mod skf;
-pub mod smooth;
-
 pub const TWO_PI: f32 = TAU;
 pub const BUTTERWORTH_Q: f32 = FRAC_1_SQRT_2;
 pub const DECIBEL_FACTOR: f32 = LOG2_10 / 20.;
@@ -1,26 +1,28 @@
-use crate::dsp::delayline::DelayLine;
-use crate::dsp::onepole::OnePole;
-use crate::dsp::resample::*;
-use crate::dsp::simper::Filter;
-use crate::dsp::smooth::SmoothBuffer;
 use crate::dsp::*;
-use crate::Params;
+use crate::{Params, BALANCE, GAIN, GAIN_OUT};
 
-use coupler::buffers::iter::BlockIterator;
-use coupler::buffers::iter::IntoBlocks;
 use coupler::buffers::BufferMut;
 use coupler::buffers::Buffers;
+use coupler::dsp::delayline::DelayLine;
+use coupler::dsp::onepole::OnePole;
+use coupler::dsp::oversample::{Oversampler, Quality};
+use coupler::dsp::simper::Filter;
 use coupler::engine::Config;
 use coupler::engine::Engine;
-use coupler::events::Data;
 use coupler::events::Event;
 use coupler::events::Events;
-use coupler::params::Params as CouplerParams;
+use coupler::params::{SmoothParams, Smoothers};
 use log::info;
 
 pub const MAX_BUF_SIZE: usize = 64;
-pub const RESAMPLE_FACTOR: f32 = 2.0;
-pub const RESAMPLE_DELAY: u64 = 16;
+pub const OVERSAMPLE_FACTOR: usize = 2;
+pub const OVERSAMPLE_QUALITY: Quality = Quality::Asymmetric;
+
+// Latency of the wet signal in whole samples. The wet signal is actually delayed by 16.5 samples,
+// so it ends up half a sample behind the dry signal, which is delayed by 16.
+pub fn latency() -> u64 {
+    OVERSAMPLE_QUALITY.latency(OVERSAMPLE_FACTOR).floor() as u64
+}
 
 fn tube(x: f32) -> f32 {
     let w = x.max(0.0);
@@ -28,11 +30,38 @@ fn tube(x: f32) -> f32 {
     softclip(s)
 }
 
-struct Track {
-    gain_in: SmoothBuffer,
-    gain_out: SmoothBuffer,
-    balance: SmoothBuffer,
+// Smoothed parameter values for one block, shared by all tracks.
+struct Controls {
+    balance: [f32; MAX_BUF_SIZE],
+    gain_in: [f32; MAX_BUF_SIZE],
+    gain_out: [f32; MAX_BUF_SIZE],
+}
+
+impl Controls {
+    fn new() -> Self {
+        Controls {
+            balance: [0.0; MAX_BUF_SIZE],
+            gain_in: [0.0; MAX_BUF_SIZE],
+            gain_out: [0.0; MAX_BUF_SIZE],
+        }
+    }
+
+    fn fill(&mut self, smoothers: &mut Smoothers, len: usize) {
+        for i in 0..len {
+            let gain = smoothers.process(GAIN);
+            let gain_out = smoothers.process(GAIN_OUT);
+
+            let compensation = if gain > 0.0 { -gain * 0.75 } else { -gain };
+
+            self.balance[i] = smoothers.process(BALANCE);
+            self.gain_in[i] = from_db(gain);
+            self.gain_out[i] = from_db(compensation) * from_db(gain_out);
+        }
+    }
+}
 
+// The part of a track that runs at the oversampled rate.
+struct Saturator {
     release: f32,
     peak: f32,
 
@@ -42,15 +71,10 @@ struct Track {
 
     pre_filter: OnePole,
     post_filter: OnePole,
-
-    dry_delay: DelayLine,
-    upsampler: Upsampler19,
-    downsampler: Downsampler51,
 }
 
-impl Track {
-    fn new(sample_rate: f32) -> Self {
-        let internal_sr = sample_rate * RESAMPLE_FACTOR;
+impl Saturator {
+    fn new(internal_sr: f32) -> Self {
         let mut peak_input_filter = Filter::new(internal_sr);
         peak_input_filter.set_highpass(50.0, 0.7);
 
@@ -69,11 +93,7 @@ impl Track {
         let release = time_constant(360.0, internal_sr);
         info!("release = {release:?}");
 
-        Track {
-            gain_in: SmoothBuffer::new(),
-            gain_out: SmoothBuffer::new(),
-            balance: SmoothBuffer::new(),
-
+        Saturator {
             release,
             peak: 0.0,
 
@@ -82,15 +102,20 @@ impl Track {
             highpass_out,
             pre_filter,
             post_filter,
-
-            dry_delay: DelayLine::new_absolute(sample_rate, 32),
-            upsampler: Upsampler19::default(),
-            downsampler: Downsampler51::default(),
         }
     }
 
-    fn process_sample(&mut self, i: usize, x: f32) -> f32 {
-        let mut s = x * self.gain_in.get(i);
+    fn reset(&mut self) {
+        self.peak = 0.0;
+        self.peak_input_filter.reset_state();
+        self.peak_filter.reset_state();
+        self.highpass_out.reset_state();
+        self.pre_filter.reset_state();
+        self.post_filter.reset_state();
+    }
+
+    fn process_sample(&mut self, x: f32, gain_in: f32, gain_out: f32) -> f32 {
+        let mut s = x * gain_in;
 
         let peak = self.peak_input_filter.process(s).abs();
 
@@ -108,53 +133,67 @@ impl Track {
 
         out = self.post_filter.process(out);
         out = self.highpass_out.process(out);
-        out *= self.gain_out.get(i);
+        out *= gain_out;
 
         out
     }
 
-    fn process(&mut self, samples: &mut [f32]) {
-        let n = samples.len();
-        self.gain_in.process_buffer(n);
-        self.gain_out.process_buffer(n);
-        self.balance.process_buffer(n);
-
+    // `samples` is at the oversampled rate, the controls are indexed at the base rate.
+    fn process(&mut self, samples: &mut [f32], controls: &Controls) {
         for (i, sample) in samples.iter_mut().enumerate() {
-            let (u1, u2) = self.upsampler.process(*sample);
-
-            let y1 = self.process_sample(i, u1);
-            let y2 = self.process_sample(i, u2);
+            let j = i / OVERSAMPLE_FACTOR;
+            *sample = self.process_sample(*sample, controls.gain_in[j], controls.gain_out[j]);
+        }
+    }
+}
 
-            let y = self.downsampler.process(y1, y2);
+struct Track {
+    saturator: Saturator,
+    oversampler: Oversampler,
+    dry_delay: DelayLine,
+    dry_buf: [f32; MAX_BUF_SIZE],
+}
 
-            self.dry_delay.push(*sample);
-            let dry = self.dry_delay.go_back_int_s(RESAMPLE_DELAY as isize);
+impl Track {
+    fn new(sample_rate: f32) -> Self {
+        let internal_sr = sample_rate * OVERSAMPLE_FACTOR as f32;
 
-            let balance = self.balance.get(i);
-            *sample = lerp(dry, y, balance);
+        Track {
+            saturator: Saturator::new(internal_sr),
+            oversampler: Oversampler::new(OVERSAMPLE_FACTOR, OVERSAMPLE_QUALITY, MAX_BUF_SIZE),
+            dry_delay: DelayLine::new_absolute(sample_rate, latency() as usize + 1),
+            dry_buf: [0.0; MAX_BUF_SIZE],
         }
     }
 
-    fn set_params(&mut self, params: &Params) {
-        self.balance.set(params.balance);
-        self.gain_in.set(from_db(params.gain));
-
-        let mut gain_out = 1.0;
+    fn reset(&mut self) {
+        self.saturator.reset();
+        self.oversampler.reset();
+        self.dry_delay.flush();
+    }
 
-        if params.gain > 0.0 {
-            gain_out *= from_db(-params.gain * 0.75);
-        } else {
-            gain_out *= from_db(-params.gain);
+    fn process(&mut self, samples: &mut [f32], controls: &Controls) {
+        let delay = latency() as usize;
+        for (dry, sample) in self.dry_buf.iter_mut().zip(samples.iter()) {
+            self.dry_delay.push(*sample);
+            *dry = self.dry_delay.go_back_int_s(delay);
         }
 
-        gain_out *= from_db(params.gain_out);
+        let saturator = &mut self.saturator;
+        self.oversampler
+            .process(samples, |samples| saturator.process(samples, controls));
 
-        self.gain_out.set(gain_out);
+        let dry = self.dry_buf.iter().zip(&controls.balance);
+        for (sample, (dry, balance)) in samples.iter_mut().zip(dry) {
+            *sample = lerp(*dry, *sample, *balance);
+        }
     }
 }
 
 pub struct PluginEngine {
     params: Params,
+    smoothers: Smoothers,
+    controls: Controls,
     tracks: Vec<Track>,
 }
 
@@ -175,20 +214,26 @@ impl PluginEngine {
             tracks.push(Track::new(sample_rate));
         }
 
-        PluginEngine { params, tracks }
+        PluginEngine {
+            smoothers: params.smoothers(config.sample_rate),
+            controls: Controls::new(),
+            params,
+            tracks,
+        }
     }
 
     fn handle_event(&mut self, event: &Event) {
-        if let Data::ParamChange { id, value } = event.data {
-            info!("id = {id:?}, value = {value:?}");
-
-            self.params.set_param(id, value);
-        }
+        self.smoothers.handle_event(&mut self.params, event);
     }
 }
 
 impl Engine for PluginEngine {
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.smoothers.reset();
+        for track in &mut self.tracks {
+            track.reset();
+        }
+    }
 
     fn flush(&mut self, events: Events) {
         for event in events {
@@ -199,22 +244,21 @@ impl Engine for PluginEngine {
     fn process(&mut self, buffers: Buffers, events: Events) {
         let mut buffers: (BufferMut,) = buffers.try_into().unwrap();
 
-        for (buffer, events) in buffers.0.split_at_events(events) {
-            for event in events {
-                self.handle_event(event);
-            }
-
-            if events.len() > 0 {
-                for track in &mut self.tracks {
-                    track.set_params(&self.params);
+        let smoothers = &mut self.smoothers;
+        let controls = &mut self.controls;
+        let tracks = &mut self.tracks;
+        buffers.0.process_blocks(
+            events,
+            MAX_BUF_SIZE,
+            &mut self.params,
+            |block, events, params| {
+                smoothers.update(params, events);
+                controls.fill(smoothers, block.len());
+
+                for (buffer, track) in block.into_iter().zip(tracks.iter_mut()) {
+                    track.process(buffer, controls);
                 }
-            }
-
-            for block in buffer.into_blocks().chunks(MAX_BUF_SIZE) {
-                for (buffer, track) in block.into_iter().zip(self.tracks.iter_mut()) {
-                    track.process(buffer);
-                }
-            }
-        }
+            },
+        );
     }
 }
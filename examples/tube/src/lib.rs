@@ -7,40 +7,43 @@ mod dsp;
 use coupler::params::Enum;
 use coupler::params::ParamValue;
 use engine::PluginEngine;
-use engine::RESAMPLE_DELAY;
 use logging::init_logging;
 
+use coupler::params::Params as CouplerParams;
+use coupler::plugin::Plugin as CouplerPlugin;
 use std::fmt;
 use std::fmt::Formatter;
-use std::io::{self, Read, Write};
-
-use serde::{Deserialize, Serialize};
+use std::io;
 
-use coupler::params::Params as CouplerParams;
-use coupler::plugin::Plugin as CouplerPlugin;
+use serde::Deserialize;
 
 use coupler::engine::Config;
 use coupler::format::clap::*;
 use coupler::format::vst3::*;
 use coupler::params::ParamId;
-use coupler::plugin::PluginInfo;
+use coupler::plugin::{Category, Feature, PluginInfo};
+use coupler::state::{State, LEGACY_VERSION};
 use coupler::view::ParentWindow;
 use coupler::{bus::*, host::*, view::*};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Enum)]
+#[derive(Debug, Clone, Deserialize, Enum)]
 enum Mode {
     A,
     B,
     C,
 }
 
-#[derive(CouplerParams, Serialize, Deserialize, Clone)]
+const BALANCE: ParamId = 0;
+const GAIN: ParamId = 1;
+const GAIN_OUT: ParamId = 2;
+
+#[derive(CouplerParams, Deserialize, Clone)]
 pub struct Params {
-    #[param(id = 0, name = "Dry/Wet", range = 0.0..1.0, format = "{:.2}")]
+    #[param(id = 0, name = "Dry/Wet", range = 0.0..1.0, format = "{:.2}", smooth = "linear")]
     balance: f32,
-    #[param(id = 1, name = "Heat", range = -12.0..12.0, format = "{:.1}dB")]
+    #[param(id = 1, name = "Heat", range = -12.0..12.0, format = "{:.1}dB", smooth = "linear")]
     gain: f32,
-    #[param(id = 2, name = "Output gain", range = 0.0..12.0, format = "{:.1}dB")]
+    #[param(id = 2, name = "Output gain", range = 0.0..12.0, format = "{:.1}dB", smooth = "linear")]
     gain_out: f32,
     #[param(id = 3, name = "Type")]
     mode: Mode,
@@ -67,11 +70,14 @@ impl CouplerPlugin for Plugin {
 
     fn info() -> PluginInfo {
         PluginInfo {
+            id: "rs.sintel.tube".to_string(),
             name: "Sintel's Secret Mojo Sauce Tube".to_string(),
             version: "0.1.0".to_string(),
             vendor: "Sintel".to_string(),
             url: "https://sintel.website".to_string(),
             email: "sintel.inquiries@gmail.com".to_string(),
+            category: Category::Effect,
+            features: vec![Feature::Distortion],
             buses: vec![BusInfo {
                 name: "Main".to_string(),
                 dir: BusDir::InOut,
@@ -85,6 +91,8 @@ impl CouplerPlugin for Plugin {
                 },
             ],
             params: Params::params(),
+            presets: Vec::new(),
+            midi_mappings: Vec::new(),
             has_view: false,
         }
     }
@@ -117,18 +125,6 @@ impl CouplerPlugin for Plugin {
         self.params.display_param(id, value, fmt)
     }
 
-    fn save(&self, output: &mut impl Write) -> io::Result<()> {
-        serde_json::to_writer(output, &self.params)?;
-
-        Ok(())
-    }
-
-    fn load(&mut self, input: &mut impl Read) -> io::Result<()> {
-        self.params = serde_json::from_reader(input)?;
-
-        Ok(())
-    }
-
     fn engine(&mut self, config: &Config) -> Self::Engine {
         PluginEngine::new(self.params.clone(), config.clone())
     }
@@ -138,7 +134,17 @@ impl CouplerPlugin for Plugin {
     }
 
     fn latency(&self, _config: &Config) -> u64 {
-        RESAMPLE_DELAY
+        engine::latency()
+    }
+
+    fn migrate(&mut self, from_version: &str, state: &mut State) -> io::Result<()> {
+        // Earlier versions saved the parameters as JSON.
+        if from_version == LEGACY_VERSION {
+            self.params = serde_json::from_slice(&state.data)?;
+            *state = State::capture(self);
+        }
+
+        Ok(())
     }
 }
 
@@ -146,6 +152,7 @@ impl Vst3Plugin for Plugin {
     fn vst3_info() -> Vst3Info {
         Vst3Info {
             class_id: Uuid(0xDB55AE3A, 0x66F446B6, 0xBF586169, 0x83FD9853),
+            controller_id: None,
         }
     }
 }
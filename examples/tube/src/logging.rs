@@ -1,11 +1,17 @@
+use coupler::logging::{self, LogConfig};
 use log::info;
 use log::LevelFilter;
 
 pub fn init_logging() {
     #[cfg(debug_assertions)]
-    simple_logging::log_to_file("out.log", LevelFilter::Info).ok();
+    let level = LevelFilter::Info;
     #[cfg(not(debug_assertions))]
-    simple_logging::log_to_file("out.log", LevelFilter::Error).ok();
+    let level = LevelFilter::Error;
+
+    logging::init(LogConfig {
+        level,
+        file_name: Some("tube.log".to_string()),
+    });
 
     log_panics::init();
     info!("Logging initialized");
@@ -6,6 +6,7 @@ pub mod convert;
 pub mod iter;
 
 use crate::events::Events;
+use crate::params::Params;
 use iter::{BlockIterator, IntoBlocks, IntoSamples};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -134,6 +135,20 @@ impl<'a, 'b> Buffers<'a, 'b> {
     ) -> iter::SplitAtEvents<'e, iter::BlocksIter<'a, 'c>> {
         self.reborrow().into_blocks().split_at_events(events)
     }
+
+    #[inline]
+    pub fn process_blocks<'c, P, F>(
+        &'c mut self,
+        events: Events,
+        max_len: usize,
+        params: &mut P,
+        f: F,
+    ) where
+        P: Params,
+        F: FnMut(Buffers<'a, 'c>, Events, &P),
+    {
+        self.reborrow().into_blocks().process_blocks(events, max_len, params, f)
+    }
 }
 
 impl<'a, 'b> IntoIterator for Buffers<'a, 'b> {
@@ -333,6 +348,15 @@ impl<'a, 'b> Buffer<'a, 'b> {
     ) -> iter::SplitAtEvents<'e, iter::BlockIter<'a, 'b>> {
         self.into_blocks().split_at_events(events)
     }
+
+    #[inline]
+    pub fn process_blocks<P, F>(&self, events: Events, max_len: usize, params: &mut P, f: F)
+    where
+        P: Params,
+        F: FnMut(Buffer<'a, 'b>, Events, &P),
+    {
+        self.into_blocks().process_blocks(events, max_len, params, f)
+    }
 }
 
 impl<'a, 'b> Index<usize> for Buffer<'a, 'b> {
@@ -502,6 +526,20 @@ impl<'a, 'b> BufferMut<'a, 'b> {
     ) -> iter::SplitAtEvents<'e, iter::BlockIterMut<'a, 'c>> {
         self.reborrow().into_blocks().split_at_events(events)
     }
+
+    #[inline]
+    pub fn process_blocks<'c, P, F>(
+        &'c mut self,
+        events: Events,
+        max_len: usize,
+        params: &mut P,
+        f: F,
+    ) where
+        P: Params,
+        F: FnMut(BufferMut<'a, 'c>, Events, &P),
+    {
+        self.reborrow().into_blocks().process_blocks(events, max_len, params, f)
+    }
 }
 
 impl<'a, 'b> Index<usize> for BufferMut<'a, 'b> {
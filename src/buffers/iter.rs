@@ -1,7 +1,8 @@
 use std::marker::PhantomData;
 
 use super::{Buffer, BufferData, BufferMut, Buffers, Sample, SampleMut, Samples};
-use crate::events::Events;
+use crate::events::{Data, Events};
+use crate::params::Params;
 
 pub trait IntoSamples {
     type Sample;
@@ -178,6 +179,60 @@ pub trait BlockIterator {
     {
         Chunks::new(self, length)
     }
+
+    /// Splits the blocks at event boundaries and into chunks of at most `max_len` samples,
+    /// applying any `ParamChange` events to `params` before the block they start.
+    ///
+    /// The callback receives each block along with the events that start it (empty for the
+    /// remaining chunks of a split block) and a snapshot of `params` which stays constant for the
+    /// duration of the block.
+    fn process_blocks<P, F>(mut self, events: Events, max_len: usize, params: &mut P, mut f: F)
+    where
+        Self: Sized,
+        P: Params,
+        F: FnMut(Self::Block, Events, &P),
+    {
+        assert!(max_len > 0);
+
+        let mut events = events;
+        let mut time = 0;
+        loop {
+            let remaining = self.len();
+
+            // Once we've reached the end of the buffer, deliver all remaining events in one go:
+            let count = if remaining == 0 {
+                events.len()
+            } else {
+                events.into_iter().take_while(|event| event.time <= time).count()
+            };
+
+            let current = events.slice(..count).unwrap();
+            events = events.slice(count..).unwrap();
+
+            for event in current {
+                if let Data::ParamChange { id, value } = event.data {
+                    params.set_param(id, value);
+                }
+            }
+
+            if remaining == 0 {
+                if !current.is_empty() {
+                    f(self.next_block(0), current, params);
+                }
+
+                break;
+            }
+
+            let mut len = remaining.min(max_len);
+            if let Some(next) = events.into_iter().next() {
+                len = len.min((next.time - time) as usize);
+            }
+
+            f(self.next_block(len), current, params);
+
+            time += len as i64;
+        }
+    }
 }
 
 impl<'a, 'b> IntoBlocks for Buffers<'a, 'b> {
@@ -414,3 +469,55 @@ impl<B: BlockIterator> Iterator for Chunks<B> {
         Some(buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Event;
+    use crate::params::test_params::TestParams;
+    use crate::params::ParamValue;
+
+    fn param_change(time: i64, value: ParamValue) -> Event {
+        Event {
+            time,
+            data: Data::ParamChange { id: 0, value },
+        }
+    }
+
+    #[test]
+    fn process_blocks() {
+        let mut data = vec![0.0f32; 80];
+        let ptrs = [data.as_mut_ptr()];
+        let buffer = unsafe { BufferMut::from_raw_parts(&ptrs, 0, data.len()) };
+
+        let events = [
+            param_change(0, 0.25),
+            param_change(40, 0.5),
+            param_change(40, 0.75),
+            param_change(100, 1.0),
+        ];
+
+        let mut params = TestParams::default();
+        let mut blocks = Vec::new();
+        buffer.into_blocks().process_blocks(
+            Events::new(&events),
+            32,
+            &mut params,
+            |block, events, params| {
+                blocks.push((block.len(), events.len(), params.a));
+            },
+        );
+
+        assert_eq!(
+            blocks,
+            [
+                (32, 1, 0.25),
+                (8, 0, 0.25),
+                (32, 2, 0.75),
+                (8, 0, 0.75),
+                (0, 1, 1.0),
+            ]
+        );
+        assert_eq!(params.a, 1.0);
+    }
+}
@@ -29,3 +29,26 @@ impl Format {
         }
     }
 }
+
+// A bus with an output side is considered active if its output port is, since an inactive input
+// side only means that the bus starts out silent.
+pub(crate) fn active_buses(
+    buses: &[BusInfo],
+    inputs_active: &[bool],
+    outputs_active: &[bool],
+) -> Vec<bool> {
+    let mut inputs_active = inputs_active.iter();
+    let mut outputs_active = outputs_active.iter();
+
+    buses
+        .iter()
+        .map(|bus| match bus.dir {
+            BusDir::In => *inputs_active.next().unwrap(),
+            BusDir::Out => *outputs_active.next().unwrap(),
+            BusDir::InOut => {
+                inputs_active.next();
+                *outputs_active.next().unwrap()
+            }
+        })
+        .collect()
+}
@@ -1,16 +1,99 @@
+use std::sync::Arc;
+
 use crate::buffers::Buffers;
 use crate::bus::Layout;
-use crate::events::Events;
+use crate::events::{Events, OutputEvents};
+
+/// How the host is running the engine.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ProcessMode {
+    /// Processing live audio, which has to keep up with real time.
+    #[default]
+    Realtime,
+    /// Processing ahead of playback. This still has to keep up on average, but may be done in
+    /// larger blocks.
+    Prefetch,
+    /// Rendering offline, e.g. during an export. Engines can switch to more expensive processing,
+    /// such as higher oversampling.
+    Offline,
+}
+
+pub trait ThreadPoolInner {
+    /// Returns `false` if the host couldn't run the tasks.
+    fn run(&self, count: usize, task: &(dyn Fn(usize) + Sync)) -> bool;
+}
+
+/// Spreads work across the host's worker threads.
+#[derive(Clone, Default)]
+pub struct ThreadPool {
+    inner: Option<Arc<dyn ThreadPoolInner + Send + Sync>>,
+}
+
+impl ThreadPool {
+    pub fn from_inner(inner: Arc<dyn ThreadPoolInner + Send + Sync>) -> ThreadPool {
+        ThreadPool { inner: Some(inner) }
+    }
+
+    /// Calls `task` once for each index in `0..count`, possibly in parallel, and returns when all
+    /// of the calls have finished. If the host has no thread pool, or can't use it right now, the
+    /// tasks are run one after another on the current thread.
+    ///
+    /// Must be called from [`Engine::process`].
+    pub fn run(&self, count: usize, task: impl Fn(usize) + Sync) {
+        if count == 0 {
+            return;
+        }
+
+        if let Some(inner) = &self.inner {
+            if inner.run(count, &task) {
+                return;
+            }
+        }
+
+        for index in 0..count {
+            task(index);
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Config {
     pub layout: Layout,
     pub sample_rate: f64,
     pub max_buffer_size: usize,
+    pub process_mode: ProcessMode,
+    pub thread_pool: ThreadPool,
+    /// Whether each bus in [`PluginInfo::buses`](crate::plugin::PluginInfo::buses) is active.
+    /// Inactive input buses contain silence, and anything written to inactive output buses is
+    /// discarded.
+    pub active_buses: Vec<bool>,
+}
+
+#[cfg(feature = "dsp")]
+impl coupler_dsp::AudioConfig for Config {
+    fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    fn max_buffer_size(&self) -> usize {
+        self.max_buffer_size
+    }
 }
 
 pub trait Engine: Send + Sized + 'static {
     fn reset(&mut self);
     fn flush(&mut self, events: Events);
     fn process(&mut self, buffers: Buffers, events: Events);
+
+    /// Called before [`process`](Self::process) when the host has switched to a different
+    /// [`ProcessMode`] since the engine was created with [`Config::process_mode`] or since the
+    /// last call.
+    #[allow(unused_variables)]
+    fn process_mode_changed(&mut self, mode: ProcessMode) {}
+
+    /// Called after each call to [`process`](Self::process) or [`flush`](Self::flush) to collect
+    /// events produced by the engine, such as [`Data::NoteEnd`](crate::events::Data::NoteEnd).
+    /// Event times are relative to the start of that block.
+    #[allow(unused_variables)]
+    fn output_events(&mut self, events: &mut OutputEvents) {}
 }
@@ -12,7 +12,27 @@ pub struct Event {
 #[derive(Copy, Clone, Debug)]
 #[non_exhaustive]
 pub enum Data {
-    ParamChange { id: ParamId, value: ParamValue },
+    ParamChange {
+        id: ParamId,
+        value: ParamValue,
+    },
+    /// The parameter moves linearly from its current value to `value` over the `len` samples
+    /// starting at the event time. A ramp is always followed by a `ParamChange` to `value` at the
+    /// end of the ramp, so engines which do not render ramps can ignore them.
+    ParamRamp {
+        id: ParamId,
+        value: ParamValue,
+        len: u32,
+    },
+    /// Sent by the engine through [`OutputEvents`] when a voice has finished playing, so that the
+    /// host can stop polyphonic modulation for it.
+    NoteEnd {
+        /// The note id of the voice, if it was started by a note with an id.
+        note_id: Option<i32>,
+        port: u16,
+        channel: u8,
+        key: u8,
+    },
 }
 
 #[derive(Copy, Clone)]
@@ -53,6 +73,23 @@ impl<'a> Events<'a> {
     }
 }
 
+/// Collects events sent from the engine to the host.
+pub struct OutputEvents<'a> {
+    events: &'a mut Vec<Event>,
+}
+
+impl<'a> OutputEvents<'a> {
+    #[inline]
+    pub fn new(events: &'a mut Vec<Event>) -> OutputEvents<'a> {
+        OutputEvents { events }
+    }
+
+    #[inline]
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+}
+
 impl<'a> Index<usize> for Events<'a> {
     type Output = Event;
 
@@ -1,49 +1,62 @@
 use std::cell::UnsafeCell;
 use std::ffi::{c_char, c_void, CStr, CString};
-use std::marker::PhantomData;
 use std::ptr;
 use std::sync::Arc;
 
-use clap_sys::{host::*, plugin::*, plugin_factory::*, version::*};
+use clap_sys::factory::preset_discovery::*;
+use clap_sys::{factory::plugin_factory::*, host::*, plugin::*, version::*};
 
 use super::instance::Instance;
-use super::ClapPlugin;
-use crate::plugin::{Plugin, PluginInfo};
-
-struct FactoryState {
-    descriptor: clap_plugin_descriptor,
-    info: Arc<PluginInfo>,
-}
+use super::preset::PresetFactory;
+use super::{ClapInfo, ClapPlugin};
+use crate::logging;
+use crate::plugin::{Category, Feature, Plugin, PluginInfo};
 
 #[doc(hidden)]
-#[repr(C)]
-pub struct Factory<P> {
-    #[allow(unused)]
-    factory: clap_plugin_factory,
-    state: UnsafeCell<Option<FactoryState>>,
-    _marker: PhantomData<P>,
+pub struct FactoryEntry {
+    info: fn() -> PluginInfo,
+    clap_info: fn() -> ClapInfo,
+    create: fn(
+        desc: *const clap_plugin_descriptor,
+        info: &Arc<PluginInfo>,
+        host: *const clap_host,
+    ) -> *const clap_plugin,
 }
 
-unsafe impl<P> Sync for Factory<P> {}
-
-#[doc(hidden)]
-impl<P: Plugin + ClapPlugin> Factory<P> {
-    #[allow(clippy::new_without_default)]
-    pub const fn new() -> Self {
-        Factory {
-            factory: clap_plugin_factory {
-                get_plugin_count: Some(Self::get_plugin_count),
-                get_plugin_descriptor: Some(Self::get_plugin_descriptor),
-                create_plugin: Some(Self::create_plugin),
-            },
-            state: UnsafeCell::new(None),
-            _marker: PhantomData,
+impl FactoryEntry {
+    pub const fn new<P: Plugin + ClapPlugin>() -> FactoryEntry {
+        FactoryEntry {
+            info: P::info,
+            clap_info: P::clap_info,
+            create: Self::create::<P>,
         }
     }
 
-    pub unsafe fn init(&self) -> bool {
-        let info = Arc::new(P::info());
-        let clap_info = P::clap_info();
+    fn create<P: Plugin>(
+        desc: *const clap_plugin_descriptor,
+        info: &Arc<PluginInfo>,
+        host: *const clap_host,
+    ) -> *const clap_plugin {
+        let instance = Box::new(Instance::<P>::new(desc, info, host));
+        Box::into_raw(instance) as *const clap_plugin
+    }
+}
+
+struct PluginState {
+    descriptor: clap_plugin_descriptor,
+    info: Arc<PluginInfo>,
+    #[allow(unused)]
+    features: Vec<CString>,
+    #[allow(unused)]
+    feature_ptrs: Vec<*const c_char>,
+    create:
+        fn(*const clap_plugin_descriptor, &Arc<PluginInfo>, *const clap_host) -> *const clap_plugin,
+}
+
+impl PluginState {
+    fn new(entry: &FactoryEntry) -> PluginState {
+        let info = Arc::new((entry.info)());
+        let clap_info = (entry.clap_info)();
 
         let id = CString::new(&*clap_info.id).unwrap().into_raw();
         let name = CString::new(&*info.name).unwrap().into_raw();
@@ -52,9 +65,16 @@ impl<P: Plugin + ClapPlugin> Factory<P> {
         let version = CString::new(&*info.version).unwrap().into_raw();
 
         const EMPTY: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"\0") };
-        const FEATURES: &[*const c_char] = &[ptr::null()];
 
-        *self.state.get() = Some(FactoryState {
+        let mut features = vec![CString::new(category_feature(info.category)).unwrap()];
+        for feature in &info.features {
+            features.push(CString::new(feature_name(feature)).unwrap());
+        }
+
+        let mut feature_ptrs: Vec<*const c_char> = features.iter().map(|f| f.as_ptr()).collect();
+        feature_ptrs.push(ptr::null());
+
+        PluginState {
             descriptor: clap_plugin_descriptor {
                 clap_version: CLAP_VERSION,
                 id,
@@ -65,34 +85,132 @@ impl<P: Plugin + ClapPlugin> Factory<P> {
                 support_url: EMPTY.as_ptr(),
                 version,
                 description: EMPTY.as_ptr(),
-                features: FEATURES.as_ptr(),
+                features: feature_ptrs.as_ptr(),
             },
             info,
-        });
+            features,
+            feature_ptrs,
+            create: entry.create,
+        }
+    }
+}
+
+impl Drop for PluginState {
+    fn drop(&mut self) {
+        unsafe {
+            drop(CString::from_raw(self.descriptor.id as *mut c_char));
+            drop(CString::from_raw(self.descriptor.name as *mut c_char));
+            drop(CString::from_raw(self.descriptor.vendor as *mut c_char));
+            drop(CString::from_raw(self.descriptor.url as *mut c_char));
+            drop(CString::from_raw(self.descriptor.version as *mut c_char));
+        }
+    }
+}
+
+fn category_feature(category: Category) -> &'static str {
+    match category {
+        Category::Instrument => "instrument",
+        Category::Effect => "audio-effect",
+        Category::NoteEffect => "note-effect",
+        Category::Analyzer => "analyzer",
+    }
+}
+
+fn feature_name(feature: &Feature) -> &str {
+    match feature {
+        Feature::Synthesizer => "synthesizer",
+        Feature::Sampler => "sampler",
+        Feature::Drum => "drum",
+        Feature::Filter => "filter",
+        Feature::Equalizer => "equalizer",
+        Feature::Compressor => "compressor",
+        Feature::Limiter => "limiter",
+        Feature::Gate => "gate",
+        Feature::Distortion => "distortion",
+        Feature::Reverb => "reverb",
+        Feature::Delay => "delay",
+        Feature::Chorus => "chorus",
+        Feature::Flanger => "flanger",
+        Feature::Phaser => "phaser",
+        Feature::PitchShifter => "pitch-shifter",
+        Feature::Utility => "utility",
+        Feature::Mono => "mono",
+        Feature::Stereo => "stereo",
+        Feature::Surround => "surround",
+        Feature::Custom(name) => name,
+    }
+}
+
+#[doc(hidden)]
+#[repr(C)]
+pub struct Factory {
+    #[allow(unused)]
+    factory: clap_plugin_factory,
+    entries: &'static [FactoryEntry],
+    state: UnsafeCell<Option<Vec<PluginState>>>,
+    presets: PresetFactory,
+}
+
+unsafe impl Sync for Factory {}
+
+#[doc(hidden)]
+impl Factory {
+    pub const fn new(entries: &'static [FactoryEntry]) -> Self {
+        Factory {
+            factory: clap_plugin_factory {
+                get_plugin_count: Some(Self::get_plugin_count),
+                get_plugin_descriptor: Some(Self::get_plugin_descriptor),
+                create_plugin: Some(Self::create_plugin),
+            },
+            entries,
+            state: UnsafeCell::new(None),
+            presets: PresetFactory::new(),
+        }
+    }
+
+    pub unsafe fn init(&self) -> bool {
+        let plugins: Vec<PluginState> = self.entries.iter().map(PluginState::new).collect();
+        self.presets.init(
+            plugins
+                .iter()
+                .map(|plugin| (CStr::from_ptr(plugin.descriptor.id), &plugin.info)),
+        );
+        *self.state.get() = Some(plugins);
 
         true
     }
 
     pub unsafe fn deinit(&self) {
-        if let Some(state) = (*self.state.get()).take() {
-            drop(CString::from_raw(state.descriptor.id as *mut c_char));
-            drop(CString::from_raw(state.descriptor.name as *mut c_char));
-            drop(CString::from_raw(state.descriptor.vendor as *mut c_char));
-            drop(CString::from_raw(state.descriptor.url as *mut c_char));
-            drop(CString::from_raw(state.descriptor.version as *mut c_char));
-        }
+        self.presets.deinit();
+        (*self.state.get()).take();
+
+        logging::shutdown();
     }
 
     pub unsafe fn get(&self, factory_id: *const c_char) -> *const c_void {
-        if CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+        let factory_id = CStr::from_ptr(factory_id);
+
+        if factory_id == CLAP_PLUGIN_FACTORY_ID {
             return self as *const Self as *const c_void;
         }
 
+        if factory_id == CLAP_PRESET_DISCOVERY_FACTORY_ID
+            || factory_id == CLAP_PRESET_DISCOVERY_FACTORY_ID_COMPAT
+        {
+            return &self.presets as *const PresetFactory as *const c_void;
+        }
+
         ptr::null()
     }
 
-    unsafe extern "C" fn get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
-        1
+    unsafe extern "C" fn get_plugin_count(factory: *const clap_plugin_factory) -> u32 {
+        let factory = &*(factory as *const Self);
+
+        if let Some(plugins) = &*factory.state.get() {
+            return plugins.len() as u32;
+        }
+
+        0
     }
 
     unsafe extern "C" fn get_plugin_descriptor(
@@ -101,9 +219,9 @@ impl<P: Plugin + ClapPlugin> Factory<P> {
     ) -> *const clap_plugin_descriptor {
         let factory = &*(factory as *const Self);
 
-        if index == 0 {
-            if let Some(state) = &*factory.state.get() {
-                return &state.descriptor;
+        if let Some(plugins) = &*factory.state.get() {
+            if let Some(plugin) = plugins.get(index as usize) {
+                return &plugin.descriptor;
             }
         }
 
@@ -117,10 +235,12 @@ impl<P: Plugin + ClapPlugin> Factory<P> {
     ) -> *const clap_plugin {
         let factory = &*(factory as *const Self);
 
-        if let Some(state) = &*factory.state.get() {
-            if CStr::from_ptr(plugin_id) == CStr::from_ptr(state.descriptor.id) {
-                let instance = Box::new(Instance::<P>::new(&state.descriptor, &state.info, host));
-                return Box::into_raw(instance) as *const clap_plugin;
+        if let Some(plugins) = &*factory.state.get() {
+            let plugin_id = CStr::from_ptr(plugin_id);
+            for plugin in plugins {
+                if plugin_id == CStr::from_ptr(plugin.descriptor.id) {
+                    return (plugin.create)(&plugin.descriptor, &plugin.info, host);
+                }
             }
         }
 
@@ -1,20 +1,23 @@
 use std::collections::HashMap;
 use std::ffi::{c_char, CStr};
+use std::iter::zip;
 use std::rc::Rc;
 use std::sync::Arc;
 
-use clap_sys::ext::{gui::*, params::*};
+use clap_sys::ext::{context_menu::*, gui::*, params::*};
 use clap_sys::{host::*, plugin::*};
 
 use super::instance::Instance;
+use crate::logging;
 use crate::params::{ParamId, ParamValue};
 use crate::plugin::Plugin;
 use crate::sync::param_gestures::ParamGestures;
-use crate::view::{ParentWindow, RawParent, View, ViewHost, ViewHostInner};
+use crate::view::{ParamIndication, ParentWindow, RawParent, View, ViewHost, ViewHostInner};
 
 struct ClapViewHost {
     host: *const clap_host,
     host_params: Option<*const clap_host_params>,
+    host_context_menu: Option<*const clap_host_context_menu>,
     param_map: Arc<HashMap<ParamId, usize>>,
     param_gestures: Arc<ParamGestures>,
 }
@@ -43,6 +46,31 @@ impl ViewHostInner for ClapViewHost {
             unsafe { (*host_params).request_flush.unwrap()(self.host) };
         }
     }
+
+    fn show_param_context_menu(&self, id: ParamId, x: f64, y: f64) -> bool {
+        let Some(host_context_menu) = self.host_context_menu else {
+            return false;
+        };
+
+        let target = clap_context_menu_target {
+            kind: CLAP_CONTEXT_MENU_TARGET_KIND_PARAM,
+            id,
+        };
+
+        unsafe {
+            if !(*host_context_menu).can_popup.unwrap()(self.host) {
+                return false;
+            }
+
+            (*host_context_menu).popup.unwrap()(
+                self.host,
+                &target,
+                0,
+                x.round() as i32,
+                y.round() as i32,
+            )
+        }
+    }
 }
 
 impl<P: Plugin> Instance<P> {
@@ -111,6 +139,8 @@ impl<P: Plugin> Instance<P> {
 
     unsafe extern "C" fn gui_destroy(plugin: *const clap_plugin) {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         main_thread_state.view = None;
@@ -126,6 +156,8 @@ impl<P: Plugin> Instance<P> {
         height: *mut u32,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         if let Some(view) = &main_thread_state.view {
@@ -187,16 +219,27 @@ impl<P: Plugin> Instance<P> {
         let raw_parent = { RawParent::X11(window.specific.x11) };
 
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         let host = ViewHost::from_inner(Rc::new(ClapViewHost {
             host: instance.host,
             host_params: main_thread_state.host_params,
+            host_context_menu: main_thread_state.host_context_menu,
             param_map: Arc::clone(&instance.param_map),
             param_gestures: Arc::clone(&instance.param_gestures),
         }));
         let parent = ParentWindow::from_raw(raw_parent);
-        let view = main_thread_state.plugin.view(host, &parent);
+        let mut view = main_thread_state.plugin.view(host, &parent);
+
+        let indications = zip(&instance.info.params, &main_thread_state.param_indications);
+        for (param, indication) in indications {
+            if *indication != ParamIndication::default() {
+                view.param_indication_changed(param.id, indication);
+            }
+        }
+
         main_thread_state.view = Some(view);
 
         true
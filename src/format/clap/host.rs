@@ -1,5 +1,215 @@
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
+use std::{mem, ptr};
+
+use clap_sys::ext::audio_ports::*;
+use clap_sys::ext::log::*;
+use clap_sys::ext::params::*;
+use clap_sys::ext::remote_controls::*;
+use clap_sys::ext::thread_pool::*;
+use clap_sys::ext::voice_info::*;
+use clap_sys::host::*;
+use log::Level;
+
+use crate::engine::ThreadPoolInner;
 use crate::host::HostInner;
+use crate::logging::HostLog;
+use crate::params::{ParamId, ParamValue};
+use crate::sync::param_gestures::ParamGestures;
+
+pub struct ClapHost {
+    pub host: *const clap_host,
+    pub param_map: Arc<HashMap<ParamId, usize>>,
+    pub param_gestures: Arc<ParamGestures>,
+    pub active: AtomicBool,
+    pub audio_ports_rescan_pending: AtomicBool,
+}
+
+// The host pointer is only dereferenced from the main thread.
+unsafe impl Send for ClapHost {}
+unsafe impl Sync for ClapHost {}
+
+impl ClapHost {
+    /// Called when the plugin is activated or deactivated, since port changes requested while the
+    /// plugin is active can only be reported once it has been deactivated.
+    pub fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+
+        if !active && self.audio_ports_rescan_pending.swap(false, Ordering::Relaxed) {
+            self.rescan_audio_ports();
+        }
+    }
+
+    fn rescan_audio_ports(&self) {
+        unsafe {
+            let host_audio_ports =
+                (*self.host).get_extension.unwrap()(self.host, CLAP_EXT_AUDIO_PORTS.as_ptr());
+            if host_audio_ports.is_null() {
+                return;
+            }
+            let host_audio_ports = &*(host_audio_ports as *const clap_host_audio_ports);
+
+            let is_supported =
+                |flag| host_audio_ports.is_rescan_flag_supported.unwrap()(self.host, flag);
+            let flags = if is_supported(CLAP_AUDIO_PORTS_RESCAN_LIST) {
+                CLAP_AUDIO_PORTS_RESCAN_LIST
+            } else {
+                [
+                    CLAP_AUDIO_PORTS_RESCAN_NAMES,
+                    CLAP_AUDIO_PORTS_RESCAN_FLAGS,
+                    CLAP_AUDIO_PORTS_RESCAN_CHANNEL_COUNT,
+                    CLAP_AUDIO_PORTS_RESCAN_PORT_TYPE,
+                    CLAP_AUDIO_PORTS_RESCAN_IN_PLACE_PAIR,
+                ]
+                .into_iter()
+                .filter(|&flag| is_supported(flag))
+                .fold(0, |flags, flag| flags | flag)
+            };
+
+            if flags != 0 {
+                host_audio_ports.rescan.unwrap()(self.host, flags);
+            }
+        }
+    }
+}
+
+impl HostInner for ClapHost {
+    fn set_param(&self, id: ParamId, value: ParamValue) {
+        if let Some(&index) = self.param_map.get(&id) {
+            // Goes through the same path as edits from the view, so that the host receives a
+            // CLAP_EVENT_PARAM_VALUE event on the next flush or process call.
+            self.param_gestures.set_value(index, value);
+
+            unsafe {
+                let host_params =
+                    (*self.host).get_extension.unwrap()(self.host, CLAP_EXT_PARAMS.as_ptr());
+                if !host_params.is_null() {
+                    let host_params = host_params as *const clap_host_params;
+                    (*host_params).request_flush.unwrap()(self.host);
+                }
+            }
+        }
+    }
+
+    fn voice_info_changed(&self) {
+        unsafe {
+            let host_voice_info =
+                (*self.host).get_extension.unwrap()(self.host, CLAP_EXT_VOICE_INFO.as_ptr());
+            if !host_voice_info.is_null() {
+                let host_voice_info = host_voice_info as *const clap_host_voice_info;
+                (*host_voice_info).changed.unwrap()(self.host);
+            }
+        }
+    }
+
+    fn remote_controls_changed(&self) {
+        unsafe {
+            let mut host_remote_controls =
+                (*self.host).get_extension.unwrap()(self.host, CLAP_EXT_REMOTE_CONTROLS.as_ptr());
+            if host_remote_controls.is_null() {
+                host_remote_controls = (*self.host).get_extension.unwrap()(
+                    self.host,
+                    CLAP_EXT_REMOTE_CONTROLS_COMPAT.as_ptr(),
+                );
+            }
+            if !host_remote_controls.is_null() {
+                let host_remote_controls = host_remote_controls as *const clap_host_remote_controls;
+                (*host_remote_controls).changed.unwrap()(self.host);
+            }
+        }
+    }
+
+    fn audio_ports_changed(&self) {
+        if self.active.load(Ordering::Relaxed) {
+            // Most rescan flags require the plugin to be inactive, so ask the host to restart the
+            // plugin and rescan once it has been deactivated.
+            self.audio_ports_rescan_pending.store(true, Ordering::Relaxed);
+            unsafe { (*self.host).request_restart.unwrap()(self.host) };
+        } else {
+            self.rescan_audio_ports();
+        }
+    }
+}
+
+pub struct ClapLog {
+    pub host: *const clap_host,
+    pub host_log: *const clap_host_log,
+}
+
+// clap_host_log::log is thread-safe.
+unsafe impl Send for ClapLog {}
+
+impl HostLog for ClapLog {
+    fn log(&self, level: Level, message: &str) {
+        let severity = match level {
+            Level::Error => CLAP_LOG_ERROR,
+            Level::Warn => CLAP_LOG_WARNING,
+            Level::Info => CLAP_LOG_INFO,
+            Level::Debug | Level::Trace => CLAP_LOG_DEBUG,
+        };
+
+        let message = CString::new(message).unwrap_or_default();
+        unsafe { (*self.host_log).log.unwrap()(self.host, severity, message.as_ptr()) };
+    }
+}
+
+pub struct ClapThreadPool {
+    pub host: *const clap_host,
+    pub host_thread_pool: AtomicPtr<clap_host_thread_pool>,
+    running: AtomicBool,
+    task: UnsafeCell<Option<*const (dyn Fn(usize) + Sync)>>,
+}
+
+// The task is only written by the thread which set `running`, and only read by worker threads
+// while that thread is blocked in request_exec.
+unsafe impl Send for ClapThreadPool {}
+unsafe impl Sync for ClapThreadPool {}
+
+impl ClapThreadPool {
+    pub fn new(host: *const clap_host) -> ClapThreadPool {
+        ClapThreadPool {
+            host,
+            host_thread_pool: AtomicPtr::new(ptr::null_mut()),
+            running: AtomicBool::new(false),
+            task: UnsafeCell::new(None),
+        }
+    }
+
+    pub unsafe fn exec(&self, task_index: u32) {
+        if let Some(task) = *self.task.get() {
+            (*task)(task_index as usize);
+        }
+    }
+}
+
+impl ThreadPoolInner for ClapThreadPool {
+    fn run(&self, count: usize, task: &(dyn Fn(usize) + Sync)) -> bool {
+        let host_thread_pool = self.host_thread_pool.load(Ordering::Relaxed);
+        if host_thread_pool.is_null() {
+            return false;
+        }
+
+        // Nested calls from inside a task run serially.
+        if self.running.swap(true, Ordering::Acquire) {
+            return false;
+        }
+
+        let result = unsafe {
+            // The task outlives the call to request_exec, and is cleared before returning.
+            *self.task.get() = Some(mem::transmute::<
+                &(dyn Fn(usize) + Sync),
+                *const (dyn Fn(usize) + Sync + 'static),
+            >(task));
+            let result = (*host_thread_pool).request_exec.unwrap()(self.host, count as u32);
+            *self.task.get() = None;
+            result
+        };
 
-pub struct ClapHost {}
+        self.running.store(false, Ordering::Release);
 
-impl HostInner for ClapHost {}
+        result
+    }
+}
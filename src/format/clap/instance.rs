@@ -1,26 +1,38 @@
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
-use std::ffi::{c_char, c_void, CStr};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fs::File;
 use std::iter::zip;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 use std::sync::Arc;
 use std::{io, mem, ptr, slice};
 
-use clap_sys::ext::{audio_ports::*, audio_ports_config::*, gui::*, params::*, state::*};
-use clap_sys::{events::*, host::*, id::*, plugin::*, process::*, stream::*};
+use clap_sys::ext::{audio_ports::*, audio_ports_activation::*, audio_ports_config::*};
+use clap_sys::ext::{context_menu::*, log::*, param_indication::*, track_info::*, voice_info::*};
+use clap_sys::ext::{gui::*, params::*, preset_load::*};
+use clap_sys::ext::{remote_controls::*, render::*, state::*, state_context::*};
+use clap_sys::ext::{thread_check::*, thread_pool::*};
+use clap_sys::factory::preset_discovery::*;
+use clap_sys::{color::*, events::*, host::*, id::*, plugin::*, process::*, stream::*};
 
-use super::host::ClapHost;
+use super::host::{ClapHost, ClapLog, ClapThreadPool};
 use crate::buffers::{BufferData, BufferType, Buffers};
-use crate::bus::{BusDir, Format};
-use crate::engine::{Config, Engine};
-use crate::events::{Data, Event, Events};
+use crate::bus::{active_buses, BusDir, Format};
+use crate::engine::{Config, Engine, ProcessMode, ThreadPool};
+use crate::events::{Data, Event, Events, OutputEvents};
 use crate::host::Host;
+use crate::logging;
 use crate::params::{ParamId, ParamInfo, ParamValue};
 use crate::plugin::{Plugin, PluginInfo};
+use crate::preset::read_preset;
+use crate::state::StateContext;
 use crate::sync::param_gestures::{GestureStates, GestureUpdate, ParamGestures};
 use crate::sync::params::ParamValues;
+use crate::thread_check;
+use crate::track::{Color, TrackInfo, TrackKind};
 use crate::util::{copy_cstring, slice_from_raw_parts_checked, DisplayParam};
-use crate::view::View;
+use crate::view::{AutomationState, MenuItem, ParamIndication, ParamMapping, View};
 
 fn port_type_from_format(format: &Format) -> &'static CStr {
     match format {
@@ -45,9 +57,111 @@ fn map_param_out(param: &ParamInfo, value: ParamValue) -> f64 {
     }
 }
 
+fn color_from_clap(color: &clap_color) -> Color {
+    Color {
+        r: color.red,
+        g: color.green,
+        b: color.blue,
+        a: color.alpha,
+    }
+}
+
+unsafe fn string_from_ptr(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
+
+unsafe fn add_menu_items(builder: &clap_context_menu_builder, items: &[MenuItem]) -> bool {
+    let add_item = builder.add_item.unwrap();
+
+    for item in items {
+        let added = match item {
+            MenuItem::Entry {
+                label,
+                enabled,
+                action,
+            } => {
+                let label = CString::new(label.as_str()).unwrap_or_default();
+                let entry = clap_context_menu_entry {
+                    label: label.as_ptr(),
+                    is_enabled: *enabled,
+                    action_id: *action,
+                };
+                add_item(
+                    builder,
+                    CLAP_CONTEXT_MENU_ITEM_ENTRY,
+                    &entry as *const _ as *const c_void,
+                )
+            }
+            MenuItem::CheckEntry {
+                label,
+                enabled,
+                checked,
+                action,
+            } => {
+                let label = CString::new(label.as_str()).unwrap_or_default();
+                let entry = clap_context_menu_check_entry {
+                    label: label.as_ptr(),
+                    is_enabled: *enabled,
+                    is_checked: *checked,
+                    action_id: *action,
+                };
+                add_item(
+                    builder,
+                    CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY,
+                    &entry as *const _ as *const c_void,
+                )
+            }
+            MenuItem::Separator => add_item(builder, CLAP_CONTEXT_MENU_ITEM_SEPARATOR, ptr::null()),
+            MenuItem::Submenu {
+                label,
+                enabled,
+                items,
+            } => {
+                let label = CString::new(label.as_str()).unwrap_or_default();
+                let submenu = clap_context_menu_submenu {
+                    label: label.as_ptr(),
+                    is_enabled: *enabled,
+                };
+                add_item(
+                    builder,
+                    CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU,
+                    &submenu as *const _ as *const c_void,
+                ) && add_menu_items(builder, items)
+                    && add_item(builder, CLAP_CONTEXT_MENU_ITEM_END_SUBMENU, ptr::null())
+            }
+        };
+
+        if !added {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn state_context(context_type: clap_plugin_state_context_type) -> Option<StateContext> {
+    match context_type {
+        CLAP_STATE_CONTEXT_FOR_PRESET => Some(StateContext::Preset),
+        CLAP_STATE_CONTEXT_FOR_DUPLICATE => Some(StateContext::Duplicate),
+        CLAP_STATE_CONTEXT_FOR_PROJECT => Some(StateContext::Project),
+        _ => None,
+    }
+}
+
 pub struct MainThreadState<P: Plugin> {
     pub host_params: Option<*const clap_host_params>,
+    pub host_preset_load: Option<*const clap_host_preset_load>,
+    pub host_track_info: Option<*const clap_host_track_info>,
+    pub host_context_menu: Option<*const clap_host_context_menu>,
     pub layout_index: usize,
+    pub inputs_active: Vec<bool>,
+    pub outputs_active: Vec<bool>,
+    pub param_indications: Vec<ParamIndication>,
+    pub process_mode: ProcessMode,
     pub plugin: P,
     pub view: Option<P::View>,
 }
@@ -56,7 +170,17 @@ pub struct ProcessState<P: Plugin> {
     gesture_states: GestureStates,
     buffer_data: Vec<BufferData>,
     buffer_ptrs: Vec<*mut f32>,
+    inputs_active: Vec<bool>,
+    outputs_active: Vec<bool>,
+    // Stands in for the channels of inactive ports, which the host may not provide buffers for.
+    silence: Vec<f32>,
+    scratch: Vec<f32>,
     events: Vec<Event>,
+    output_events: Vec<Event>,
+    // Whether the plugin reports voice info, without which hosts don't expect note end events.
+    note_ends: bool,
+    // The mode the engine was last told about.
+    process_mode: ProcessMode,
     engine: Option<P::Engine>,
 }
 
@@ -65,6 +189,9 @@ pub struct Instance<P: Plugin> {
     #[allow(unused)]
     pub clap_plugin: clap_plugin,
     pub host: *const clap_host,
+    pub host_thread_check: AtomicPtr<clap_host_thread_check>,
+    pub clap_host: Arc<ClapHost>,
+    pub thread_pool: Arc<ClapThreadPool>,
     pub info: Arc<PluginInfo>,
     pub input_bus_map: Vec<usize>,
     pub output_bus_map: Vec<usize>,
@@ -74,6 +201,8 @@ pub struct Instance<P: Plugin> {
     // Plugin -> engine parameter changes
     pub engine_params: ParamValues,
     pub param_gestures: Arc<ParamGestures>,
+    // Set by the render extension and picked up by the engine at the next process call.
+    pub render_offline: AtomicBool,
     pub main_thread_state: UnsafeCell<MainThreadState<P>>,
     pub process_state: UnsafeCell<ProcessState<P>>,
 }
@@ -86,6 +215,8 @@ impl<P: Plugin> Instance<P> {
         info: &Arc<PluginInfo>,
         host: *const clap_host,
     ) -> Self {
+        thread_check::set_main_thread();
+
         let mut input_bus_map = Vec::new();
         let mut output_bus_map = Vec::new();
         for (index, bus) in info.buses.iter().enumerate() {
@@ -99,10 +230,24 @@ impl<P: Plugin> Instance<P> {
             }
         }
 
+        let inputs_active = vec![true; input_bus_map.len()];
+        let outputs_active = vec![true; output_bus_map.len()];
+
         let mut param_map = HashMap::new();
         for (index, param) in info.params.iter().enumerate() {
             param_map.insert(param.id, index);
         }
+        let param_map = Arc::new(param_map);
+
+        let param_gestures = Arc::new(ParamGestures::with_count(info.params.len()));
+
+        let clap_host = Arc::new(ClapHost {
+            host,
+            param_map: param_map.clone(),
+            param_gestures: param_gestures.clone(),
+            active: AtomicBool::new(false),
+            audio_ports_rescan_pending: AtomicBool::new(false),
+        });
 
         Instance {
             clap_plugin: clap_plugin {
@@ -120,29 +265,69 @@ impl<P: Plugin> Instance<P> {
                 on_main_thread: Some(Self::on_main_thread),
             },
             host,
+            host_thread_check: AtomicPtr::new(ptr::null_mut()),
+            clap_host: clap_host.clone(),
+            thread_pool: Arc::new(ClapThreadPool::new(host)),
             info: info.clone(),
             input_bus_map,
             output_bus_map,
-            param_map: Arc::new(param_map),
+            param_map,
             plugin_params: ParamValues::with_count(info.params.len()),
             engine_params: ParamValues::with_count(info.params.len()),
-            param_gestures: Arc::new(ParamGestures::with_count(info.params.len())),
+            param_gestures,
+            render_offline: AtomicBool::new(false),
             main_thread_state: UnsafeCell::new(MainThreadState {
                 host_params: None,
+                host_preset_load: None,
+                host_track_info: None,
+                host_context_menu: None,
                 layout_index: 0,
-                plugin: P::new(Host::from_inner(Arc::new(ClapHost {}))),
+                inputs_active,
+                outputs_active,
+                param_indications: vec![ParamIndication::default(); info.params.len()],
+                process_mode: ProcessMode::Realtime,
+                plugin: P::new(Host::from_inner(clap_host)),
                 view: None,
             }),
             process_state: UnsafeCell::new(ProcessState {
                 gesture_states: GestureStates::with_count(info.params.len()),
                 buffer_data: Vec::new(),
                 buffer_ptrs: Vec::new(),
+                inputs_active: Vec::new(),
+                outputs_active: Vec::new(),
+                silence: Vec::new(),
+                scratch: Vec::new(),
                 events: Vec::with_capacity(4096),
+                output_events: Vec::with_capacity(256),
+                note_ends: false,
+                process_mode: ProcessMode::Realtime,
                 engine: None,
             }),
         }
     }
 
+    #[track_caller]
+    pub(super) fn check_main_thread(&self) {
+        thread_check::check_main_thread(|| unsafe {
+            let host_thread_check = self.host_thread_check.load(Ordering::Relaxed);
+            host_thread_check
+                .as_ref()
+                .and_then(|thread_check| thread_check.is_main_thread)
+                .map(|is_main_thread| is_main_thread(self.host))
+        });
+    }
+
+    #[track_caller]
+    fn check_audio_thread(&self) {
+        thread_check::check_audio_thread(|| unsafe {
+            let host_thread_check = self.host_thread_check.load(Ordering::Relaxed);
+            host_thread_check
+                .as_ref()
+                .and_then(|thread_check| thread_check.is_audio_thread)
+                .map(|is_audio_thread| is_audio_thread(self.host))
+        });
+    }
+
     fn sync_plugin(&self, main_thread_state: &mut MainThreadState<P>) {
         for (index, value) in self.plugin_params.poll() {
             let id = self.info.params[index].id;
@@ -154,6 +339,22 @@ impl<P: Plugin> Instance<P> {
         }
     }
 
+    // Called after the plugin's state has been replaced wholesale, e.g. by loading a preset.
+    unsafe fn params_reloaded(&self, main_thread_state: &mut MainThreadState<P>) {
+        for (index, param) in self.info.params.iter().enumerate() {
+            let value = main_thread_state.plugin.get_param(param.id);
+            self.engine_params.set(index, value);
+
+            if let Some(view) = &mut main_thread_state.view {
+                view.param_changed(param.id, value);
+            }
+        }
+
+        if let Some(host_params) = main_thread_state.host_params {
+            (*host_params).rescan.unwrap()(self.host, CLAP_PARAM_RESCAN_VALUES);
+        }
+    }
+
     fn sync_engine(&self, events: &mut Vec<Event>) {
         for (index, value) in self.engine_params.poll() {
             events.push(Event {
@@ -231,6 +432,50 @@ impl<P: Plugin> Instance<P> {
         }
     }
 
+    unsafe fn send_output_events(
+        &self,
+        engine: &mut P::Engine,
+        output_events: &mut Vec<Event>,
+        note_ends: bool,
+        out_events: *const clap_output_events,
+    ) {
+        output_events.clear();
+        engine.output_events(&mut OutputEvents::new(output_events));
+
+        if !note_ends {
+            return;
+        }
+
+        for event in output_events.iter() {
+            if let Data::NoteEnd {
+                note_id,
+                port,
+                channel,
+                key,
+            } = event.data
+            {
+                let event = clap_event_note {
+                    header: clap_event_header {
+                        size: mem::size_of::<clap_event_note>() as u32,
+                        time: event.time.max(0) as u32,
+                        space_id: CLAP_CORE_EVENT_SPACE_ID,
+                        type_: CLAP_EVENT_NOTE_END,
+                        flags: 0,
+                    },
+                    note_id: note_id.unwrap_or(-1),
+                    port_index: port as i16,
+                    channel: channel as i16,
+                    key: key as i16,
+                    velocity: 0.0,
+                };
+                (*out_events).try_push.unwrap()(
+                    out_events,
+                    &event as *const clap_event_note as *const clap_event_header,
+                );
+            }
+        }
+    }
+
     unsafe fn send_gesture_events(
         &self,
         update: &GestureUpdate,
@@ -304,18 +549,87 @@ impl<P: Plugin> Instance<P> {
 impl<P: Plugin> Instance<P> {
     unsafe extern "C" fn init(plugin: *const clap_plugin) -> bool {
         let instance = &*(plugin as *const Self);
+
+        let host_thread_check =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_THREAD_CHECK.as_ptr());
+        instance.host_thread_check.store(
+            host_thread_check as *mut clap_host_thread_check,
+            Ordering::Relaxed,
+        );
+
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
+        let host_thread_pool =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_THREAD_POOL.as_ptr());
+        instance.thread_pool.host_thread_pool.store(
+            host_thread_pool as *mut clap_host_thread_pool,
+            Ordering::Relaxed,
+        );
+
         let host_params =
             (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_PARAMS.as_ptr());
         if !host_params.is_null() {
             main_thread_state.host_params = Some(host_params as *const clap_host_params);
         }
 
+        let mut host_preset_load =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_PRESET_LOAD.as_ptr());
+        if host_preset_load.is_null() {
+            host_preset_load = (*instance.host).get_extension.unwrap()(
+                instance.host,
+                CLAP_EXT_PRESET_LOAD_COMPAT.as_ptr(),
+            );
+        }
+        if !host_preset_load.is_null() {
+            main_thread_state.host_preset_load =
+                Some(host_preset_load as *const clap_host_preset_load);
+        }
+
+        let mut host_track_info =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_TRACK_INFO.as_ptr());
+        if host_track_info.is_null() {
+            host_track_info = (*instance.host).get_extension.unwrap()(
+                instance.host,
+                CLAP_EXT_TRACK_INFO_COMPAT.as_ptr(),
+            );
+        }
+        if !host_track_info.is_null() {
+            main_thread_state.host_track_info =
+                Some(host_track_info as *const clap_host_track_info);
+            instance.update_track_info(main_thread_state);
+        }
+
+        let mut host_context_menu =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_CONTEXT_MENU.as_ptr());
+        if host_context_menu.is_null() {
+            host_context_menu = (*instance.host).get_extension.unwrap()(
+                instance.host,
+                CLAP_EXT_CONTEXT_MENU_COMPAT.as_ptr(),
+            );
+        }
+        if !host_context_menu.is_null() {
+            main_thread_state.host_context_menu =
+                Some(host_context_menu as *const clap_host_context_menu);
+        }
+
+        let host_log =
+            (*instance.host).get_extension.unwrap()(instance.host, CLAP_EXT_LOG.as_ptr());
+        if !host_log.is_null() {
+            let log = ClapLog {
+                host: instance.host,
+                host_log: host_log as *const clap_host_log,
+            };
+            logging::add_host_log(plugin as usize, Box::new(log));
+        }
+
         true
     }
 
     unsafe extern "C" fn destroy(plugin: *const clap_plugin) {
+        logging::remove_host_log(plugin as usize);
+
         drop(Box::from_raw(plugin as *mut Self));
     }
 
@@ -326,6 +640,8 @@ impl<P: Plugin> Instance<P> {
         max_frames_count: u32,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
         let process_state = &mut *instance.process_state.get();
 
@@ -351,10 +667,25 @@ impl<P: Plugin> Instance<P> {
 
         process_state.buffer_ptrs.resize(total_channels, NonNull::dangling().as_ptr());
 
+        let max_frames = max_frames_count as usize;
+        process_state.inputs_active.clone_from(&main_thread_state.inputs_active);
+        process_state.outputs_active.clone_from(&main_thread_state.outputs_active);
+        process_state.silence = vec![0.0; max_frames];
+        process_state.scratch = vec![0.0; total_channels * max_frames];
+        process_state.note_ends = main_thread_state.plugin.voice_info().is_some();
+        process_state.process_mode = main_thread_state.process_mode;
+
         let config = Config {
             layout: layout.clone(),
             sample_rate,
-            max_buffer_size: max_frames_count as usize,
+            max_buffer_size: max_frames,
+            process_mode: main_thread_state.process_mode,
+            thread_pool: ThreadPool::from_inner(instance.thread_pool.clone()),
+            active_buses: active_buses(
+                &instance.info.buses,
+                &main_thread_state.inputs_active,
+                &main_thread_state.outputs_active,
+            ),
         };
 
         // Discard any pending plugin -> engine parameter changes, since they will already be
@@ -362,12 +693,15 @@ impl<P: Plugin> Instance<P> {
         for _ in instance.engine_params.poll() {}
 
         process_state.engine = Some(main_thread_state.plugin.engine(&config));
+        instance.clap_host.set_active(true);
 
         true
     }
 
     unsafe extern "C" fn deactivate(plugin: *const clap_plugin) {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
         let process_state = &mut *instance.process_state.get();
 
@@ -376,16 +710,29 @@ impl<P: Plugin> Instance<P> {
         instance.sync_plugin(main_thread_state);
 
         process_state.engine = None;
+        instance.clap_host.set_active(false);
     }
 
-    unsafe extern "C" fn start_processing(_plugin: *const clap_plugin) -> bool {
+    unsafe extern "C" fn start_processing(plugin: *const clap_plugin) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_audio_thread();
+
         true
     }
 
-    unsafe extern "C" fn stop_processing(_plugin: *const clap_plugin) {}
+    unsafe extern "C" fn stop_processing(plugin: *const clap_plugin) {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_audio_thread();
+    }
 
     unsafe extern "C" fn reset(plugin: *const clap_plugin) {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_audio_thread();
+        let _audio_thread = thread_check::enter_audio_thread();
+
         let process_state = &mut *instance.process_state.get();
 
         if let Some(engine) = &mut process_state.engine {
@@ -406,15 +753,32 @@ impl<P: Plugin> Instance<P> {
         process: *const clap_process,
     ) -> clap_process_status {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_audio_thread();
+        let _audio_thread = thread_check::enter_audio_thread();
+
         let process_state = &mut *instance.process_state.get();
 
         let Some(engine) = &mut process_state.engine else {
             return CLAP_PROCESS_ERROR;
         };
 
+        let process_mode = if instance.render_offline.load(Ordering::Relaxed) {
+            ProcessMode::Offline
+        } else {
+            ProcessMode::Realtime
+        };
+        if process_mode != process_state.process_mode {
+            process_state.process_mode = process_mode;
+            engine.process_mode_changed(process_mode);
+        }
+
         let process = &*process;
 
         let len = process.frames_count as usize;
+        if len > process_state.silence.len() {
+            return CLAP_PROCESS_ERROR;
+        }
 
         let input_count = process.audio_inputs_count as usize;
         let output_count = process.audio_outputs_count as usize;
@@ -427,9 +791,20 @@ impl<P: Plugin> Instance<P> {
         let inputs = slice_from_raw_parts_checked(process.audio_inputs, input_count);
         let outputs = slice_from_raw_parts_checked(process.audio_outputs, output_count);
 
-        for (&bus_index, output) in zip(&instance.output_bus_map, outputs) {
+        for (port_index, (&bus_index, output)) in zip(&instance.output_bus_map, outputs).enumerate()
+        {
             let data = &process_state.buffer_data[bus_index];
 
+            // Anything written to an inactive port is discarded.
+            if !process_state.outputs_active[port_index] {
+                let max_frames = process_state.silence.len();
+                for channel in data.start..data.end {
+                    process_state.buffer_ptrs[channel] =
+                        process_state.scratch[channel * max_frames..].as_mut_ptr();
+                }
+                continue;
+            }
+
             let channel_count = output.channel_count as usize;
             if channel_count != data.end - data.start {
                 return CLAP_PROCESS_ERROR;
@@ -440,10 +815,25 @@ impl<P: Plugin> Instance<P> {
             process_state.buffer_ptrs[data.start..data.end].copy_from_slice(channels);
         }
 
-        for (&bus_index, input) in zip(&instance.input_bus_map, inputs) {
+        for (port_index, (&bus_index, input)) in zip(&instance.input_bus_map, inputs).enumerate() {
             let data = &process_state.buffer_data[bus_index];
             let bus_info = &instance.info.buses[bus_index];
 
+            // Inactive ports are treated as silent.
+            if !process_state.inputs_active[port_index] {
+                let ptrs = &mut process_state.buffer_ptrs[data.start..data.end];
+                match bus_info.dir {
+                    BusDir::In => ptrs.fill(process_state.silence.as_mut_ptr()),
+                    BusDir::InOut => {
+                        for &mut dst in ptrs {
+                            slice::from_raw_parts_mut(dst, len).fill(0.0);
+                        }
+                    }
+                    BusDir::Out => unreachable!(),
+                }
+                continue;
+            }
+
             let channel_count = input.channel_count as usize;
             if channel_count != data.end - data.start {
                 return CLAP_PROCESS_ERROR;
@@ -491,6 +881,12 @@ impl<P: Plugin> Instance<P> {
             ),
             Events::new(&process_state.events),
         );
+        instance.send_output_events(
+            engine,
+            &mut process_state.output_events,
+            process_state.note_ends,
+            process.out_events,
+        );
 
         CLAP_PROCESS_CONTINUE
     }
@@ -505,6 +901,10 @@ impl<P: Plugin> Instance<P> {
             return &Self::AUDIO_PORTS as *const _ as *const c_void;
         }
 
+        if id == CLAP_EXT_AUDIO_PORTS_ACTIVATION || id == CLAP_EXT_AUDIO_PORTS_ACTIVATION_COMPAT {
+            return &Self::AUDIO_PORTS_ACTIVATION as *const _ as *const c_void;
+        }
+
         if id == CLAP_EXT_AUDIO_PORTS_CONFIG {
             return &Self::AUDIO_PORTS_CONFIG as *const _ as *const c_void;
         }
@@ -517,6 +917,42 @@ impl<P: Plugin> Instance<P> {
             return &Self::STATE as *const _ as *const c_void;
         }
 
+        if id == CLAP_EXT_STATE_CONTEXT {
+            return &Self::STATE_CONTEXT as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_PRESET_LOAD || id == CLAP_EXT_PRESET_LOAD_COMPAT {
+            return &Self::PRESET_LOAD as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_RENDER {
+            return &Self::RENDER as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_REMOTE_CONTROLS || id == CLAP_EXT_REMOTE_CONTROLS_COMPAT {
+            return &Self::REMOTE_CONTROLS as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_PARAM_INDICATION || id == CLAP_EXT_PARAM_INDICATION_COMPAT {
+            return &Self::PARAM_INDICATION as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_CONTEXT_MENU || id == CLAP_EXT_CONTEXT_MENU_COMPAT {
+            return &Self::CONTEXT_MENU as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_TRACK_INFO || id == CLAP_EXT_TRACK_INFO_COMPAT {
+            return &Self::TRACK_INFO as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_VOICE_INFO {
+            return &Self::VOICE_INFO as *const _ as *const c_void;
+        }
+
+        if id == CLAP_EXT_THREAD_POOL {
+            return &Self::THREAD_POOL as *const _ as *const c_void;
+        }
+
         if id == CLAP_EXT_GUI {
             let instance = &*(plugin as *const Self);
             if instance.info.has_view {
@@ -529,6 +965,8 @@ impl<P: Plugin> Instance<P> {
 
     unsafe extern "C" fn on_main_thread(plugin: *const clap_plugin) {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         instance.sync_plugin(main_thread_state);
@@ -558,6 +996,8 @@ impl<P: Plugin> Instance<P> {
         info: *mut clap_audio_port_info,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         let bus_index = if is_input {
@@ -605,6 +1045,45 @@ impl<P: Plugin> Instance<P> {
     }
 }
 
+impl<P: Plugin> Instance<P> {
+    const AUDIO_PORTS_ACTIVATION: clap_plugin_audio_ports_activation =
+        clap_plugin_audio_ports_activation {
+            can_activate_while_processing: Some(Self::audio_ports_activation_can_activate),
+            set_active: Some(Self::audio_ports_activation_set_active),
+        };
+
+    unsafe extern "C" fn audio_ports_activation_can_activate(_plugin: *const clap_plugin) -> bool {
+        false
+    }
+
+    unsafe extern "C" fn audio_ports_activation_set_active(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        is_active: bool,
+        _sample_size: u32,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        // Takes effect the next time the plugin is activated.
+        let active = if is_input {
+            main_thread_state.inputs_active.get_mut(port_index as usize)
+        } else {
+            main_thread_state.outputs_active.get_mut(port_index as usize)
+        };
+
+        if let Some(active) = active {
+            *active = is_active;
+            return true;
+        }
+
+        false
+    }
+}
+
 impl<P: Plugin> Instance<P> {
     const AUDIO_PORTS_CONFIG: clap_plugin_audio_ports_config = clap_plugin_audio_ports_config {
         count: Some(Self::audio_ports_config_count),
@@ -668,6 +1147,8 @@ impl<P: Plugin> Instance<P> {
         config_id: clap_id,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         if instance.info.layouts.get(config_id as usize).is_some() {
@@ -732,6 +1213,8 @@ impl<P: Plugin> Instance<P> {
         value: *mut f64,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         if let Some(&index) = instance.param_map.get(&param_id) {
@@ -753,6 +1236,8 @@ impl<P: Plugin> Instance<P> {
         size: u32,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         if let Some(&index) = instance.param_map.get(&param_id) {
@@ -783,6 +1268,8 @@ impl<P: Plugin> Instance<P> {
         value: *mut f64,
     ) -> bool {
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         if let Some(&index) = instance.param_map.get(&param_id) {
@@ -810,6 +1297,10 @@ impl<P: Plugin> Instance<P> {
 
         // If we are in the active state, flush will be called on the audio thread.
         if let Some(engine) = &mut process_state.engine {
+            let _log = logging::enter_instance(plugin as usize);
+            instance.check_audio_thread();
+            let _audio_thread = thread_check::enter_audio_thread();
+
             process_state.events.clear();
             instance.sync_engine(&mut process_state.events);
             instance.process_param_events(in_, &mut process_state.events);
@@ -821,9 +1312,17 @@ impl<P: Plugin> Instance<P> {
             );
 
             engine.flush(Events::new(&process_state.events));
+            instance.send_output_events(
+                engine,
+                &mut process_state.output_events,
+                process_state.note_ends,
+                out,
+            );
         }
         // Otherwise, flush will be called on the main thread.
         else {
+            let _log = logging::enter_instance(plugin as usize);
+            instance.check_main_thread();
             let main_thread_state = &mut *instance.main_thread_state.get();
 
             let size = (*in_).size.unwrap()(in_);
@@ -863,6 +1362,298 @@ impl<P: Plugin> Instance<P> {
     }
 }
 
+impl<P: Plugin> Instance<P> {
+    const RENDER: clap_plugin_render = clap_plugin_render {
+        has_hard_realtime_requirement: Some(Self::render_has_hard_realtime_requirement),
+        set: Some(Self::render_set),
+    };
+
+    unsafe extern "C" fn render_has_hard_realtime_requirement(_plugin: *const clap_plugin) -> bool {
+        false
+    }
+
+    unsafe extern "C" fn render_set(
+        plugin: *const clap_plugin,
+        mode: clap_plugin_render_mode,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        let process_mode = match mode {
+            CLAP_RENDER_REALTIME => ProcessMode::Realtime,
+            CLAP_RENDER_OFFLINE => ProcessMode::Offline,
+            _ => return false,
+        };
+
+        // A running engine is told about the new mode at the start of the next process call.
+        main_thread_state.process_mode = process_mode;
+        instance
+            .render_offline
+            .store(process_mode == ProcessMode::Offline, Ordering::Relaxed);
+
+        true
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const REMOTE_CONTROLS: clap_plugin_remote_controls = clap_plugin_remote_controls {
+        count: Some(Self::remote_controls_count),
+        get: Some(Self::remote_controls_get),
+    };
+
+    unsafe extern "C" fn remote_controls_count(plugin: *const clap_plugin) -> u32 {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        main_thread_state.plugin.remote_controls().len() as u32
+    }
+
+    unsafe extern "C" fn remote_controls_get(
+        plugin: *const clap_plugin,
+        page_index: u32,
+        page: *mut clap_remote_controls_page,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        let pages = main_thread_state.plugin.remote_controls();
+        if let Some(info) = pages.get(page_index as usize) {
+            let page = &mut *page;
+
+            copy_cstring(&info.section, &mut page.section_name);
+            page.page_id = page_index;
+            copy_cstring(&info.name, &mut page.page_name);
+            page.param_ids = [CLAP_INVALID_ID; CLAP_REMOTE_CONTROLS_COUNT];
+            for (param_id, &id) in zip(&mut page.param_ids, &info.params) {
+                if let Some(id) = id {
+                    *param_id = id;
+                }
+            }
+            page.is_for_preset = false;
+
+            return true;
+        }
+
+        false
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const PARAM_INDICATION: clap_plugin_param_indication = clap_plugin_param_indication {
+        set_mapping: Some(Self::param_indication_set_mapping),
+        set_automation: Some(Self::param_indication_set_automation),
+    };
+
+    unsafe extern "C" fn param_indication_set_mapping(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        has_mapping: bool,
+        color: *const clap_color,
+        label: *const c_char,
+        description: *const c_char,
+    ) {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        if let Some(&index) = instance.param_map.get(&param_id) {
+            let indication = &mut main_thread_state.param_indications[index];
+            indication.mapping = has_mapping.then(|| ParamMapping {
+                color: color.as_ref().map(color_from_clap),
+                label: string_from_ptr(label),
+                description: string_from_ptr(description),
+            });
+
+            if let Some(view) = &mut main_thread_state.view {
+                view.param_indication_changed(param_id, indication);
+            }
+        }
+    }
+
+    unsafe extern "C" fn param_indication_set_automation(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        automation_state: u32,
+        color: *const clap_color,
+    ) {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        if let Some(&index) = instance.param_map.get(&param_id) {
+            let indication = &mut main_thread_state.param_indications[index];
+            indication.automation = match automation_state {
+                CLAP_PARAM_INDICATION_AUTOMATION_PRESENT => AutomationState::Present,
+                CLAP_PARAM_INDICATION_AUTOMATION_PLAYING => AutomationState::Playing,
+                CLAP_PARAM_INDICATION_AUTOMATION_RECORDING => AutomationState::Recording,
+                CLAP_PARAM_INDICATION_AUTOMATION_OVERRIDING => AutomationState::Overriding,
+                _ => AutomationState::None,
+            };
+            indication.automation_color = color.as_ref().map(color_from_clap);
+
+            if let Some(view) = &mut main_thread_state.view {
+                view.param_indication_changed(param_id, indication);
+            }
+        }
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const CONTEXT_MENU: clap_plugin_context_menu = clap_plugin_context_menu {
+        populate: Some(Self::context_menu_populate),
+        perform: Some(Self::context_menu_perform),
+    };
+
+    unsafe extern "C" fn context_menu_populate(
+        plugin: *const clap_plugin,
+        target: *const clap_context_menu_target,
+        builder: *const clap_context_menu_builder,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        let target = &*target;
+        if target.kind != CLAP_CONTEXT_MENU_TARGET_KIND_PARAM {
+            return true;
+        }
+
+        if !instance.param_map.contains_key(&target.id) {
+            return false;
+        }
+
+        let items = main_thread_state.plugin.param_context_menu(target.id);
+        add_menu_items(&*builder, &items)
+    }
+
+    unsafe extern "C" fn context_menu_perform(
+        plugin: *const clap_plugin,
+        target: *const clap_context_menu_target,
+        action_id: clap_id,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        let target = &*target;
+        if target.kind != CLAP_CONTEXT_MENU_TARGET_KIND_PARAM
+            || !instance.param_map.contains_key(&target.id)
+        {
+            return false;
+        }
+
+        main_thread_state.plugin.param_context_menu_action(target.id, action_id);
+
+        true
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const TRACK_INFO: clap_plugin_track_info = clap_plugin_track_info {
+        changed: Some(Self::track_info_changed),
+    };
+
+    unsafe extern "C" fn track_info_changed(plugin: *const clap_plugin) {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        instance.update_track_info(main_thread_state);
+    }
+
+    unsafe fn update_track_info(&self, main_thread_state: &mut MainThreadState<P>) {
+        let Some(host_track_info) = main_thread_state.host_track_info else {
+            return;
+        };
+
+        let mut info: clap_track_info = mem::zeroed();
+        if !(*host_track_info).get.unwrap()(self.host, &mut info) {
+            return;
+        }
+
+        let mut track_info = TrackInfo::default();
+        if info.flags & CLAP_TRACK_INFO_HAS_TRACK_NAME != 0 {
+            let name = slice::from_raw_parts(info.name.as_ptr() as *const u8, info.name.len());
+            if let Ok(name) = CStr::from_bytes_until_nul(name) {
+                track_info.name = Some(name.to_string_lossy().into_owned());
+            }
+        }
+        if info.flags & CLAP_TRACK_INFO_HAS_TRACK_COLOR != 0 {
+            track_info.color = Some(color_from_clap(&info.color));
+        }
+        if info.flags & CLAP_TRACK_INFO_HAS_AUDIO_CHANNEL != 0 {
+            track_info.channel_count = u32::try_from(info.audio_channel_count).ok();
+        }
+        track_info.kind = if info.flags & CLAP_TRACK_INFO_IS_FOR_MASTER != 0 {
+            TrackKind::Master
+        } else if info.flags & CLAP_TRACK_INFO_IS_FOR_RETURN_TRACK != 0 {
+            TrackKind::Return
+        } else if info.flags & CLAP_TRACK_INFO_IS_FOR_BUS != 0 {
+            TrackKind::Bus
+        } else {
+            TrackKind::Regular
+        };
+
+        main_thread_state.plugin.track_info_changed(&track_info);
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const THREAD_POOL: clap_plugin_thread_pool = clap_plugin_thread_pool {
+        exec: Some(Self::thread_pool_exec),
+    };
+
+    unsafe extern "C" fn thread_pool_exec(plugin: *const clap_plugin, task_index: u32) {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+
+        instance.thread_pool.exec(task_index);
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const VOICE_INFO: clap_plugin_voice_info = clap_plugin_voice_info {
+        get: Some(Self::voice_info_get),
+    };
+
+    unsafe extern "C" fn voice_info_get(
+        plugin: *const clap_plugin,
+        info: *mut clap_voice_info,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        if let Some(voice_info) = main_thread_state.plugin.voice_info() {
+            let info = &mut *info;
+
+            info.voice_count = voice_info.voice_count;
+            info.voice_capacity = voice_info.voice_capacity;
+            info.flags = if voice_info.note_id_addressable {
+                CLAP_VOICE_INFO_SUPPORTS_OVERLAPPING_NOTES
+            } else {
+                0
+            };
+
+            return true;
+        }
+
+        false
+    }
+}
+
 impl<P: Plugin> Instance<P> {
     const STATE: clap_plugin_state = clap_plugin_state {
         save: Some(Self::state_save),
@@ -872,6 +1663,21 @@ impl<P: Plugin> Instance<P> {
     unsafe extern "C" fn state_save(
         plugin: *const clap_plugin,
         stream: *const clap_ostream,
+    ) -> bool {
+        Self::save(plugin, stream, StateContext::Project)
+    }
+
+    unsafe extern "C" fn state_load(
+        plugin: *const clap_plugin,
+        stream: *const clap_istream,
+    ) -> bool {
+        Self::load(plugin, stream, StateContext::Project)
+    }
+
+    unsafe fn save(
+        plugin: *const clap_plugin,
+        stream: *const clap_ostream,
+        context: StateContext,
     ) -> bool {
         struct StreamWriter(*const clap_ostream);
 
@@ -901,16 +1707,19 @@ impl<P: Plugin> Instance<P> {
         }
 
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         instance.sync_plugin(main_thread_state);
-        let result = main_thread_state.plugin.save(&mut StreamWriter(stream));
+        let result = main_thread_state.plugin.save(&mut StreamWriter(stream), context);
         result.is_ok()
     }
 
-    unsafe extern "C" fn state_load(
+    unsafe fn load(
         plugin: *const clap_plugin,
         stream: *const clap_istream,
+        context: StateContext,
     ) -> bool {
         struct StreamReader(*const clap_istream);
 
@@ -936,22 +1745,117 @@ impl<P: Plugin> Instance<P> {
         }
 
         let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
         let main_thread_state = &mut *instance.main_thread_state.get();
 
         instance.sync_plugin(main_thread_state);
-        if main_thread_state.plugin.load(&mut StreamReader(stream)).is_ok() {
-            for (index, param) in instance.info.params.iter().enumerate() {
-                let value = main_thread_state.plugin.get_param(param.id);
-                instance.engine_params.set(index, value);
+        if main_thread_state.plugin.load(&mut StreamReader(stream), context).is_ok() {
+            instance.params_reloaded(main_thread_state);
+            return true;
+        }
+
+        false
+    }
+}
+
+impl<P: Plugin> Instance<P> {
+    const STATE_CONTEXT: clap_plugin_state_context = clap_plugin_state_context {
+        save: Some(Self::state_context_save),
+        load: Some(Self::state_context_load),
+    };
+
+    unsafe extern "C" fn state_context_save(
+        plugin: *const clap_plugin,
+        stream: *const clap_ostream,
+        context_type: clap_plugin_state_context_type,
+    ) -> bool {
+        match state_context(context_type) {
+            Some(context) => Self::save(plugin, stream, context),
+            None => false,
+        }
+    }
+
+    unsafe extern "C" fn state_context_load(
+        plugin: *const clap_plugin,
+        stream: *const clap_istream,
+        context_type: clap_plugin_state_context_type,
+    ) -> bool {
+        match state_context(context_type) {
+            Some(context) => Self::load(plugin, stream, context),
+            None => false,
+        }
+    }
+}
 
-                if let Some(view) = &mut main_thread_state.view {
-                    view.param_changed(param.id, value);
+impl<P: Plugin> Instance<P> {
+    const PRESET_LOAD: clap_plugin_preset_load = clap_plugin_preset_load {
+        from_location: Some(Self::preset_load_from_location),
+    };
+
+    unsafe extern "C" fn preset_load_from_location(
+        plugin: *const clap_plugin,
+        location_kind: clap_preset_discovery_location_kind,
+        location: *const c_char,
+        load_key: *const c_char,
+    ) -> bool {
+        let instance = &*(plugin as *const Self);
+        let _log = logging::enter_instance(plugin as usize);
+        instance.check_main_thread();
+        let main_thread_state = &mut *instance.main_thread_state.get();
+
+        instance.sync_plugin(main_thread_state);
+
+        let result = match location_kind {
+            // Factory presets are identified by their index, as declared by the preset provider.
+            CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN => {
+                let index = CStr::from_ptr(load_key).to_str().ok().and_then(|key| key.parse().ok());
+                match index {
+                    Some(index) if index < instance.info.presets.len() => {
+                        main_thread_state.plugin.load_preset(index)
+                    }
+                    _ => Err(io::ErrorKind::NotFound.into()),
                 }
             }
+            CLAP_PRESET_DISCOVERY_LOCATION_FILE => match CStr::from_ptr(location).to_str() {
+                Ok(path) => File::open(path)
+                    .and_then(|mut file| read_preset(&mut main_thread_state.plugin, &mut file))
+                    .map(|_| ()),
+                Err(_) => Err(io::ErrorKind::InvalidInput.into()),
+            },
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        };
 
-            return true;
-        }
+        match result {
+            Ok(()) => {
+                instance.params_reloaded(main_thread_state);
+
+                if let Some(host_preset_load) = main_thread_state.host_preset_load {
+                    (*host_preset_load).loaded.unwrap()(
+                        instance.host,
+                        location_kind,
+                        location,
+                        load_key,
+                    );
+                }
 
-        false
+                true
+            }
+            Err(err) => {
+                if let Some(host_preset_load) = main_thread_state.host_preset_load {
+                    let msg = CString::new(err.to_string()).unwrap_or_default();
+                    (*host_preset_load).on_error.unwrap()(
+                        instance.host,
+                        location_kind,
+                        location,
+                        load_key,
+                        err.raw_os_error().unwrap_or(0),
+                        msg.as_ptr(),
+                    );
+                }
+
+                false
+            }
+        }
     }
 }
@@ -6,14 +6,19 @@ mod factory;
 mod gui;
 mod host;
 mod instance;
+mod preset;
 
 #[cfg(test)]
 mod tests;
 
 #[doc(hidden)]
-pub use factory::Factory;
+pub use factory::{Factory, FactoryEntry};
 
 pub struct ClapInfo {
+    /// CLAP plugin id, reported to the host in the plugin descriptor. Saved state and preset files
+    /// identify the plugin by [`PluginInfo::id`] instead, so this may differ from it.
+    ///
+    /// [`PluginInfo::id`]: crate::plugin::PluginInfo::id
     pub id: String,
 }
 
@@ -47,12 +52,14 @@ impl EntryPoint {
 
 #[macro_export]
 macro_rules! clap {
-    ($plugin:ty) => {
+    ($($plugin:ty),+ $(,)?) => {
         #[allow(non_upper_case_globals)]
         #[no_mangle]
         static clap_entry: ::coupler::format::clap::EntryPoint = {
-            static FACTORY: ::coupler::format::clap::Factory<$plugin> =
-                ::coupler::format::clap::Factory::new();
+            static PLUGINS: &[::coupler::format::clap::FactoryEntry] =
+                &[$(::coupler::format::clap::FactoryEntry::new::<$plugin>()),+];
+            static FACTORY: ::coupler::format::clap::Factory =
+                ::coupler::format::clap::Factory::new(PLUGINS);
 
             unsafe extern "C" fn init(_plugin_path: *const ::std::ffi::c_char) -> bool {
                 FACTORY.init()
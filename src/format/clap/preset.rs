@@ -0,0 +1,249 @@
+use std::cell::UnsafeCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fs::File;
+use std::sync::Arc;
+use std::{io, ptr};
+
+use clap_sys::factory::preset_discovery::*;
+use clap_sys::universal_plugin_id::clap_universal_plugin_id;
+use clap_sys::version::CLAP_VERSION;
+
+use crate::plugin::PluginInfo;
+use crate::preset::{read_preset_header, EXTENSION};
+
+struct ProviderState {
+    descriptor: clap_preset_discovery_provider_descriptor,
+    id: CString,
+    name: CString,
+    #[allow(unused)]
+    vendor: CString,
+    clap_id: CString,
+    extension: CString,
+    info: Arc<PluginInfo>,
+}
+
+impl ProviderState {
+    fn new(clap_id: &CStr, info: &Arc<PluginInfo>) -> ProviderState {
+        let mut id = clap_id.to_bytes().to_vec();
+        id.extend_from_slice(b".presets");
+        let id = CString::new(id).unwrap();
+        let name = CString::new(&*info.name).unwrap();
+        let vendor = CString::new(&*info.vendor).unwrap();
+
+        ProviderState {
+            descriptor: clap_preset_discovery_provider_descriptor {
+                clap_version: CLAP_VERSION,
+                id: id.as_ptr(),
+                name: name.as_ptr(),
+                vendor: vendor.as_ptr(),
+            },
+            id,
+            name,
+            vendor,
+            clap_id: clap_id.to_owned(),
+            extension: CString::new(EXTENSION).unwrap(),
+            info: info.clone(),
+        }
+    }
+}
+
+#[repr(C)]
+struct Provider {
+    provider: clap_preset_discovery_provider,
+    indexer: *const clap_preset_discovery_indexer,
+    state: *const ProviderState,
+}
+
+impl Provider {
+    unsafe extern "C" fn init(provider: *const clap_preset_discovery_provider) -> bool {
+        let provider = &*(provider as *const Self);
+        let state = &*provider.state;
+
+        const FILETYPE_NAME: &CStr =
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"Coupler preset\0") };
+
+        let filetype = clap_preset_discovery_filetype {
+            name: FILETYPE_NAME.as_ptr(),
+            description: ptr::null(),
+            file_extension: state.extension.as_ptr(),
+        };
+        if !(*provider.indexer).declare_filetype.unwrap()(provider.indexer, &filetype) {
+            return false;
+        }
+
+        if state.info.presets.is_empty() {
+            return true;
+        }
+
+        let location = clap_preset_discovery_location {
+            flags: CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+            name: state.name.as_ptr(),
+            kind: CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+            location: ptr::null(),
+        };
+        (*provider.indexer).declare_location.unwrap()(provider.indexer, &location)
+    }
+
+    unsafe extern "C" fn destroy(provider: *const clap_preset_discovery_provider) {
+        drop(Box::from_raw(provider as *mut Self));
+    }
+
+    unsafe extern "C" fn get_metadata(
+        provider: *const clap_preset_discovery_provider,
+        location_kind: clap_preset_discovery_location_kind,
+        location: *const c_char,
+        receiver: *const clap_preset_discovery_metadata_receiver,
+    ) -> bool {
+        let provider = &*(provider as *const Self);
+        let state = &*provider.state;
+
+        const ABI: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"clap\0") };
+
+        let plugin_id = clap_universal_plugin_id {
+            abi: ABI.as_ptr(),
+            id: state.clap_id.as_ptr(),
+        };
+
+        if location_kind == CLAP_PRESET_DISCOVERY_LOCATION_FILE {
+            let header = CStr::from_ptr(location)
+                .to_str()
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+                .and_then(|path| read_preset_header(&mut File::open(path)?));
+            let header = match header {
+                Ok(header) => header,
+                Err(err) => {
+                    let msg = CString::new(err.to_string()).unwrap_or_default();
+                    let os_error = err.raw_os_error().unwrap_or(0);
+                    (*receiver).on_error.unwrap()(receiver, os_error, msg.as_ptr());
+                    return false;
+                }
+            };
+
+            // Preset files share an extension across plugins, so skip files saved by others. Files
+            // name the plugin by `PluginInfo::id` rather than the CLAP id, since they are shared with
+            // other formats.
+            if header.plugin_id == state.info.id {
+                let name = CString::new(header.name).unwrap_or_default();
+                if (*receiver).begin_preset.unwrap()(receiver, name.as_ptr(), ptr::null()) {
+                    (*receiver).add_plugin_id.unwrap()(receiver, &plugin_id);
+                }
+            }
+
+            return true;
+        }
+
+        if location_kind != CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+            return false;
+        }
+
+        for (index, preset) in state.info.presets.iter().enumerate() {
+            let name = CString::new(&*preset.name).unwrap();
+            let load_key = CString::new(index.to_string()).unwrap();
+            if !(*receiver).begin_preset.unwrap()(receiver, name.as_ptr(), load_key.as_ptr()) {
+                break;
+            }
+
+            (*receiver).add_plugin_id.unwrap()(receiver, &plugin_id);
+            (*receiver).set_flags.unwrap()(receiver, CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT);
+
+            if let Some(category) = &preset.category {
+                let category = CString::new(&**category).unwrap();
+                (*receiver).add_feature.unwrap()(receiver, category.as_ptr());
+            }
+        }
+
+        true
+    }
+
+    unsafe extern "C" fn get_extension(
+        _provider: *const clap_preset_discovery_provider,
+        _extension_id: *const c_char,
+    ) -> *const c_void {
+        ptr::null()
+    }
+}
+
+#[repr(C)]
+pub struct PresetFactory {
+    #[allow(unused)]
+    factory: clap_preset_discovery_factory,
+    state: UnsafeCell<Option<Vec<ProviderState>>>,
+}
+
+impl PresetFactory {
+    pub const fn new() -> PresetFactory {
+        PresetFactory {
+            factory: clap_preset_discovery_factory {
+                count: Some(Self::count),
+                get_descriptor: Some(Self::get_descriptor),
+                create: Some(Self::create),
+            },
+            state: UnsafeCell::new(None),
+        }
+    }
+
+    pub unsafe fn init<'a>(&self, plugins: impl Iterator<Item = (&'a CStr, &'a Arc<PluginInfo>)>) {
+        let providers = plugins.map(|(id, info)| ProviderState::new(id, info)).collect();
+        *self.state.get() = Some(providers);
+    }
+
+    pub unsafe fn deinit(&self) {
+        (*self.state.get()).take();
+    }
+
+    unsafe extern "C" fn count(factory: *const clap_preset_discovery_factory) -> u32 {
+        let factory = &*(factory as *const Self);
+
+        if let Some(providers) = &*factory.state.get() {
+            return providers.len() as u32;
+        }
+
+        0
+    }
+
+    unsafe extern "C" fn get_descriptor(
+        factory: *const clap_preset_discovery_factory,
+        index: u32,
+    ) -> *const clap_preset_discovery_provider_descriptor {
+        let factory = &*(factory as *const Self);
+
+        if let Some(providers) = &*factory.state.get() {
+            if let Some(provider) = providers.get(index as usize) {
+                return &provider.descriptor;
+            }
+        }
+
+        ptr::null()
+    }
+
+    unsafe extern "C" fn create(
+        factory: *const clap_preset_discovery_factory,
+        indexer: *const clap_preset_discovery_indexer,
+        provider_id: *const c_char,
+    ) -> *const clap_preset_discovery_provider {
+        let factory = &*(factory as *const Self);
+
+        if let Some(providers) = &*factory.state.get() {
+            let provider_id = CStr::from_ptr(provider_id);
+            for state in providers {
+                if provider_id == state.id.as_c_str() {
+                    let provider = Box::new(Provider {
+                        provider: clap_preset_discovery_provider {
+                            desc: &state.descriptor,
+                            provider_data: ptr::null_mut(),
+                            init: Some(Provider::init),
+                            destroy: Some(Provider::destroy),
+                            get_metadata: Some(Provider::get_metadata),
+                            get_extension: Some(Provider::get_extension),
+                        },
+                        indexer,
+                        state,
+                    });
+                    return Box::into_raw(provider) as *const clap_preset_discovery_provider;
+                }
+            }
+        }
+
+        ptr::null()
+    }
+}
@@ -1,53 +1,171 @@
-use std::ffi::{c_char, CStr};
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_void, CStr, CString};
 use std::fmt::{self, Formatter};
 use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{mem, ptr, slice};
 
-use crate::buffers::Buffers;
-use crate::events::Events;
-use crate::view::{ParentWindow, Size, View, ViewHost};
+use crate::buffers::{Buffer, BufferMut, Buffers};
+use crate::bus::{BusDir, BusInfo, Format, Layout};
+use crate::events::{Data, Event, Events, OutputEvents};
+use crate::view::{
+    AutomationState, MenuItem, ParamIndication, ParamMapping, ParentWindow, Size, View, ViewHost,
+};
 
-use clap_sys::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::color::clap_color;
+use clap_sys::events::*;
+use clap_sys::ext::audio_ports::*;
+use clap_sys::ext::audio_ports_activation::*;
+use clap_sys::ext::context_menu::*;
+use clap_sys::ext::param_indication::*;
+use clap_sys::ext::params::*;
+use clap_sys::ext::preset_load::*;
+use clap_sys::ext::remote_controls::*;
+use clap_sys::ext::render::*;
+use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::ext::state_context::*;
+use clap_sys::ext::thread_check::*;
+use clap_sys::ext::thread_pool::*;
+use clap_sys::ext::track_info::*;
+use clap_sys::ext::voice_info::*;
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::factory::preset_discovery::*;
+use clap_sys::host::clap_host;
+use clap_sys::id::{clap_id, CLAP_INVALID_ID};
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::*;
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::timestamp::clap_timestamp;
+use clap_sys::universal_plugin_id::clap_universal_plugin_id;
 use clap_sys::version::CLAP_VERSION;
 
-use crate::engine::{Config, Engine};
+use crate::engine::{Config, Engine, ProcessMode, ThreadPool};
 use crate::host::Host;
-use crate::params::{ParamId, ParamValue};
-use crate::plugin::{Plugin, PluginInfo};
+use crate::params::{ParamId, ParamInfo, ParamValue};
+use crate::plugin::{Category, Feature, Plugin, PluginInfo, RemoteControlsPage, VoiceInfo};
+use crate::preset::{write_preset, PresetInfo, EXTENSION};
+use crate::state::{State, StateContext, LEGACY_VERSION};
+use crate::track::{Color, TrackInfo, TrackKind};
+use crate::util::copy_cstring;
 
-use super::{ClapInfo, ClapPlugin, Factory};
+use super::instance::Instance;
+use super::{ClapInfo, ClapPlugin, Factory, FactoryEntry};
 
-const NAME: &str = "test plugin";
+const NAMES: [&str; 2] = ["test plugin", "other test plugin"];
 const VERSION: &str = "1.2.3";
 const VENDOR: &str = "test vendor";
 const URL: &str = "https://example.com/";
 const EMAIL: &str = "example@example.com";
-const ID: &str = "com.example.plugin";
+const IDS: [&str; 2] = ["com.example.plugin", "com.example.other-plugin"];
+const CLAP_IDS: [&str; 2] = ["com.example.plugin", "com.example.other-plugin.clap"];
+const OLD_VERSION: &str = "1.0.0";
+const PARAM: ParamId = 0;
+const OLD_PARAM: ParamId = 1;
+const PRESETS: [(&str, Option<&str>, ParamValue); 2] =
+    [("soft", None, 0.25), ("loud", Some("bright"), 1.0)];
+const VOICE_INFO: VoiceInfo = VoiceInfo {
+    voice_count: 8,
+    voice_capacity: 16,
+    note_id_addressable: true,
+};
+const NOTE_END: Data = Data::NoteEnd {
+    note_id: Some(3),
+    port: 0,
+    channel: 1,
+    key: 60,
+};
 
-struct TestPlugin;
+struct TestPlugin<const INDEX: usize> {
+    host: Host,
+    value: ParamValue,
+    data: Vec<u8>,
+    track_info: Option<TrackInfo>,
+    menu_actions: Vec<(ParamId, u32)>,
+    active_buses: Vec<bool>,
+    // Every mode the engine has been created with or switched to.
+    process_modes: Arc<Mutex<Vec<ProcessMode>>>,
+}
 
-impl Plugin for TestPlugin {
+impl<const INDEX: usize> Plugin for TestPlugin<INDEX> {
     type Engine = TestEngine;
     type View = TestView;
 
     fn info() -> PluginInfo {
         PluginInfo {
-            name: NAME.to_string(),
+            id: IDS[INDEX].to_string(),
+            name: NAMES[INDEX].to_string(),
             version: VERSION.to_string(),
             vendor: VENDOR.to_string(),
             url: URL.to_string(),
             email: EMAIL.to_string(),
-            buses: Vec::new(),
-            layouts: vec![],
-            params: Vec::new(),
+            category: Category::Effect,
+            features: vec![
+                Feature::Reverb,
+                Feature::Stereo,
+                Feature::Custom("custom".to_string()),
+            ],
+            // Only the second plugin has audio buses.
+            buses: if INDEX == 1 {
+                vec![
+                    BusInfo {
+                        name: "input".to_string(),
+                        dir: BusDir::In,
+                    },
+                    BusInfo {
+                        name: "output".to_string(),
+                        dir: BusDir::Out,
+                    },
+                ]
+            } else {
+                Vec::new()
+            },
+            layouts: if INDEX == 1 {
+                vec![Layout {
+                    formats: vec![Format::Mono, Format::Mono],
+                }]
+            } else {
+                vec![Layout::default()]
+            },
+            params: vec![ParamInfo {
+                id: PARAM,
+                name: "param".to_string(),
+                default: 0.5,
+                steps: None,
+            }],
+            // Only the first plugin has factory presets.
+            presets: if INDEX == 0 {
+                PRESETS
+                    .iter()
+                    .map(|&(name, category, _)| PresetInfo {
+                        name: name.to_string(),
+                        category: category.map(str::to_string),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            midi_mappings: Vec::new(),
             has_view: false,
         }
     }
-    fn new(_host: Host) -> Self {
-        TestPlugin
+    fn new(host: Host) -> Self {
+        TestPlugin {
+            host,
+            value: 0.5,
+            data: Vec::new(),
+            track_info: None,
+            menu_actions: Vec::new(),
+            active_buses: Vec::new(),
+            process_modes: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    fn set_param(&mut self, _id: ParamId, value: ParamValue) {
+        self.value = value;
     }
-    fn set_param(&mut self, _id: ParamId, _value: ParamValue) {}
     fn get_param(&self, _id: ParamId) -> ParamValue {
-        0.0
+        self.value
     }
     fn parse_param(&self, _id: ParamId, _text: &str) -> Option<ParamValue> {
         None
@@ -60,14 +178,31 @@ impl Plugin for TestPlugin {
     ) -> Result<(), fmt::Error> {
         Ok(())
     }
-    fn save(&self, _output: &mut impl Write) -> io::Result<()> {
+    fn save(&self, output: &mut impl Write, context: StateContext) -> io::Result<()> {
+        let mut state = State::capture(self);
+        if context != StateContext::Preset {
+            state.data = self.data.clone();
+        }
+        state.write(output)
+    }
+    fn load(&mut self, input: &mut impl Read, context: StateContext) -> io::Result<()> {
+        let mut state = State::read_or_legacy(input, &Self::info().id)?;
+        state.restore(self)?;
+        if context != StateContext::Preset {
+            self.data = state.data;
+        }
         Ok(())
     }
-    fn load(&mut self, _input: &mut impl Read) -> io::Result<()> {
+    fn load_preset(&mut self, index: usize) -> io::Result<()> {
+        self.value = PRESETS[index].2;
         Ok(())
     }
-    fn engine(&mut self, _config: &Config) -> Self::Engine {
-        TestEngine
+    fn engine(&mut self, config: &Config) -> Self::Engine {
+        self.active_buses.clone_from(&config.active_buses);
+        self.process_modes.lock().unwrap().push(config.process_mode);
+        TestEngine {
+            process_modes: self.process_modes.clone(),
+        }
     }
     fn view(&mut self, _host: ViewHost, _parent: &ParentWindow) -> Self::View {
         TestView
@@ -77,20 +212,93 @@ impl Plugin for TestPlugin {
     fn latency(&self, _config: &Config) -> u64 {
         0
     }
+
+    fn migrate(&mut self, from_version: &str, state: &mut State) -> io::Result<()> {
+        if from_version == OLD_VERSION {
+            if let Some(value) = state.remove_param(OLD_PARAM) {
+                state.set_param(PARAM, value);
+            }
+        }
+        if from_version == LEGACY_VERSION {
+            let legacy: serde_json::Value = serde_json::from_slice(&state.data)?;
+            let value = legacy["value"].as_f64().ok_or(io::ErrorKind::InvalidData)?;
+            state.set_param(PARAM, value);
+            state.data.clear();
+        }
+        Ok(())
+    }
+
+    fn voice_info(&self) -> Option<VoiceInfo> {
+        (INDEX == 0).then_some(VOICE_INFO)
+    }
+
+    fn track_info_changed(&mut self, info: &TrackInfo) {
+        self.track_info = Some(info.clone());
+    }
+
+    fn remote_controls(&self) -> Vec<RemoteControlsPage> {
+        vec![RemoteControlsPage {
+            section: "main".to_string(),
+            name: "page".to_string(),
+            params: vec![None, Some(PARAM)],
+        }]
+    }
+
+    fn param_context_menu(&self, _id: ParamId) -> Vec<MenuItem> {
+        vec![
+            MenuItem::Entry {
+                label: "reset".to_string(),
+                enabled: true,
+                action: 1,
+            },
+            MenuItem::Separator,
+            MenuItem::Submenu {
+                label: "more".to_string(),
+                enabled: true,
+                items: vec![MenuItem::CheckEntry {
+                    label: "fine".to_string(),
+                    enabled: false,
+                    checked: true,
+                    action: 2,
+                }],
+            },
+        ]
+    }
+
+    fn param_context_menu_action(&mut self, id: ParamId, action: u32) {
+        self.menu_actions.push((id, action));
+    }
 }
 
-impl ClapPlugin for TestPlugin {
+impl<const INDEX: usize> ClapPlugin for TestPlugin<INDEX> {
     fn clap_info() -> ClapInfo {
-        ClapInfo { id: ID.to_string() }
+        ClapInfo {
+            id: CLAP_IDS[INDEX].to_string(),
+        }
     }
 }
 
-struct TestEngine;
+struct TestEngine {
+    process_modes: Arc<Mutex<Vec<ProcessMode>>>,
+}
 
 impl Engine for TestEngine {
     fn reset(&mut self) {}
     fn flush(&mut self, _events: Events) {}
-    fn process(&mut self, _buffers: Buffers, _events: Events) {}
+    fn process(&mut self, buffers: Buffers, _events: Events) {
+        if let Ok((input, mut output)) = <(Buffer, BufferMut)>::try_from(buffers) {
+            output[0].copy_from_slice(&input[0]);
+        }
+    }
+    fn process_mode_changed(&mut self, mode: ProcessMode) {
+        self.process_modes.lock().unwrap().push(mode);
+    }
+    fn output_events(&mut self, events: &mut OutputEvents) {
+        events.push(Event {
+            time: 0,
+            data: NOTE_END,
+        });
+    }
 }
 
 struct TestView;
@@ -109,9 +317,14 @@ unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Result<&'a str, std::str::Utf8
     CStr::from_ptr(ptr).to_str()
 }
 
+static PLUGINS: &[FactoryEntry] = &[
+    FactoryEntry::new::<TestPlugin<0>>(),
+    FactoryEntry::new::<TestPlugin<1>>(),
+];
+
 #[test]
 fn factory() {
-    let factory = Factory::<TestPlugin>::new();
+    let factory = Factory::new(PLUGINS);
 
     let result = unsafe { factory.init() };
     assert!(result);
@@ -120,9 +333,9 @@ fn factory() {
         unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
 
     let plugin_count = unsafe { ((*plugin_factory).get_plugin_count).unwrap()(plugin_factory) };
-    assert_eq!(plugin_count, 1);
+    assert_eq!(plugin_count, 2);
 
-    let desc_ptr = unsafe { ((*plugin_factory).get_plugin_descriptor).unwrap()(plugin_factory, 1) };
+    let desc_ptr = unsafe { ((*plugin_factory).get_plugin_descriptor).unwrap()(plugin_factory, 2) };
     assert!(desc_ptr.is_null());
 
     let desc_ptr = unsafe { ((*plugin_factory).get_plugin_descriptor).unwrap()(plugin_factory, 0) };
@@ -132,15 +345,1484 @@ fn factory() {
     assert_eq!(desc.clap_version.major, CLAP_VERSION.major);
     assert_eq!(desc.clap_version.minor, CLAP_VERSION.minor);
     assert_eq!(desc.clap_version.revision, CLAP_VERSION.revision);
-    assert_eq!(unsafe { str_from_ptr(desc.id).unwrap() }, ID);
-    assert_eq!(unsafe { str_from_ptr(desc.name).unwrap() }, NAME);
+    assert_eq!(unsafe { str_from_ptr(desc.id).unwrap() }, IDS[0]);
+    assert_eq!(unsafe { str_from_ptr(desc.name).unwrap() }, NAMES[0]);
     assert_eq!(unsafe { str_from_ptr(desc.vendor).unwrap() }, VENDOR);
     assert_eq!(unsafe { str_from_ptr(desc.url).unwrap() }, URL);
     assert_eq!(unsafe { str_from_ptr(desc.manual_url).unwrap() }, "");
     assert_eq!(unsafe { str_from_ptr(desc.support_url).unwrap() }, "");
     assert_eq!(unsafe { str_from_ptr(desc.version).unwrap() }, VERSION);
     assert_eq!(unsafe { str_from_ptr(desc.description).unwrap() }, "");
-    assert!(unsafe { *desc.features }.is_null());
 
+    let features = unsafe { std::slice::from_raw_parts(desc.features, 5) };
+    assert_eq!(
+        unsafe { str_from_ptr(features[0]).unwrap() },
+        "audio-effect"
+    );
+    assert_eq!(unsafe { str_from_ptr(features[1]).unwrap() }, "reverb");
+    assert_eq!(unsafe { str_from_ptr(features[2]).unwrap() }, "stereo");
+    assert_eq!(unsafe { str_from_ptr(features[3]).unwrap() }, "custom");
+    assert!(features[4].is_null());
+
+    let desc_ptr = unsafe { ((*plugin_factory).get_plugin_descriptor).unwrap()(plugin_factory, 1) };
+    assert!(!desc_ptr.is_null());
+
+    let desc = unsafe { &*desc_ptr };
+    assert_eq!(unsafe { str_from_ptr(desc.id).unwrap() }, CLAP_IDS[1]);
+    assert_eq!(unsafe { str_from_ptr(desc.name).unwrap() }, NAMES[1]);
+
+    let plugin = unsafe {
+        ((*plugin_factory).create_plugin).unwrap()(
+            plugin_factory,
+            ptr::null(),
+            b"unknown\0".as_ptr() as *const c_char,
+        )
+    };
+    assert!(plugin.is_null());
+
+    unsafe { factory.deinit() };
+}
+
+unsafe extern "C" fn write_stream(
+    stream: *const clap_ostream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    let bytes = &mut *((*stream).ctx as *mut Vec<u8>);
+    bytes.extend_from_slice(slice::from_raw_parts(buffer as *const u8, size as usize));
+    size as i64
+}
+
+unsafe extern "C" fn read_stream(
+    stream: *const clap_istream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let bytes = &mut *((*stream).ctx as *mut &[u8]);
+    let len = bytes.len().min(size as usize);
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buffer as *mut u8, len);
+    *bytes = &bytes[len..];
+    len as i64
+}
+
+unsafe fn save_state(plugin: *const clap_plugin) -> Option<Vec<u8>> {
+    let state = (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_STATE.as_ptr());
+    let state = &*(state as *const clap_plugin_state);
+
+    let mut bytes = Vec::new();
+    let stream = clap_ostream {
+        ctx: &mut bytes as *mut Vec<u8> as *mut c_void,
+        write: Some(write_stream),
+    };
+    state.save.unwrap()(plugin, &stream).then_some(bytes)
+}
+
+unsafe fn load_state(plugin: *const clap_plugin, mut bytes: &[u8]) -> bool {
+    let state = (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_STATE.as_ptr());
+    let state = &*(state as *const clap_plugin_state);
+
+    let stream = clap_istream {
+        ctx: &mut bytes as *mut &[u8] as *mut c_void,
+        read: Some(read_stream),
+    };
+    state.load.unwrap()(plugin, &stream)
+}
+
+#[test]
+fn state() {
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let create = || unsafe {
+        let plugin =
+            ((*plugin_factory).create_plugin).unwrap()(plugin_factory, ptr::null(), id.as_ptr());
+        assert!(!plugin.is_null());
+        plugin
+    };
+    let plugin_state = |plugin: *const clap_plugin| unsafe {
+        let instance = &*(plugin as *const Instance<TestPlugin<0>>);
+        &mut (*instance.main_thread_state.get()).plugin
+    };
+
+    let plugin = create();
+    plugin_state(plugin).value = 0.25;
+    plugin_state(plugin).data = vec![1, 2, 3];
+    let bytes = unsafe { save_state(plugin) }.unwrap();
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+
+    let plugin = create();
+    assert!(unsafe { load_state(plugin, &bytes) });
+    assert_eq!(plugin_state(plugin).value, 0.25);
+    assert_eq!(plugin_state(plugin).data, [1, 2, 3]);
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    assert_eq!(
+        instance.engine_params.poll().collect::<Vec<_>>(),
+        [(0, 0.25)]
+    );
+
+    // A state from an older version is migrated, and unknown params are ignored.
+    let mut old = State::new(IDS[0], OLD_VERSION);
+    old.set_param(OLD_PARAM, 0.75);
+    old.set_param(99, 1.0);
+    let mut bytes = Vec::new();
+    old.write(&mut bytes).unwrap();
+    assert!(unsafe { load_state(plugin, &bytes) });
+    assert_eq!(plugin_state(plugin).value, 0.75);
+
+    // Data saved before the plugin used `State` is passed to `migrate`.
+    assert!(unsafe { load_state(plugin, br#"{"value":0.125}"#) });
+    assert_eq!(plugin_state(plugin).value, 0.125);
+    assert!(plugin_state(plugin).data.is_empty());
+    assert!(!unsafe { load_state(plugin, b"garbage") });
+
+    // Params missing from the state fall back to their defaults.
+    let mut bytes = Vec::new();
+    State::new(IDS[0], VERSION).write(&mut bytes).unwrap();
+    assert!(unsafe { load_state(plugin, &bytes) });
+    assert_eq!(plugin_state(plugin).value, 0.5);
+
+    // A state from a different plugin is rejected.
+    let mut bytes = Vec::new();
+    State::new(IDS[1], VERSION).write(&mut bytes).unwrap();
+    assert!(!unsafe { load_state(plugin, &bytes) });
+    assert_eq!(plugin_state(plugin).value, 0.5);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn state_context() {
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin = unsafe {
+        ((*plugin_factory).create_plugin).unwrap()(plugin_factory, ptr::null(), id.as_ptr())
+    };
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let state_context =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_STATE_CONTEXT.as_ptr()) };
+    let state_context = unsafe { &*(state_context as *const clap_plugin_state_context) };
+
+    let save = |context_type| unsafe {
+        let mut bytes = Vec::new();
+        let stream = clap_ostream {
+            ctx: &mut bytes as *mut Vec<u8> as *mut c_void,
+            write: Some(write_stream),
+        };
+        state_context.save.unwrap()(plugin, &stream, context_type).then_some(bytes)
+    };
+    let load = |mut bytes: &[u8], context_type| unsafe {
+        let stream = clap_istream {
+            ctx: &mut bytes as *mut &[u8] as *mut c_void,
+            read: Some(read_stream),
+        };
+        state_context.load.unwrap()(plugin, &stream, context_type)
+    };
+
+    plugin_state().value = 0.25;
+    plugin_state().data = vec![1, 2, 3];
+
+    let project = save(CLAP_STATE_CONTEXT_FOR_PROJECT).unwrap();
+    assert_eq!(State::read(&mut &project[..]).unwrap().data, [1, 2, 3]);
+    let duplicate = save(CLAP_STATE_CONTEXT_FOR_DUPLICATE).unwrap();
+    assert_eq!(State::read(&mut &duplicate[..]).unwrap().data, [1, 2, 3]);
+    assert!(save(0).is_none());
+
+    // The test plugin leaves its extra data out of presets.
+    let preset = save(CLAP_STATE_CONTEXT_FOR_PRESET).unwrap();
+    let state = State::read(&mut &preset[..]).unwrap();
+    assert_eq!(state.params, [(PARAM, 0.25)]);
+    assert!(state.data.is_empty());
+
+    plugin_state().value = 0.5;
+    assert!(load(&preset, CLAP_STATE_CONTEXT_FOR_PRESET));
+    assert_eq!(plugin_state().value, 0.25);
+    assert_eq!(plugin_state().data, [1, 2, 3]);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[derive(Default)]
+struct TestHost {
+    restarts: Cell<u32>,
+    rescans: Cell<u32>,
+    flush_requests: Cell<u32>,
+    presets_loaded: Cell<u32>,
+    preset_errors: Cell<u32>,
+    voice_info_changes: Cell<u32>,
+    remote_controls_changes: Cell<u32>,
+    track_flags: Cell<u64>,
+    plugin: Cell<Option<*const clap_plugin>>,
+    reject_exec: Cell<bool>,
+    exec_requests: Cell<u32>,
+    audio_port_rescans: Cell<u32>,
+}
+
+impl TestHost {
+    unsafe fn from_ptr<'a>(host: *const clap_host) -> &'a TestHost {
+        &*((*host).host_data as *const TestHost)
+    }
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    extension_id: *const c_char,
+) -> *const c_void {
+    if CStr::from_ptr(extension_id) == CLAP_EXT_AUDIO_PORTS {
+        return &HOST_AUDIO_PORTS as *const clap_host_audio_ports as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_PARAMS {
+        return &HOST_PARAMS as *const clap_host_params as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_PRESET_LOAD {
+        return &HOST_PRESET_LOAD as *const clap_host_preset_load as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_REMOTE_CONTROLS {
+        return &HOST_REMOTE_CONTROLS as *const clap_host_remote_controls as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_THREAD_CHECK {
+        return &HOST_THREAD_CHECK as *const clap_host_thread_check as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_THREAD_POOL {
+        return &HOST_THREAD_POOL as *const clap_host_thread_pool as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_TRACK_INFO {
+        return &HOST_TRACK_INFO as *const clap_host_track_info as *const c_void;
+    }
+
+    if CStr::from_ptr(extension_id) == CLAP_EXT_VOICE_INFO {
+        return &HOST_VOICE_INFO as *const clap_host_voice_info as *const c_void;
+    }
+
+    ptr::null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+unsafe extern "C" fn host_request_restart(host: *const clap_host) {
+    let host = TestHost::from_ptr(host);
+    host.restarts.set(host.restarts.get() + 1);
+}
+
+static HOST_AUDIO_PORTS: clap_host_audio_ports = clap_host_audio_ports {
+    is_rescan_flag_supported: Some(host_audio_ports_is_rescan_flag_supported),
+    rescan: Some(host_audio_ports_rescan),
+};
+
+unsafe extern "C" fn host_audio_ports_is_rescan_flag_supported(
+    _host: *const clap_host,
+    _flag: u32,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn host_audio_ports_rescan(host: *const clap_host, flags: u32) {
+    assert_eq!(flags, CLAP_AUDIO_PORTS_RESCAN_LIST);
+    let host = TestHost::from_ptr(host);
+    host.audio_port_rescans.set(host.audio_port_rescans.get() + 1);
+}
+
+static HOST_PARAMS: clap_host_params = clap_host_params {
+    rescan: Some(host_params_rescan),
+    clear: Some(host_params_clear),
+    request_flush: Some(host_params_request_flush),
+};
+
+unsafe extern "C" fn host_params_rescan(host: *const clap_host, flags: clap_param_rescan_flags) {
+    assert_eq!(flags, CLAP_PARAM_RESCAN_VALUES);
+    let host = TestHost::from_ptr(host);
+    host.rescans.set(host.rescans.get() + 1);
+}
+
+unsafe extern "C" fn host_params_clear(
+    _host: *const clap_host,
+    _param_id: clap_id,
+    _flags: clap_param_clear_flags,
+) {
+}
+
+unsafe extern "C" fn host_params_request_flush(host: *const clap_host) {
+    let host = TestHost::from_ptr(host);
+    host.flush_requests.set(host.flush_requests.get() + 1);
+}
+
+static HOST_PRESET_LOAD: clap_host_preset_load = clap_host_preset_load {
+    on_error: Some(host_preset_load_on_error),
+    loaded: Some(host_preset_load_loaded),
+};
+
+unsafe extern "C" fn host_preset_load_on_error(
+    host: *const clap_host,
+    _location_kind: clap_preset_discovery_location_kind,
+    _location: *const c_char,
+    _load_key: *const c_char,
+    _os_error: i32,
+    _msg: *const c_char,
+) {
+    let host = TestHost::from_ptr(host);
+    host.preset_errors.set(host.preset_errors.get() + 1);
+}
+
+unsafe extern "C" fn host_preset_load_loaded(
+    host: *const clap_host,
+    _location_kind: clap_preset_discovery_location_kind,
+    _location: *const c_char,
+    _load_key: *const c_char,
+) {
+    let host = TestHost::from_ptr(host);
+    host.presets_loaded.set(host.presets_loaded.get() + 1);
+}
+
+// A host which can't tell thread roles apart may leave these null.
+static HOST_THREAD_CHECK: clap_host_thread_check = clap_host_thread_check {
+    is_main_thread: None,
+    is_audio_thread: None,
+};
+
+static HOST_VOICE_INFO: clap_host_voice_info = clap_host_voice_info {
+    changed: Some(host_voice_info_changed),
+};
+
+unsafe extern "C" fn host_voice_info_changed(host: *const clap_host) {
+    let host = TestHost::from_ptr(host);
+    host.voice_info_changes.set(host.voice_info_changes.get() + 1);
+}
+
+static HOST_REMOTE_CONTROLS: clap_host_remote_controls = clap_host_remote_controls {
+    changed: Some(host_remote_controls_changed),
+    suggest_page: Some(host_remote_controls_suggest_page),
+};
+
+unsafe extern "C" fn host_remote_controls_changed(host: *const clap_host) {
+    let host = TestHost::from_ptr(host);
+    host.remote_controls_changes.set(host.remote_controls_changes.get() + 1);
+}
+
+unsafe extern "C" fn host_remote_controls_suggest_page(_host: *const clap_host, _page_id: clap_id) {
+}
+
+static HOST_THREAD_POOL: clap_host_thread_pool = clap_host_thread_pool {
+    request_exec: Some(host_thread_pool_request_exec),
+};
+
+unsafe extern "C" fn host_thread_pool_request_exec(host: *const clap_host, num_tasks: u32) -> bool {
+    let host = TestHost::from_ptr(host);
+    host.exec_requests.set(host.exec_requests.get() + 1);
+
+    if host.reject_exec.get() {
+        return false;
+    }
+
+    let plugin = host.plugin.get().unwrap();
+    let thread_pool = (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_THREAD_POOL.as_ptr());
+    let thread_pool = &*(thread_pool as *const clap_plugin_thread_pool);
+    for task_index in 0..num_tasks {
+        thread_pool.exec.unwrap()(plugin, task_index);
+    }
+
+    true
+}
+
+static HOST_TRACK_INFO: clap_host_track_info = clap_host_track_info {
+    get: Some(host_track_info_get),
+};
+
+unsafe extern "C" fn host_track_info_get(
+    host: *const clap_host,
+    info: *mut clap_track_info,
+) -> bool {
+    let host = TestHost::from_ptr(host);
+    let info = &mut *info;
+
+    info.flags = host.track_flags.get();
+    copy_cstring("track", &mut info.name);
+    info.color = clap_color {
+        alpha: 255,
+        red: 1,
+        green: 2,
+        blue: 3,
+    };
+    info.audio_channel_count = 2;
+    info.audio_port_type = ptr::null();
+
+    true
+}
+
+unsafe extern "C" fn input_events_size(_list: *const clap_input_events) -> u32 {
+    0
+}
+
+unsafe extern "C" fn input_events_get(
+    _list: *const clap_input_events,
+    _index: u32,
+) -> *const clap_event_header {
+    ptr::null()
+}
+
+unsafe extern "C" fn output_events_try_push(
+    list: *const clap_output_events,
+    event: *const clap_event_header,
+) -> bool {
+    let values = &*((*list).ctx as *const RefCell<Vec<(clap_id, f64)>>);
+    if (*event).space_id == CLAP_CORE_EVENT_SPACE_ID && (*event).type_ == CLAP_EVENT_PARAM_VALUE {
+        let event = &*(event as *const clap_event_param_value);
+        values.borrow_mut().push((event.param_id, event.value));
+    }
+    true
+}
+
+unsafe extern "C" fn output_events_push_note_end(
+    list: *const clap_output_events,
+    event: *const clap_event_header,
+) -> bool {
+    let note_ends = &*((*list).ctx as *const RefCell<Vec<(i32, i16, i16, i16)>>);
+    if (*event).space_id == CLAP_CORE_EVENT_SPACE_ID && (*event).type_ == CLAP_EVENT_NOTE_END {
+        let event = &*(event as *const clap_event_note);
+        note_ends
+            .borrow_mut()
+            .push((event.note_id, event.port_index, event.channel, event.key));
+    }
+    true
+}
+
+fn make_host(test_host: &TestHost) -> clap_host {
+    clap_host {
+        clap_version: CLAP_VERSION,
+        host_data: test_host as *const TestHost as *mut c_void,
+        name: ptr::null(),
+        vendor: ptr::null(),
+        url: ptr::null(),
+        version: ptr::null(),
+        get_extension: Some(host_get_extension),
+        request_restart: Some(host_request_restart),
+        request_process: Some(host_request),
+        request_callback: Some(host_request),
+    }
+}
+
+#[test]
+fn host_params() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    // Loading state asks the host to rescan parameter values.
+    let mut state = State::new(IDS[0], VERSION);
+    state.set_param(PARAM, 0.25);
+    let mut bytes = Vec::new();
+    state.write(&mut bytes).unwrap();
+    assert!(unsafe { load_state(plugin, &bytes) });
+    assert_eq!(test_host.rescans.get(), 1);
+
+    // Programmatic changes are reported to the host on the next flush.
+    plugin_state().host.set_param(PARAM, 0.75);
+    assert_eq!(test_host.flush_requests.get(), 1);
+
+    let params = unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_PARAMS.as_ptr()) };
+    let params = unsafe { &*(params as *const clap_plugin_params) };
+
+    let in_events = clap_input_events {
+        ctx: ptr::null_mut(),
+        size: Some(input_events_size),
+        get: Some(input_events_get),
+    };
+    let values = RefCell::new(Vec::new());
+    let out_events = clap_output_events {
+        ctx: &values as *const RefCell<Vec<(clap_id, f64)>> as *mut c_void,
+        try_push: Some(output_events_try_push),
+    };
+    unsafe { params.flush.unwrap()(plugin, &in_events, &out_events) };
+
+    assert_eq!(*values.borrow(), [(PARAM, 0.75)]);
+    assert_eq!(plugin_state().value, 0.75);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[derive(Default)]
+struct TestIndexer {
+    filetypes: RefCell<Vec<String>>,
+    locations: RefCell<Vec<(u32, clap_preset_discovery_location_kind)>>,
+    presets: RefCell<Vec<(String, String, Vec<String>)>>,
+    plugin_ids: RefCell<Vec<String>>,
+    errors: Cell<u32>,
+}
+
+impl TestIndexer {
+    unsafe fn from_indexer<'a>(indexer: *const clap_preset_discovery_indexer) -> &'a TestIndexer {
+        &*((*indexer).indexer_data as *const TestIndexer)
+    }
+
+    unsafe fn from_receiver<'a>(
+        receiver: *const clap_preset_discovery_metadata_receiver,
+    ) -> &'a TestIndexer {
+        &*((*receiver).receiver_data as *const TestIndexer)
+    }
+}
+
+unsafe extern "C" fn indexer_declare_filetype(
+    indexer: *const clap_preset_discovery_indexer,
+    filetype: *const clap_preset_discovery_filetype,
+) -> bool {
+    let indexer = TestIndexer::from_indexer(indexer);
+    let extension = str_from_ptr((*filetype).file_extension).unwrap().to_string();
+    indexer.filetypes.borrow_mut().push(extension);
+    true
+}
+
+unsafe extern "C" fn indexer_declare_location(
+    indexer: *const clap_preset_discovery_indexer,
+    location: *const clap_preset_discovery_location,
+) -> bool {
+    let location = &*location;
+    assert!(location.location.is_null());
+    let indexer = TestIndexer::from_indexer(indexer);
+    indexer.locations.borrow_mut().push((location.flags, location.kind));
+    true
+}
+
+unsafe extern "C" fn indexer_declare_soundpack(
+    _indexer: *const clap_preset_discovery_indexer,
+    _soundpack: *const clap_preset_discovery_soundpack,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn indexer_get_extension(
+    _indexer: *const clap_preset_discovery_indexer,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn receiver_on_error(
+    receiver: *const clap_preset_discovery_metadata_receiver,
+    _os_error: i32,
+    _error_message: *const c_char,
+) {
+    let indexer = TestIndexer::from_receiver(receiver);
+    indexer.errors.set(indexer.errors.get() + 1);
+}
+
+unsafe extern "C" fn receiver_begin_preset(
+    receiver: *const clap_preset_discovery_metadata_receiver,
+    name: *const c_char,
+    load_key: *const c_char,
+) -> bool {
+    let indexer = TestIndexer::from_receiver(receiver);
+    let name = str_from_ptr(name).unwrap().to_string();
+    let load_key = if load_key.is_null() {
+        String::new()
+    } else {
+        str_from_ptr(load_key).unwrap().to_string()
+    };
+    indexer.presets.borrow_mut().push((name, load_key, Vec::new()));
+    true
+}
+
+unsafe extern "C" fn receiver_add_plugin_id(
+    receiver: *const clap_preset_discovery_metadata_receiver,
+    plugin_id: *const clap_universal_plugin_id,
+) {
+    let indexer = TestIndexer::from_receiver(receiver);
+    assert_eq!(str_from_ptr((*plugin_id).abi).unwrap(), "clap");
+    let id = str_from_ptr((*plugin_id).id).unwrap().to_string();
+    indexer.plugin_ids.borrow_mut().push(id);
+}
+
+unsafe extern "C" fn receiver_set_soundpack_id(
+    _receiver: *const clap_preset_discovery_metadata_receiver,
+    _soundpack_id: *const c_char,
+) {
+}
+
+unsafe extern "C" fn receiver_set_flags(
+    _receiver: *const clap_preset_discovery_metadata_receiver,
+    flags: u32,
+) {
+    assert_eq!(flags, CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT);
+}
+
+unsafe extern "C" fn receiver_add_string(
+    _receiver: *const clap_preset_discovery_metadata_receiver,
+    _string: *const c_char,
+) {
+}
+
+unsafe extern "C" fn receiver_set_timestamps(
+    _receiver: *const clap_preset_discovery_metadata_receiver,
+    _creation_time: clap_timestamp,
+    _modification_time: clap_timestamp,
+) {
+}
+
+unsafe extern "C" fn receiver_add_feature(
+    receiver: *const clap_preset_discovery_metadata_receiver,
+    feature: *const c_char,
+) {
+    let indexer = TestIndexer::from_receiver(receiver);
+    let mut presets = indexer.presets.borrow_mut();
+    let feature = str_from_ptr(feature).unwrap().to_string();
+    presets.last_mut().unwrap().2.push(feature);
+}
+
+unsafe extern "C" fn receiver_add_extra_info(
+    _receiver: *const clap_preset_discovery_metadata_receiver,
+    _key: *const c_char,
+    _value: *const c_char,
+) {
+}
+
+fn make_indexer(test_indexer: &TestIndexer) -> clap_preset_discovery_indexer {
+    clap_preset_discovery_indexer {
+        clap_version: CLAP_VERSION,
+        name: ptr::null(),
+        vendor: ptr::null(),
+        url: ptr::null(),
+        version: ptr::null(),
+        indexer_data: test_indexer as *const TestIndexer as *mut c_void,
+        declare_filetype: Some(indexer_declare_filetype),
+        declare_location: Some(indexer_declare_location),
+        declare_soundpack: Some(indexer_declare_soundpack),
+        get_extension: Some(indexer_get_extension),
+    }
+}
+
+fn make_receiver(test_indexer: &TestIndexer) -> clap_preset_discovery_metadata_receiver {
+    clap_preset_discovery_metadata_receiver {
+        receiver_data: test_indexer as *const TestIndexer as *mut c_void,
+        on_error: Some(receiver_on_error),
+        begin_preset: Some(receiver_begin_preset),
+        add_plugin_id: Some(receiver_add_plugin_id),
+        set_soundpack_id: Some(receiver_set_soundpack_id),
+        set_flags: Some(receiver_set_flags),
+        add_creator: Some(receiver_add_string),
+        set_description: Some(receiver_add_string),
+        set_timestamps: Some(receiver_set_timestamps),
+        add_feature: Some(receiver_add_feature),
+        add_extra_info: Some(receiver_add_extra_info),
+    }
+}
+
+#[test]
+fn preset_discovery() {
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let preset_factory = unsafe {
+        factory.get(CLAP_PRESET_DISCOVERY_FACTORY_ID.as_ptr())
+            as *const clap_preset_discovery_factory
+    };
+    assert!(!preset_factory.is_null());
+
+    // Every plugin gets a provider, for indexing preset files.
+    let count = unsafe { (*preset_factory).count.unwrap()(preset_factory) };
+    assert_eq!(count, 2);
+
+    let desc = unsafe { (*preset_factory).get_descriptor.unwrap()(preset_factory, 0) };
+    let desc = unsafe { &*desc };
+    assert_eq!(
+        unsafe { str_from_ptr(desc.id).unwrap() },
+        "com.example.plugin.presets"
+    );
+    assert_eq!(unsafe { str_from_ptr(desc.name).unwrap() }, NAMES[0]);
+    assert_eq!(unsafe { str_from_ptr(desc.vendor).unwrap() }, VENDOR);
+
+    let test_indexer = TestIndexer::default();
+    let indexer = make_indexer(&test_indexer);
+
+    let provider = unsafe {
+        (*preset_factory).create.unwrap()(
+            preset_factory,
+            &indexer,
+            b"unknown\0".as_ptr() as *const c_char,
+        )
+    };
+    assert!(provider.is_null());
+
+    let provider = unsafe { (*preset_factory).create.unwrap()(preset_factory, &indexer, desc.id) };
+    assert!(!provider.is_null());
+
+    assert!(unsafe { (*provider).init.unwrap()(provider) });
+    assert_eq!(*test_indexer.filetypes.borrow(), [EXTENSION]);
+    assert_eq!(
+        *test_indexer.locations.borrow(),
+        [(
+            CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+            CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN
+        )]
+    );
+
+    let receiver = make_receiver(&test_indexer);
+    let result = unsafe {
+        (*provider).get_metadata.unwrap()(
+            provider,
+            CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+            ptr::null(),
+            &receiver,
+        )
+    };
+    assert!(result);
+
+    let expected = [
+        ("soft".to_string(), "0".to_string(), vec![]),
+        (
+            "loud".to_string(),
+            "1".to_string(),
+            vec!["bright".to_string()],
+        ),
+    ];
+    assert_eq!(*test_indexer.presets.borrow(), expected);
+    assert_eq!(*test_indexer.plugin_ids.borrow(), [IDS[0], IDS[0]]);
+
+    unsafe { (*provider).destroy.unwrap()(provider) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn preset_load() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let preset_load =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_PRESET_LOAD.as_ptr()) };
+    let preset_load = unsafe { &*(preset_load as *const clap_plugin_preset_load) };
+
+    let load = |load_key: &[u8]| unsafe {
+        preset_load.from_location.unwrap()(
+            plugin,
+            CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+            ptr::null(),
+            load_key.as_ptr() as *const c_char,
+        )
+    };
+
+    assert!(load(b"1\0"));
+    assert_eq!(plugin_state().value, PRESETS[1].2);
+    assert_eq!(test_host.presets_loaded.get(), 1);
+    assert_eq!(test_host.rescans.get(), 1);
+
+    assert!(!load(b"2\0"));
+    assert!(!load(b"loud\0"));
+    assert_eq!(plugin_state().value, PRESETS[1].2);
+    assert_eq!(test_host.preset_errors.get(), 2);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn render() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let process_modes = || unsafe {
+        let plugin = &(*instance.main_thread_state.get()).plugin;
+        plugin.process_modes.lock().unwrap().clone()
+    };
+
+    let render = unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_RENDER.as_ptr()) };
+    let render = unsafe { &*(render as *const clap_plugin_render) };
+    assert!(!unsafe { render.has_hard_realtime_requirement.unwrap()(plugin) });
+
+    // The mode set before activation is passed to the engine in its config.
+    assert!(unsafe { render.set.unwrap()(plugin, CLAP_RENDER_OFFLINE) });
+    assert!(!unsafe { render.set.unwrap()(plugin, 7) });
+    assert!(unsafe { (*plugin).activate.unwrap()(plugin, 44100.0, 1, 4) });
+    assert_eq!(process_modes(), [ProcessMode::Offline]);
+
+    let in_events = clap_input_events {
+        ctx: ptr::null_mut(),
+        size: Some(input_events_size),
+        get: Some(input_events_get),
+    };
+    let values: RefCell<Vec<(clap_id, f64)>> = RefCell::new(Vec::new());
+    let out_events = clap_output_events {
+        ctx: &values as *const _ as *mut c_void,
+        try_push: Some(output_events_try_push),
+    };
+    let process = clap_process {
+        steady_time: -1,
+        frames_count: 4,
+        transport: ptr::null(),
+        audio_inputs: ptr::null(),
+        audio_outputs: ptr::null_mut(),
+        audio_inputs_count: 0,
+        audio_outputs_count: 0,
+        in_events: &in_events,
+        out_events: &out_events,
+    };
+
+    // A running engine is told about changes at the next process call, without a restart.
+    assert!(unsafe { render.set.unwrap()(plugin, CLAP_RENDER_REALTIME) });
+    assert_eq!(process_modes(), [ProcessMode::Offline]);
+    for _ in 0..2 {
+        assert_eq!(
+            unsafe { (*plugin).process.unwrap()(plugin, &process) },
+            CLAP_PROCESS_CONTINUE
+        );
+    }
+    assert_eq!(
+        process_modes(),
+        [ProcessMode::Offline, ProcessMode::Realtime]
+    );
+    assert_eq!(test_host.restarts.get(), 0);
+
+    unsafe { (*plugin).deactivate.unwrap()(plugin) };
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn voice_info() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let voice_info =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_VOICE_INFO.as_ptr()) };
+    let voice_info = unsafe { &*(voice_info as *const clap_plugin_voice_info) };
+    let mut info: clap_voice_info = unsafe { mem::zeroed() };
+    assert!(unsafe { voice_info.get.unwrap()(plugin, &mut info) });
+    assert_eq!(info.voice_count, VOICE_INFO.voice_count);
+    assert_eq!(info.voice_capacity, VOICE_INFO.voice_capacity);
+    assert_eq!(info.flags, CLAP_VOICE_INFO_SUPPORTS_OVERLAPPING_NOTES);
+
+    plugin_state().host.voice_info_changed();
+    assert_eq!(test_host.voice_info_changes.get(), 1);
+
+    // Note end events from the engine are passed on to the host.
+    assert!(unsafe { (*plugin).activate.unwrap()(plugin, 44100.0, 1, 256) });
+
+    let params = unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_PARAMS.as_ptr()) };
+    let params = unsafe { &*(params as *const clap_plugin_params) };
+
+    let in_events = clap_input_events {
+        ctx: ptr::null_mut(),
+        size: Some(input_events_size),
+        get: Some(input_events_get),
+    };
+    let note_ends: RefCell<Vec<(i32, i16, i16, i16)>> = RefCell::new(Vec::new());
+    let out_events = clap_output_events {
+        ctx: &note_ends as *const _ as *mut c_void,
+        try_push: Some(output_events_push_note_end),
+    };
+    unsafe { params.flush.unwrap()(plugin, &in_events, &out_events) };
+    assert_eq!(*note_ends.borrow(), [(3, 0, 1, 60)]);
+
+    unsafe { (*plugin).deactivate.unwrap()(plugin) };
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+
+    // Plugins without voice info report failure, and don't send note end events.
+    let id = CString::new(CLAP_IDS[1]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+    let voice_info =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_VOICE_INFO.as_ptr()) };
+    let voice_info = unsafe { &*(voice_info as *const clap_plugin_voice_info) };
+    assert!(!unsafe { voice_info.get.unwrap()(plugin, &mut info) });
+
+    assert!(unsafe { (*plugin).activate.unwrap()(plugin, 44100.0, 1, 256) });
+    note_ends.borrow_mut().clear();
+    unsafe { params.flush.unwrap()(plugin, &in_events, &out_events) };
+    assert!(note_ends.borrow().is_empty());
+
+    unsafe { (*plugin).deactivate.unwrap()(plugin) };
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn remote_controls() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let remote_controls =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_REMOTE_CONTROLS.as_ptr()) };
+    let remote_controls = unsafe { &*(remote_controls as *const clap_plugin_remote_controls) };
+    assert_eq!(unsafe { remote_controls.count.unwrap()(plugin) }, 1);
+
+    let mut page: clap_remote_controls_page = unsafe { mem::zeroed() };
+    assert!(unsafe { remote_controls.get.unwrap()(plugin, 0, &mut page) });
+    assert_eq!(
+        unsafe { str_from_ptr(page.section_name.as_ptr()) },
+        Ok("main")
+    );
+    assert_eq!(unsafe { str_from_ptr(page.page_name.as_ptr()) }, Ok("page"));
+    assert_eq!(page.page_id, 0);
+    assert_eq!(
+        page.param_ids[..3],
+        [CLAP_INVALID_ID, PARAM, CLAP_INVALID_ID]
+    );
+    assert!(!unsafe { remote_controls.get.unwrap()(plugin, 1, &mut page) });
+
+    plugin_state().host.remote_controls_changed();
+    assert_eq!(test_host.remote_controls_changes.get(), 1);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn track_info() {
+    let test_host = TestHost::default();
+    test_host
+        .track_flags
+        .set(CLAP_TRACK_INFO_HAS_TRACK_NAME | CLAP_TRACK_INFO_HAS_TRACK_COLOR);
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    // The plugin receives the track info when it is initialized.
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+    assert_eq!(
+        plugin_state().track_info,
+        Some(TrackInfo {
+            name: Some("track".to_string()),
+            color: Some(Color {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 255,
+            }),
+            channel_count: None,
+            kind: TrackKind::Regular,
+        })
+    );
+
+    test_host
+        .track_flags
+        .set(CLAP_TRACK_INFO_HAS_AUDIO_CHANNEL | CLAP_TRACK_INFO_IS_FOR_MASTER);
+    let track_info =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_TRACK_INFO.as_ptr()) };
+    let track_info = unsafe { &*(track_info as *const clap_plugin_track_info) };
+    unsafe { track_info.changed.unwrap()(plugin) };
+    assert_eq!(
+        plugin_state().track_info,
+        Some(TrackInfo {
+            name: None,
+            color: None,
+            channel_count: Some(2),
+            kind: TrackKind::Master,
+        })
+    );
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn param_indication() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let indications = || unsafe { &(*instance.main_thread_state.get()).param_indications };
+
+    let param_indication =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_PARAM_INDICATION.as_ptr()) };
+    let param_indication = unsafe { &*(param_indication as *const clap_plugin_param_indication) };
+
+    let color = clap_color {
+        alpha: 255,
+        red: 1,
+        green: 2,
+        blue: 3,
+    };
+    unsafe {
+        param_indication.set_automation.unwrap()(
+            plugin,
+            PARAM,
+            CLAP_PARAM_INDICATION_AUTOMATION_PLAYING,
+            &color,
+        );
+        param_indication.set_mapping.unwrap()(
+            plugin,
+            PARAM,
+            true,
+            ptr::null(),
+            b"CC 21\0".as_ptr() as *const c_char,
+            ptr::null(),
+        );
+    }
+    assert_eq!(
+        indications()[0],
+        ParamIndication {
+            automation: AutomationState::Playing,
+            automation_color: Some(Color {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 255,
+            }),
+            mapping: Some(ParamMapping {
+                color: None,
+                label: Some("CC 21".to_string()),
+                description: None,
+            }),
+        }
+    );
+
+    // Unknown parameters are ignored. Clearing the mapping and automation resets the indication.
+    unsafe {
+        param_indication.set_mapping.unwrap()(
+            plugin,
+            99,
+            true,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+        );
+        param_indication.set_mapping.unwrap()(
+            plugin,
+            PARAM,
+            false,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+        );
+        param_indication.set_automation.unwrap()(
+            plugin,
+            PARAM,
+            CLAP_PARAM_INDICATION_AUTOMATION_NONE,
+            ptr::null(),
+        );
+    }
+    assert_eq!(indications()[0], ParamIndication::default());
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn context_menu() {
+    unsafe extern "C" fn add_item(
+        builder: *const clap_context_menu_builder,
+        item_kind: clap_context_menu_item_kind,
+        item_data: *const c_void,
+    ) -> bool {
+        let items = &*((*builder).ctx as *const RefCell<Vec<(u32, String)>>);
+
+        let label = match item_kind {
+            CLAP_CONTEXT_MENU_ITEM_ENTRY => {
+                let entry = &*(item_data as *const clap_context_menu_entry);
+                format!("{}:{}", str_from_ptr(entry.label).unwrap(), entry.action_id)
+            }
+            CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY => {
+                let entry = &*(item_data as *const clap_context_menu_check_entry);
+                assert!(!entry.is_enabled && entry.is_checked);
+                format!("{}:{}", str_from_ptr(entry.label).unwrap(), entry.action_id)
+            }
+            CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU => {
+                let submenu = &*(item_data as *const clap_context_menu_submenu);
+                str_from_ptr(submenu.label).unwrap().to_string()
+            }
+            _ => String::new(),
+        };
+        items.borrow_mut().push((item_kind, label));
+
+        true
+    }
+
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let context_menu =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_CONTEXT_MENU.as_ptr()) };
+    let context_menu = unsafe { &*(context_menu as *const clap_plugin_context_menu) };
+
+    let items: RefCell<Vec<(u32, String)>> = RefCell::new(Vec::new());
+    let builder = clap_context_menu_builder {
+        ctx: &items as *const _ as *mut c_void,
+        add_item: Some(add_item),
+        supports: None,
+    };
+
+    let target = clap_context_menu_target {
+        kind: CLAP_CONTEXT_MENU_TARGET_KIND_PARAM,
+        id: PARAM,
+    };
+    assert!(unsafe { context_menu.populate.unwrap()(plugin, &target, &builder) });
+    assert_eq!(
+        *items.borrow(),
+        [
+            (CLAP_CONTEXT_MENU_ITEM_ENTRY, "reset:1".to_string()),
+            (CLAP_CONTEXT_MENU_ITEM_SEPARATOR, String::new()),
+            (CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU, "more".to_string()),
+            (CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY, "fine:2".to_string()),
+            (CLAP_CONTEXT_MENU_ITEM_END_SUBMENU, String::new()),
+        ]
+    );
+
+    let unknown = clap_context_menu_target {
+        kind: CLAP_CONTEXT_MENU_TARGET_KIND_PARAM,
+        id: OLD_PARAM,
+    };
+    assert!(!unsafe { context_menu.populate.unwrap()(plugin, &unknown, &builder) });
+    assert!(!unsafe { context_menu.perform.unwrap()(plugin, &unknown, 1) });
+
+    assert!(unsafe { context_menu.perform.unwrap()(plugin, &target, 2) });
+    assert_eq!(plugin_state().menu_actions, [(PARAM, 2)]);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn thread_pool() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+    test_host.plugin.set(Some(plugin));
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let thread_pool = ThreadPool::from_inner(instance.thread_pool.clone());
+
+    let tasks = AtomicU32::new(0);
+    let run = |thread_pool: &ThreadPool| {
+        tasks.store(0, Ordering::Relaxed);
+        thread_pool.run(4, |index| {
+            tasks.fetch_or(1 << index, Ordering::Relaxed);
+        });
+        tasks.load(Ordering::Relaxed)
+    };
+
+    assert_eq!(run(&thread_pool), 0b1111);
+    assert_eq!(test_host.exec_requests.get(), 1);
+
+    // Tasks still run if the host turns down the request, or has no thread pool.
+    test_host.reject_exec.set(true);
+    assert_eq!(run(&thread_pool), 0b1111);
+    assert_eq!(test_host.exec_requests.get(), 2);
+    assert_eq!(run(&ThreadPool::default()), 0b1111);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn audio_ports_activation() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(CLAP_IDS[1]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<1>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let activation = unsafe {
+        (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_AUDIO_PORTS_ACTIVATION.as_ptr())
+    };
+    let activation = unsafe { &*(activation as *const clap_plugin_audio_ports_activation) };
+    assert!(!unsafe { activation.can_activate_while_processing.unwrap()(plugin) });
+    assert!(unsafe { activation.set_active.unwrap()(plugin, true, 0, false, 32) });
+    assert!(!unsafe { activation.set_active.unwrap()(plugin, false, 1, false, 32) });
+
+    assert!(unsafe { (*plugin).activate.unwrap()(plugin, 44100.0, 1, 4) });
+    assert_eq!(plugin_state().active_buses, [false, true]);
+
+    // The host doesn't need to provide buffers for inactive ports, which read as silence.
+    let input = clap_audio_buffer {
+        data32: ptr::null_mut(),
+        data64: ptr::null_mut(),
+        channel_count: 0,
+        latency: 0,
+        constant_mask: 0,
+    };
+    let mut samples = [1.0f32; 4];
+    let mut channels = [samples.as_mut_ptr()];
+    let mut output = clap_audio_buffer {
+        data32: channels.as_mut_ptr(),
+        data64: ptr::null_mut(),
+        channel_count: 1,
+        latency: 0,
+        constant_mask: 0,
+    };
+    let in_events = clap_input_events {
+        ctx: ptr::null_mut(),
+        size: Some(input_events_size),
+        get: Some(input_events_get),
+    };
+    let values: RefCell<Vec<(clap_id, f64)>> = RefCell::new(Vec::new());
+    let out_events = clap_output_events {
+        ctx: &values as *const _ as *mut c_void,
+        try_push: Some(output_events_try_push),
+    };
+    let process = clap_process {
+        steady_time: -1,
+        frames_count: 4,
+        transport: ptr::null(),
+        audio_inputs: &input,
+        audio_outputs: &mut output,
+        audio_inputs_count: 1,
+        audio_outputs_count: 1,
+        in_events: &in_events,
+        out_events: &out_events,
+    };
+    assert_eq!(
+        unsafe { (*plugin).process.unwrap()(plugin, &process) },
+        CLAP_PROCESS_CONTINUE
+    );
+    assert_eq!(samples, [0.0; 4]);
+
+    // Port changes are reported once the host has deactivated the plugin.
+    plugin_state().host.audio_ports_changed();
+    assert_eq!(test_host.restarts.get(), 1);
+    assert_eq!(test_host.audio_port_rescans.get(), 0);
+
+    unsafe { (*plugin).deactivate.unwrap()(plugin) };
+    assert_eq!(test_host.audio_port_rescans.get(), 1);
+
+    plugin_state().host.audio_ports_changed();
+    assert_eq!(test_host.audio_port_rescans.get(), 2);
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
+    unsafe { factory.deinit() };
+}
+
+#[test]
+fn preset_file() {
+    let test_host = TestHost::default();
+    let host = make_host(&test_host);
+
+    let factory = Factory::new(PLUGINS);
+    assert!(unsafe { factory.init() });
+
+    let plugin_factory =
+        unsafe { factory.get(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory };
+    let id = CString::new(IDS[0]).unwrap();
+    let plugin =
+        unsafe { ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, id.as_ptr()) };
+    assert!(unsafe { (*plugin).init.unwrap()(plugin) });
+
+    let instance = unsafe { &*(plugin as *const Instance<TestPlugin<0>>) };
+    let plugin_state = || unsafe { &mut (*instance.main_thread_state.get()).plugin };
+
+    let path =
+        std::env::temp_dir().join(format!("coupler-test-{}.{}", std::process::id(), EXTENSION));
+    let location = CString::new(path.to_str().unwrap()).unwrap();
+    let missing = CString::new(path.with_extension("missing").to_str().unwrap()).unwrap();
+
+    plugin_state().value = 0.25;
+    let mut file = std::fs::File::create(&path).unwrap();
+    write_preset(plugin_state(), "my preset", &mut file).unwrap();
+    drop(file);
+
+    // The other plugin's CLAP id differs from its `PluginInfo::id`, which its preset files carry.
+    let other_id = CString::new(CLAP_IDS[1]).unwrap();
+    let other = unsafe {
+        ((*plugin_factory).create_plugin).unwrap()(plugin_factory, &host, other_id.as_ptr())
+    };
+    assert!(unsafe { (*other).init.unwrap()(other) });
+    let other_instance = unsafe { &*(other as *const Instance<TestPlugin<1>>) };
+
+    let other_path = path.with_file_name(format!(
+        "coupler-test-other-{}.{}",
+        std::process::id(),
+        EXTENSION
+    ));
+    let other_location = CString::new(other_path.to_str().unwrap()).unwrap();
+    let mut file = std::fs::File::create(&other_path).unwrap();
+    let other_plugin = unsafe { &(*other_instance.main_thread_state.get()).plugin };
+    write_preset(other_plugin, "other preset", &mut file).unwrap();
+    drop(file);
+    unsafe { (*other).destroy.unwrap()(other) };
+
+    // Only the provider for the plugin that saved a file reports it, under its CLAP id.
+    let preset_factory = unsafe {
+        factory.get(CLAP_PRESET_DISCOVERY_FACTORY_ID.as_ptr())
+            as *const clap_preset_discovery_factory
+    };
+    for index in 0..2 {
+        let test_indexer = TestIndexer::default();
+        let indexer = make_indexer(&test_indexer);
+        let receiver = make_receiver(&test_indexer);
+
+        let desc = unsafe { (*preset_factory).get_descriptor.unwrap()(preset_factory, index) };
+        let provider =
+            unsafe { (*preset_factory).create.unwrap()(preset_factory, &indexer, (*desc).id) };
+        assert!(unsafe { (*provider).init.unwrap()(provider) });
+
+        let get_metadata = |location: &CString| unsafe {
+            (*provider).get_metadata.unwrap()(
+                provider,
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                location.as_ptr(),
+                &receiver,
+            )
+        };
+
+        assert!(get_metadata(&location));
+        assert!(get_metadata(&other_location));
+        let name = ["my preset", "other preset"][index as usize];
+        let expected = [(name.to_string(), String::new(), vec![])];
+        assert_eq!(*test_indexer.presets.borrow(), expected);
+        assert_eq!(
+            *test_indexer.plugin_ids.borrow(),
+            [CLAP_IDS[index as usize]]
+        );
+
+        assert!(!get_metadata(&missing));
+        assert_eq!(test_indexer.errors.get(), 1);
+
+        unsafe { (*provider).destroy.unwrap()(provider) };
+    }
+
+    let preset_load =
+        unsafe { (*plugin).get_extension.unwrap()(plugin, CLAP_EXT_PRESET_LOAD.as_ptr()) };
+    let preset_load = unsafe { &*(preset_load as *const clap_plugin_preset_load) };
+
+    let load = |location: &CString| unsafe {
+        preset_load.from_location.unwrap()(
+            plugin,
+            CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+            location.as_ptr(),
+            ptr::null(),
+        )
+    };
+
+    plugin_state().value = 0.5;
+    assert!(load(&location));
+    assert_eq!(plugin_state().value, 0.25);
+    assert_eq!(test_host.presets_loaded.get(), 1);
+
+    assert!(!load(&missing));
+    assert_eq!(test_host.preset_errors.get(), 1);
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&other_path).unwrap();
+
+    unsafe { (*plugin).destroy.unwrap()(plugin) };
     unsafe { factory.deinit() };
 }
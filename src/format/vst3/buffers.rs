@@ -42,6 +42,14 @@ impl ScratchBuffers {
         self.outputs_active[index] = active;
     }
 
+    pub fn inputs_active(&self) -> &[bool] {
+        &self.inputs_active
+    }
+
+    pub fn outputs_active(&self) -> &[bool] {
+        &self.outputs_active
+    }
+
     pub fn resize(&mut self, buses: &[BusInfo], config: &Config) {
         self.data.clear();
         let mut total_channels = 0;
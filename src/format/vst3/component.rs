@@ -1,26 +1,111 @@
 use std::cell::UnsafeCell;
 use std::collections::{HashMap, HashSet};
 use std::ffi::{c_void, CStr};
-use std::ptr;
+use std::marker::PhantomData;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::{mem, ptr};
 
 use vst3::{Class, ComRef, ComWrapper, Steinberg::Vst::*, Steinberg::*};
 
 use super::buffers::ScratchBuffers;
-use super::host::Vst3Host;
+use super::host::{Vst3Host, DATA_ATTR, ID_ATTR, PARAM_MESSAGE, STATE_MESSAGE, VALUE_ATTR};
 use super::util::{copy_wstring, utf16_from_ptr};
 use super::view::{PlugView, Vst3ViewHost};
-use crate::bus::{BusDir, Format, Layout};
-use crate::engine::{Config, Engine};
-use crate::events::{Data, Event, Events};
+use crate::bus::{active_buses, BusDir, Format, Layout};
+use crate::engine::{Config, Engine, ProcessMode, ThreadPool};
+use crate::events::{Data, Event, Events, OutputEvents};
 use crate::host::Host;
+use crate::logging;
+use crate::midi::MidiController;
 use crate::params::ParamId;
 use crate::plugin::{Plugin, PluginInfo};
+use crate::state::StateContext;
 use crate::sync::params::ParamValues;
+use crate::thread_check;
+use crate::track::{Color, TrackInfo};
 use crate::util::{slice_from_raw_parts_checked, DisplayParam};
 use crate::view::View;
 
+const ROOT_UNIT_ID: UnitID = 0;
+const NO_PARENT_UNIT_ID: UnitID = -1;
+const NO_PROGRAM_LIST_ID: ProgramListID = -1;
+const PROGRAM_LIST_ID: ProgramListID = 0;
+
+const INSTRUMENT_ATTRIBUTE: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"MusicalInstrument\0") };
+
+const CHANNEL_NAME_KEY: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"channel name\0") };
+const CHANNEL_COLOR_KEY: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"channel color\0") };
+
+// Picks the id of the parameter that selects a factory preset. Ids at or above 0x80000000 are
+// reserved for the host, so this counts down from just below them to the first id the plugin
+// doesn't use.
+pub(super) fn program_change_id(param_map: &HashMap<ParamId, usize>) -> ParamID {
+    (0..=0x7FFF_FFFF).rev().find(|id| !param_map.contains_key(id)).unwrap()
+}
+
+// Hosts that support IStreamAttributes tag state saved for presets as "Default".
+unsafe fn state_context(stream: ComRef<IBStream>) -> StateContext {
+    const STATE_TYPE: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"StateType\0") };
+
+    let Some(stream) = stream.cast::<IStreamAttributes>() else {
+        return StateContext::Project;
+    };
+    let Some(attributes) = ComRef::from_raw(stream.getAttributes()) else {
+        return StateContext::Project;
+    };
+
+    let mut state_type = [0 as TChar; 128];
+    let size = std::mem::size_of_val(&state_type) as uint32;
+    if attributes.getString(STATE_TYPE.as_ptr(), state_type.as_mut_ptr(), size) != kResultOk {
+        return StateContext::Project;
+    }
+
+    let state_type = utf16_from_ptr(state_type.as_ptr());
+    if state_type.iter().copied().eq("Default".encode_utf16()) {
+        StateContext::Preset
+    } else {
+        StateContext::Project
+    }
+}
+
+// Merges the runs of `events` starting at each index in `runs`, each already in time order, into
+// a single list in time order. Pairs of runs are merged back and forth between `events` and
+// `scratch`, which has the same capacity, so this doesn't allocate. The merge is stable, so a ramp
+// stays ahead of the change at the end of the previous segment.
+fn merge_runs(events: &mut Vec<Event>, scratch: &mut Vec<Event>, runs: &mut Vec<usize>) {
+    while runs.len() > 1 {
+        scratch.clear();
+
+        let mut merged = 0;
+        for i in (0..runs.len()).step_by(2) {
+            let start = runs[i];
+            let mid = runs.get(i + 1).copied().unwrap_or(events.len());
+            let end = runs.get(i + 2).copied().unwrap_or(events.len());
+
+            runs[merged] = scratch.len();
+            merged += 1;
+
+            let (mut a, mut b) = (&events[start..mid], &events[mid..end]);
+            while let (Some(x), Some(y)) = (a.first(), b.first()) {
+                if y.time < x.time {
+                    scratch.push(*y);
+                    b = &b[1..];
+                } else {
+                    scratch.push(*x);
+                    a = &a[1..];
+                }
+            }
+            scratch.extend_from_slice(a);
+            scratch.extend_from_slice(b);
+        }
+
+        runs.truncate(merged);
+        mem::swap(events, scratch);
+    }
+}
+
 fn format_to_speaker_arrangement(format: &Format) -> SpeakerArrangement {
     match format {
         Format::Mono => SpeakerArr::kMono,
@@ -36,38 +121,74 @@ fn speaker_arrangement_to_format(speaker_arrangement: SpeakerArrangement) -> Opt
     }
 }
 
+// Which interfaces a `Component` exposes. When a plugin has a separate controller class, the
+// factory creates a `Processor` and a `Controller`, each with its own plugin instance. The host
+// keeps the controller up to date through setComponentState, and the two exchange any other
+// changes through IConnectionPoint messages.
+pub trait Mode: 'static {
+    const SPLIT: bool;
+}
+
+pub struct Combined;
+
+impl Mode for Combined {
+    const SPLIT: bool = false;
+}
+
+pub struct Processor;
+
+impl Mode for Processor {
+    const SPLIT: bool = true;
+}
+
+pub struct Controller;
+
+impl Mode for Controller {
+    const SPLIT: bool = true;
+}
+
 pub struct MainThreadState<P: Plugin> {
     pub config: Config,
     pub plugin: P,
-    pub view_host: Rc<Vst3ViewHost>,
+    pub view_host: Rc<Vst3ViewHost<P>>,
     pub view: Option<P::View>,
+    pub program: usize,
 }
 
 struct ProcessState<P: Plugin> {
     config: Config,
     scratch_buffers: ScratchBuffers,
     events: Vec<Event>,
+    // Where each parameter queue starts in `events`, and space to merge the queues.
+    event_runs: Vec<usize>,
+    merge_scratch: Vec<Event>,
+    output_events: Vec<Event>,
     engine: Option<P::Engine>,
 }
 
-pub struct Component<P: Plugin> {
+pub struct Component<P: Plugin, M: Mode> {
     info: Arc<PluginInfo>,
+    controller_id: Option<TUID>,
     input_bus_map: Vec<usize>,
     output_bus_map: Vec<usize>,
     layout_set: HashSet<Layout>,
-    param_map: HashMap<ParamId, usize>,
-    plugin_params: ParamValues,
-    engine_params: ParamValues,
-    _host: Arc<Vst3Host>,
+    param_map: Arc<HashMap<ParamId, usize>>,
+    program_change_id: ParamID,
+    plugin_params: Arc<ParamValues>,
+    engine_params: Arc<ParamValues>,
+    host: Arc<Vst3Host>,
     main_thread_state: Arc<UnsafeCell<MainThreadState<P>>>,
     // When the audio processor is *not* active, references to ProcessState may only be formed from
     // the main thread. When the audio processor *is* active, references to ProcessState may only
     // be formed from the audio thread.
     process_state: UnsafeCell<ProcessState<P>>,
+    _mode: PhantomData<M>,
 }
 
-impl<P: Plugin> Component<P> {
-    pub fn new(info: &Arc<PluginInfo>) -> Component<P> {
+impl<P: Plugin, M: Mode> Component<P, M> {
+    pub fn new(info: &Arc<PluginInfo>, controller_id: Option<TUID>) -> Component<P, M> {
+        thread_check::set_main_thread();
+
         let mut input_bus_map = Vec::new();
         let mut output_bus_map = Vec::new();
         for (index, bus) in info.buses.iter().enumerate() {
@@ -92,33 +213,55 @@ impl<P: Plugin> Component<P> {
             layout: info.layouts.first().cloned().unwrap_or_default(),
             sample_rate: 0.0,
             max_buffer_size: 0,
+            process_mode: ProcessMode::Realtime,
+            thread_pool: ThreadPool::default(),
+            active_buses: vec![true; info.buses.len()],
         };
 
         let scratch_buffers = ScratchBuffers::new(input_bus_map.len(), output_bus_map.len());
 
-        let host = Arc::new(Vst3Host::new());
+        let program_change_id = program_change_id(&param_map);
+
+        let param_map = Arc::new(param_map);
+        let plugin_params = Arc::new(ParamValues::with_count(info.params.len()));
+        let engine_params = Arc::new(ParamValues::with_count(info.params.len()));
+
+        let host = Arc::new(Vst3Host::new(
+            param_map.clone(),
+            plugin_params.clone(),
+            engine_params.clone(),
+        ));
 
         Component {
             info: info.clone(),
+            controller_id,
             input_bus_map,
             output_bus_map,
             layout_set,
             param_map,
-            plugin_params: ParamValues::with_count(info.params.len()),
-            engine_params: ParamValues::with_count(info.params.len()),
-            _host: host.clone(),
-            main_thread_state: Arc::new(UnsafeCell::new(MainThreadState {
-                config: config.clone(),
-                plugin: P::new(Host::from_inner(host)),
-                view_host: Rc::new(Vst3ViewHost::new()),
-                view: None,
-            })),
+            program_change_id,
+            plugin_params,
+            engine_params,
+            host: host.clone(),
+            main_thread_state: Arc::new_cyclic(|main_thread_state| {
+                UnsafeCell::new(MainThreadState {
+                    config: config.clone(),
+                    plugin: P::new(Host::from_inner(host.clone())),
+                    view_host: Rc::new(Vst3ViewHost::new(host, main_thread_state.clone())),
+                    view: None,
+                    program: 0,
+                })
+            }),
             process_state: UnsafeCell::new(ProcessState {
                 config,
                 scratch_buffers,
                 events: Vec::with_capacity(4096),
+                event_runs: Vec::with_capacity(info.params.len() + 1),
+                merge_scratch: Vec::with_capacity(4096),
+                output_events: Vec::with_capacity(256),
                 engine: None,
             }),
+            _mode: PhantomData,
         }
     }
 
@@ -128,29 +271,150 @@ impl<P: Plugin> Component<P> {
             plugin.set_param(id, value);
         }
     }
+
+    // Called after the plugin's state has been replaced wholesale, e.g. by loading a preset.
+    fn params_reloaded(&self, main_thread_state: &mut MainThreadState<P>) {
+        for (index, param) in self.info.params.iter().enumerate() {
+            let value = main_thread_state.plugin.get_param(param.id);
+            self.engine_params.set(index, value);
+
+            if let Some(view) = &mut main_thread_state.view {
+                view.param_changed(param.id, value);
+            }
+        }
+
+        self.host.rescan_param_values();
+    }
+
+    unsafe fn load_state(&self, state: *mut IBStream) -> tresult {
+        use std::io::{Error, ErrorKind, Read, Result};
+
+        struct StreamReader<'a>(ComRef<'a, IBStream>);
+
+        impl<'a> Read for StreamReader<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+                let ptr = buf.as_mut_ptr() as *mut c_void;
+                let len = buf.len() as int32;
+                let mut bytes: int32 = 0;
+                let result = unsafe { self.0.read(ptr, len, &mut bytes) };
+
+                if result == kResultOk {
+                    Ok(bytes as usize)
+                } else {
+                    Err(Error::new(ErrorKind::Other, "failed to read from stream"))
+                }
+            }
+        }
+
+        if let Some(state) = ComRef::from_raw(state) {
+            let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+            thread_check::check_main_thread(|| None);
+            let main_thread_state = &mut *self.main_thread_state.get();
+
+            self.sync_plugin(&mut main_thread_state.plugin);
+
+            let context = state_context(state);
+            if main_thread_state.plugin.load(&mut StreamReader(state), context).is_ok() {
+                self.params_reloaded(main_thread_state);
+                return kResultOk;
+            }
+        }
+
+        kResultFalse
+    }
+
+    // Sends the whole plugin state to the processor after the controller loads a preset.
+    fn send_state(&self, main_thread_state: &MainThreadState<P>) {
+        let mut data = Vec::new();
+        if main_thread_state.plugin.save(&mut data, StateContext::Duplicate).is_ok() {
+            self.host.send_message(STATE_MESSAGE, |attributes| unsafe {
+                let ptr = data.as_ptr() as *const c_void;
+                attributes.setBinary(DATA_ATTR.as_ptr(), ptr, data.len() as uint32);
+            });
+        }
+    }
+
+    // Hosts only apply MIDI mappings for plugins with an event input bus.
+    fn event_input_count(&self) -> int32 {
+        if self.info.midi_mappings.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn program_to_normalized(&self, program: usize) -> ParamValue {
+        let count = self.info.presets.len();
+        if count > 1 {
+            program as ParamValue / (count - 1) as ParamValue
+        } else {
+            0.0
+        }
+    }
+
+    fn normalized_to_program(&self, value: ParamValue) -> usize {
+        let count = self.info.presets.len();
+        if count > 1 {
+            (value.clamp(0.0, 1.0) * (count - 1) as ParamValue).round() as usize
+        } else {
+            0
+        }
+    }
 }
 
-impl<P: Plugin> Class for Component<P> {
+impl<P: Plugin> Class for Component<P, Combined> {
     type Interfaces = (
         IComponent,
         IAudioProcessor,
         IProcessContextRequirements,
         IEditController,
+        IUnitInfo,
+        IMidiMapping,
+        IInfoListener,
+    );
+}
+
+impl<P: Plugin> Class for Component<P, Processor> {
+    type Interfaces = (
+        IComponent,
+        IAudioProcessor,
+        IProcessContextRequirements,
+        IConnectionPoint,
     );
 }
 
-impl<P: Plugin> IPluginBaseTrait for Component<P> {
-    unsafe fn initialize(&self, _context: *mut FUnknown) -> tresult {
+impl<P: Plugin> Class for Component<P, Controller> {
+    type Interfaces = (
+        IEditController,
+        IUnitInfo,
+        IMidiMapping,
+        IInfoListener,
+        IConnectionPoint,
+    );
+}
+
+impl<P: Plugin, M: Mode> IPluginBaseTrait for Component<P, M> {
+    unsafe fn initialize(&self, context: *mut FUnknown) -> tresult {
+        let host_application = ComRef::from_raw(context).and_then(|context| context.cast());
+        *self.host.host_application.borrow_mut() = host_application;
+
         kResultOk
     }
 
     unsafe fn terminate(&self) -> tresult {
+        self.host.host_application.borrow_mut().take();
+
         kResultOk
     }
 }
 
-impl<P: Plugin> IComponentTrait for Component<P> {
-    unsafe fn getControllerClassId(&self, _classId: *mut TUID) -> tresult {
+impl<P: Plugin, M: Mode> IComponentTrait for Component<P, M> {
+    unsafe fn getControllerClassId(&self, classId: *mut TUID) -> tresult {
+        if let Some(controller_id) = &self.controller_id {
+            *classId = *controller_id;
+            return kResultOk;
+        }
+
         kNotImplemented
     }
 
@@ -165,7 +429,10 @@ impl<P: Plugin> IComponentTrait for Component<P> {
                 BusDirections_::kOutput => self.output_bus_map.len() as int32,
                 _ => 0,
             },
-            MediaTypes_::kEvent => 0,
+            MediaTypes_::kEvent => match dir as BusDirections {
+                BusDirections_::kInput => self.event_input_count(),
+                _ => 0,
+            },
             _ => 0,
         }
     }
@@ -177,6 +444,8 @@ impl<P: Plugin> IComponentTrait for Component<P> {
         index: int32,
         bus: *mut BusInfo,
     ) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
         match type_ as MediaTypes {
@@ -209,7 +478,22 @@ impl<P: Plugin> IComponentTrait for Component<P> {
                     }
                 }
             }
-            MediaTypes_::kEvent => {}
+            MediaTypes_::kEvent => {
+                if dir as BusDirections == BusDirections_::kInput
+                    && index < self.event_input_count()
+                {
+                    let bus = &mut *bus;
+
+                    bus.mediaType = type_;
+                    bus.direction = dir;
+                    bus.channelCount = 16;
+                    copy_wstring("MIDI In", &mut bus.name);
+                    bus.busType = BusTypes_::kMain as BusType;
+                    bus.flags = BusInfo_::BusFlags_::kDefaultActive as uint32;
+
+                    return kResultOk;
+                }
+            }
             _ => {}
         }
 
@@ -231,6 +515,8 @@ impl<P: Plugin> IComponentTrait for Component<P> {
         index: int32,
         state: TBool,
     ) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let process_state = &mut *self.process_state.get();
 
         match type_ as MediaTypes {
@@ -249,7 +535,13 @@ impl<P: Plugin> IComponentTrait for Component<P> {
                 }
                 _ => {}
             },
-            MediaTypes_::kEvent => {}
+            MediaTypes_::kEvent => {
+                if dir as BusDirections == BusDirections_::kInput
+                    && index < self.event_input_count()
+                {
+                    return kResultOk;
+                }
+            }
             _ => {}
         }
 
@@ -257,6 +549,8 @@ impl<P: Plugin> IComponentTrait for Component<P> {
     }
 
     unsafe fn setActive(&self, state: TBool) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
         let process_state = &mut *self.process_state.get();
 
@@ -268,6 +562,11 @@ impl<P: Plugin> IComponentTrait for Component<P> {
             process_state.engine = None;
         } else {
             process_state.config = main_thread_state.config.clone();
+            process_state.config.active_buses = active_buses(
+                &self.info.buses,
+                process_state.scratch_buffers.inputs_active(),
+                process_state.scratch_buffers.outputs_active(),
+            );
             process_state.scratch_buffers.resize(&self.info.buses, &process_state.config);
 
             // Discard any pending plugin -> engine parameter changes, since they will already be
@@ -281,45 +580,7 @@ impl<P: Plugin> IComponentTrait for Component<P> {
     }
 
     unsafe fn setState(&self, state: *mut IBStream) -> tresult {
-        use std::io::{Error, ErrorKind, Read, Result};
-
-        struct StreamReader<'a>(ComRef<'a, IBStream>);
-
-        impl<'a> Read for StreamReader<'a> {
-            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-                let ptr = buf.as_mut_ptr() as *mut c_void;
-                let len = buf.len() as int32;
-                let mut bytes: int32 = 0;
-                let result = unsafe { self.0.read(ptr, len, &mut bytes) };
-
-                if result == kResultOk {
-                    Ok(bytes as usize)
-                } else {
-                    Err(Error::new(ErrorKind::Other, "failed to read from stream"))
-                }
-            }
-        }
-
-        if let Some(state) = ComRef::from_raw(state) {
-            let main_thread_state = &mut *self.main_thread_state.get();
-
-            self.sync_plugin(&mut main_thread_state.plugin);
-
-            if main_thread_state.plugin.load(&mut StreamReader(state)).is_ok() {
-                for (index, param) in self.info.params.iter().enumerate() {
-                    let value = main_thread_state.plugin.get_param(param.id);
-                    self.engine_params.set(index, value);
-
-                    if let Some(view) = &mut main_thread_state.view {
-                        view.param_changed(param.id, value);
-                    }
-                }
-
-                return kResultOk;
-            }
-        }
-
-        kResultFalse
+        self.load_state(state)
     }
 
     unsafe fn getState(&self, state: *mut IBStream) -> tresult {
@@ -347,11 +608,14 @@ impl<P: Plugin> IComponentTrait for Component<P> {
         }
 
         if let Some(state) = ComRef::from_raw(state) {
+            let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+            thread_check::check_main_thread(|| None);
             let main_thread_state = &mut *self.main_thread_state.get();
 
             self.sync_plugin(&mut main_thread_state.plugin);
 
-            if main_thread_state.plugin.save(&mut StreamWriter(state)).is_ok() {
+            let context = state_context(state);
+            if main_thread_state.plugin.save(&mut StreamWriter(state), context).is_ok() {
                 return kResultOk;
             }
         }
@@ -360,7 +624,7 @@ impl<P: Plugin> IComponentTrait for Component<P> {
     }
 }
 
-impl<P: Plugin> IAudioProcessorTrait for Component<P> {
+impl<P: Plugin, M: Mode> IAudioProcessorTrait for Component<P, M> {
     unsafe fn setBusArrangements(
         &self,
         inputs: *mut SpeakerArrangement,
@@ -402,6 +666,8 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
         }
 
         if self.layout_set.contains(&candidate) {
+            let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+            thread_check::check_main_thread(|| None);
             let main_thread_state = &mut *self.main_thread_state.get();
             main_thread_state.config.layout = candidate;
             return kResultTrue;
@@ -416,6 +682,8 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
         index: int32,
         arr: *mut SpeakerArrangement,
     ) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
         let bus_index = match dir as BusDirections {
@@ -444,6 +712,8 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
     }
 
     unsafe fn getLatencySamples(&self) -> uint32 {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
 
         self.sync_plugin(&mut main_thread_state.plugin);
@@ -451,16 +721,27 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
     }
 
     unsafe fn setupProcessing(&self, setup: *mut ProcessSetup) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
 
         let setup = &*setup;
         main_thread_state.config.sample_rate = setup.sampleRate;
         main_thread_state.config.max_buffer_size = setup.maxSamplesPerBlock as usize;
+        main_thread_state.config.process_mode = match setup.processMode as ProcessModes {
+            ProcessModes_::kPrefetch => ProcessMode::Prefetch,
+            ProcessModes_::kOffline => ProcessMode::Offline,
+            _ => ProcessMode::Realtime,
+        };
 
         kResultOk
     }
 
     unsafe fn setProcessing(&self, state: TBool) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_audio_thread(|| None);
+        let _audio_thread = thread_check::enter_audio_thread();
+
         let process_state = &mut *self.process_state.get();
 
         let Some(engine) = &mut process_state.engine else {
@@ -482,6 +763,9 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
 
             if !process_state.events.is_empty() {
                 engine.flush(Events::new(&process_state.events));
+
+                process_state.output_events.clear();
+                engine.output_events(&mut OutputEvents::new(&mut process_state.output_events));
             }
 
             engine.reset();
@@ -491,6 +775,10 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
     }
 
     unsafe fn process(&self, data: *mut ProcessData) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_audio_thread(|| None);
+        let _audio_thread = thread_check::enter_audio_thread();
+
         let process_state = &mut *self.process_state.get();
 
         let Some(engine) = &mut process_state.engine else {
@@ -510,6 +798,8 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
         };
 
         process_state.events.clear();
+        process_state.event_runs.clear();
+        process_state.event_runs.push(0);
 
         for (index, value) in self.engine_params.poll() {
             process_state.events.push(Event {
@@ -535,6 +825,11 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
                     continue;
                 };
 
+                process_state.event_runs.push(process_state.events.len());
+
+                // VST3 automation is piecewise linear between points, starting from the current
+                // value at the start of the block.
+                let mut prev_offset = 0;
                 for index in 0..point_count {
                     let mut offset = 0;
                     let mut value = 0.0;
@@ -544,6 +839,18 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
                         continue;
                     }
 
+                    if offset > prev_offset {
+                        process_state.events.push(Event {
+                            time: prev_offset as i64,
+                            data: Data::ParamRamp {
+                                id,
+                                value,
+                                len: (offset - prev_offset) as u32,
+                            },
+                        });
+                    }
+                    prev_offset = offset;
+
                     process_state.events.push(Event {
                         time: offset as i64,
                         data: Data::ParamChange { id, value },
@@ -554,6 +861,13 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
             }
         }
 
+        // Each queue is in time order, but the queues are appended one after another.
+        merge_runs(
+            &mut process_state.events,
+            &mut process_state.merge_scratch,
+            &mut process_state.event_runs,
+        );
+
         let events = Events::new(&process_state.events);
         if let Some(buffers) = buffers {
             engine.process(buffers, events);
@@ -561,6 +875,11 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
             engine.flush(events);
         }
 
+        // VST3 has no equivalent of note end events, but the engine still expects its output
+        // events to be collected.
+        process_state.output_events.clear();
+        engine.output_events(&mut OutputEvents::new(&mut process_state.output_events));
+
         kResultOk
     }
 
@@ -569,14 +888,19 @@ impl<P: Plugin> IAudioProcessorTrait for Component<P> {
     }
 }
 
-impl<P: Plugin> IProcessContextRequirementsTrait for Component<P> {
+impl<P: Plugin, M: Mode> IProcessContextRequirementsTrait for Component<P, M> {
     unsafe fn getProcessContextRequirements(&self) -> uint32 {
         0
     }
 }
 
-impl<P: Plugin> IEditControllerTrait for Component<P> {
-    unsafe fn setComponentState(&self, _state: *mut IBStream) -> tresult {
+impl<P: Plugin, M: Mode> IEditControllerTrait for Component<P, M> {
+    unsafe fn setComponentState(&self, state: *mut IBStream) -> tresult {
+        // A combined component already has the state loaded through IComponent::setState.
+        if M::SPLIT {
+            return self.load_state(state);
+        }
+
         kResultOk
     }
 
@@ -589,10 +913,27 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
     }
 
     unsafe fn getParameterCount(&self) -> int32 {
-        self.info.params.len() as int32
+        let has_presets = !self.info.presets.is_empty();
+        (self.info.params.len() + has_presets as usize) as int32
     }
 
     unsafe fn getParameterInfo(&self, paramIndex: int32, info: *mut ParameterInfo) -> tresult {
+        if paramIndex as usize == self.info.params.len() && !self.info.presets.is_empty() {
+            let info = &mut *info;
+
+            info.id = self.program_change_id;
+            copy_wstring("Program", &mut info.title);
+            copy_wstring("Program", &mut info.shortTitle);
+            copy_wstring("", &mut info.units);
+            info.stepCount = (self.info.presets.len() - 1) as int32;
+            info.defaultNormalizedValue = 0.0;
+            info.unitId = ROOT_UNIT_ID;
+            info.flags = (ParameterInfo_::ParameterFlags_::kIsProgramChange
+                | ParameterInfo_::ParameterFlags_::kIsList) as int32;
+
+            return kResultOk;
+        }
+
         if let Some(param) = self.info.params.get(paramIndex as usize) {
             let info = &mut *info;
 
@@ -621,8 +962,17 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
         valueNormalized: ParamValue,
         string: *mut String128,
     ) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
+        if id == self.program_change_id && !self.info.presets.is_empty() {
+            let program = self.normalized_to_program(valueNormalized);
+            copy_wstring(&self.info.presets[program].name, &mut *string);
+
+            return kResultOk;
+        }
+
         if self.param_map.contains_key(&id) {
             let display = format!(
                 "{}",
@@ -642,8 +992,20 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
         string: *mut TChar,
         valueNormalized: *mut ParamValue,
     ) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
+        if id == self.program_change_id {
+            if let Ok(display) = String::from_utf16(utf16_from_ptr(string)) {
+                let program = self.info.presets.iter().position(|preset| preset.name == display);
+                if let Some(program) = program {
+                    *valueNormalized = self.program_to_normalized(program);
+                    return kResultOk;
+                }
+            }
+        }
+
         if self.param_map.contains_key(&id) {
             if let Ok(display) = String::from_utf16(utf16_from_ptr(string)) {
                 if let Some(value) = main_thread_state.plugin.parse_param(id, &display) {
@@ -669,8 +1031,14 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
     }
 
     unsafe fn getParamNormalized(&self, id: ParamID) -> ParamValue {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
+        if id == self.program_change_id {
+            return self.program_to_normalized(main_thread_state.program);
+        }
+
         if self.param_map.contains_key(&id) {
             return main_thread_state.plugin.get_param(id);
         }
@@ -679,8 +1047,32 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
     }
 
     unsafe fn setParamNormalized(&self, id: ParamID, value: ParamValue) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
 
+        if id == self.program_change_id && !self.info.presets.is_empty() {
+            let program = self.normalized_to_program(value);
+            if program == main_thread_state.program {
+                return kResultOk;
+            }
+
+            self.sync_plugin(&mut main_thread_state.plugin);
+
+            if main_thread_state.plugin.load_preset(program).is_ok() {
+                main_thread_state.program = program;
+                self.params_reloaded(main_thread_state);
+
+                if M::SPLIT {
+                    self.send_state(main_thread_state);
+                }
+
+                return kResultOk;
+            }
+
+            return kResultFalse;
+        }
+
         if self.param_map.contains_key(&id) {
             main_thread_state.plugin.set_param(id, value);
 
@@ -695,9 +1087,7 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
     }
 
     unsafe fn setComponentHandler(&self, handler: *mut IComponentHandler) -> tresult {
-        let main_thread_state = &mut *self.main_thread_state.get();
-
-        let mut current_handler = main_thread_state.view_host.handler.borrow_mut();
+        let mut current_handler = self.host.handler.borrow_mut();
         if let Some(handler) = ComRef::from_raw(handler) {
             *current_handler = Some(handler.to_com_ptr());
         } else {
@@ -717,6 +1107,280 @@ impl<P: Plugin> IEditControllerTrait for Component<P> {
         }
 
         let view = ComWrapper::new(PlugView::new(&self.main_thread_state));
-        view.to_com_ptr::<IPlugView>().unwrap().into_raw()
+        let view = view.to_com_ptr::<IPlugView>().unwrap();
+
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
+        let main_thread_state = &*self.main_thread_state.get();
+        main_thread_state.view_host.set_view(view.as_ptr());
+
+        view.into_raw()
+    }
+}
+
+impl<P: Plugin, M: Mode> IUnitInfoTrait for Component<P, M> {
+    unsafe fn getUnitCount(&self) -> int32 {
+        1
+    }
+
+    unsafe fn getUnitInfo(&self, unitIndex: int32, info: *mut UnitInfo) -> tresult {
+        if unitIndex != 0 {
+            return kInvalidArgument;
+        }
+
+        let info = &mut *info;
+
+        info.id = ROOT_UNIT_ID;
+        info.parentUnitId = NO_PARENT_UNIT_ID;
+        copy_wstring("Root", &mut info.name);
+        info.programListId = if self.info.presets.is_empty() {
+            NO_PROGRAM_LIST_ID
+        } else {
+            PROGRAM_LIST_ID
+        };
+
+        kResultOk
+    }
+
+    unsafe fn getProgramListCount(&self) -> int32 {
+        !self.info.presets.is_empty() as int32
+    }
+
+    unsafe fn getProgramListInfo(&self, listIndex: int32, info: *mut ProgramListInfo) -> tresult {
+        if listIndex != 0 || self.info.presets.is_empty() {
+            return kInvalidArgument;
+        }
+
+        let info = &mut *info;
+
+        info.id = PROGRAM_LIST_ID;
+        copy_wstring("Presets", &mut info.name);
+        info.programCount = self.info.presets.len() as int32;
+
+        kResultOk
+    }
+
+    unsafe fn getProgramName(
+        &self,
+        listId: ProgramListID,
+        programIndex: int32,
+        name: *mut String128,
+    ) -> tresult {
+        if listId == PROGRAM_LIST_ID {
+            if let Some(preset) = self.info.presets.get(programIndex as usize) {
+                copy_wstring(&preset.name, &mut *name);
+                return kResultOk;
+            }
+        }
+
+        kInvalidArgument
+    }
+
+    unsafe fn getProgramInfo(
+        &self,
+        listId: ProgramListID,
+        programIndex: int32,
+        attributeId: CString,
+        attributeValue: *mut String128,
+    ) -> tresult {
+        if listId == PROGRAM_LIST_ID && !attributeId.is_null() {
+            if let Some(preset) = self.info.presets.get(programIndex as usize) {
+                if CStr::from_ptr(attributeId) == INSTRUMENT_ATTRIBUTE {
+                    if let Some(category) = &preset.category {
+                        copy_wstring(category, &mut *attributeValue);
+                        return kResultOk;
+                    }
+                }
+            }
+        }
+
+        kResultFalse
+    }
+
+    unsafe fn hasProgramPitchNames(&self, _listId: ProgramListID, _programIndex: int32) -> tresult {
+        kResultFalse
+    }
+
+    unsafe fn getProgramPitchName(
+        &self,
+        _listId: ProgramListID,
+        _programIndex: int32,
+        _midiPitch: int16,
+        _name: *mut String128,
+    ) -> tresult {
+        kResultFalse
+    }
+
+    unsafe fn getSelectedUnit(&self) -> UnitID {
+        ROOT_UNIT_ID
+    }
+
+    unsafe fn selectUnit(&self, unitId: UnitID) -> tresult {
+        if unitId == ROOT_UNIT_ID {
+            kResultOk
+        } else {
+            kInvalidArgument
+        }
+    }
+
+    unsafe fn getUnitByBus(
+        &self,
+        _type_: MediaType,
+        _dir: BusDirection,
+        _busIndex: int32,
+        _channel: int32,
+        _unitId: *mut UnitID,
+    ) -> tresult {
+        kResultFalse
+    }
+
+    unsafe fn setUnitProgramData(
+        &self,
+        _listOrUnitId: int32,
+        _programIndex: int32,
+        _data: *mut IBStream,
+    ) -> tresult {
+        kNotImplemented
+    }
+}
+
+impl<P: Plugin, M: Mode> IMidiMappingTrait for Component<P, M> {
+    unsafe fn getMidiControllerAssignment(
+        &self,
+        busIndex: int32,
+        channel: int16,
+        midiControllerNumber: CtrlNumber,
+        id: *mut ParamID,
+    ) -> tresult {
+        if busIndex != 0 || !(0..16).contains(&channel) {
+            return kResultFalse;
+        }
+
+        let controller = match midiControllerNumber {
+            0..=127 => MidiController::ControlChange(midiControllerNumber as u8),
+            n if n == ControllerNumbers_::kAfterTouch as CtrlNumber => {
+                MidiController::ChannelPressure
+            }
+            n if n == ControllerNumbers_::kPitchBend as CtrlNumber => MidiController::PitchBend,
+            _ => return kResultFalse,
+        };
+
+        let mapping = self.info.midi_mappings.iter().find(|mapping| {
+            mapping.matches(controller, channel as u8)
+                && self.param_map.contains_key(&mapping.param)
+        });
+        if let Some(mapping) = mapping {
+            *id = mapping.param;
+            return kResultOk;
+        }
+
+        kResultFalse
+    }
+}
+
+impl<P: Plugin, M: Mode> IInfoListenerTrait for Component<P, M> {
+    unsafe fn setChannelContextInfos(&self, list: *mut IAttributeList) -> tresult {
+        let Some(list) = ComRef::from_raw(list) else {
+            return kInvalidArgument;
+        };
+
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
+        let main_thread_state = &mut *self.main_thread_state.get();
+
+        // VST3 doesn't report the kind of track or its channel count.
+        let mut track_info = TrackInfo::default();
+
+        let mut name: String128 = [0; 128];
+        let size = mem::size_of_val(&name) as uint32;
+        if list.getString(CHANNEL_NAME_KEY.as_ptr(), name.as_mut_ptr(), size) == kResultOk {
+            name[name.len() - 1] = 0;
+            track_info.name = Some(String::from_utf16_lossy(utf16_from_ptr(name.as_ptr())));
+        }
+
+        // Colors are packed as 0xAARRGGBB.
+        let mut color: int64 = 0;
+        if list.getInt(CHANNEL_COLOR_KEY.as_ptr(), &mut color) == kResultOk {
+            let [b, g, r, a] = (color as u32).to_le_bytes();
+            track_info.color = Some(Color { r, g, b, a });
+        }
+
+        main_thread_state.plugin.track_info_changed(&track_info);
+
+        kResultOk
+    }
+}
+
+impl<P: Plugin, M: Mode> IConnectionPointTrait for Component<P, M> {
+    unsafe fn connect(&self, other: *mut IConnectionPoint) -> tresult {
+        if let Some(other) = ComRef::from_raw(other) {
+            *self.host.peer.borrow_mut() = Some(other.to_com_ptr());
+            return kResultOk;
+        }
+
+        kInvalidArgument
+    }
+
+    unsafe fn disconnect(&self, _other: *mut IConnectionPoint) -> tresult {
+        self.host.peer.borrow_mut().take();
+
+        kResultOk
+    }
+
+    unsafe fn notify(&self, message: *mut IMessage) -> tresult {
+        let Some(message) = ComRef::from_raw(message) else {
+            return kInvalidArgument;
+        };
+        let Some(attributes) = ComRef::from_raw(message.getAttributes()) else {
+            return kResultFalse;
+        };
+        let id = message.getMessageID();
+        if id.is_null() {
+            return kResultFalse;
+        }
+        let id = CStr::from_ptr(id);
+
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
+        let main_thread_state = &mut *self.main_thread_state.get();
+
+        if id == STATE_MESSAGE {
+            let mut data = ptr::null();
+            let mut size = 0;
+            if attributes.getBinary(DATA_ATTR.as_ptr(), &mut data, &mut size) != kResultOk {
+                return kResultFalse;
+            }
+            let data = slice_from_raw_parts_checked(data as *const u8, size as usize);
+
+            self.sync_plugin(&mut main_thread_state.plugin);
+
+            if main_thread_state.plugin.load(&mut &data[..], StateContext::Duplicate).is_ok() {
+                self.params_reloaded(main_thread_state);
+                return kResultOk;
+            }
+        } else if id == PARAM_MESSAGE {
+            let mut param_id = 0;
+            let mut value = 0.0;
+            if attributes.getInt(ID_ATTR.as_ptr(), &mut param_id) != kResultOk
+                || attributes.getFloat(VALUE_ATTR.as_ptr(), &mut value) != kResultOk
+            {
+                return kResultFalse;
+            }
+
+            let param_id = param_id as ParamId;
+            if self.param_map.contains_key(&param_id) {
+                main_thread_state.plugin.set_param(param_id, value);
+
+                if let Some(view) = &mut main_thread_state.view {
+                    view.param_changed(param_id, value);
+                }
+
+                self.host.perform_edit(param_id, value);
+
+                return kResultOk;
+            }
+        }
+
+        kResultFalse
     }
 }
@@ -1,63 +1,186 @@
 use std::ffi::{c_void, CStr};
-use std::marker::PhantomData;
 use std::sync::Arc;
 
 use vst3::{uid, Class, ComWrapper, Steinberg::Vst::*, Steinberg::*};
 
-use super::component::Component;
+use super::component::{Combined, Component, Controller, Mode, Processor};
 use super::util::copy_wstring;
 use super::{Uuid, Vst3Info, Vst3Plugin};
-use crate::plugin::{Plugin, PluginInfo};
+use crate::plugin::{Category, Feature, Plugin, PluginInfo};
 use crate::util::copy_cstring;
 
+const AUDIO_MODULE_CLASS: &str = "Audio Module Class";
+const CONTROLLER_CLASS: &str = "Component Controller Class";
+
 fn uuid_to_tuid(uuid: &Uuid) -> TUID {
     uid(uuid.0, uuid.1, uuid.2, uuid.3)
 }
 
-pub struct Factory<P> {
+fn subcategory(feature: &Feature) -> &str {
+    match feature {
+        Feature::Synthesizer => "Synth",
+        Feature::Sampler => "Sampler",
+        Feature::Drum => "Drum",
+        Feature::Filter => "Filter",
+        Feature::Equalizer => "EQ",
+        Feature::Compressor | Feature::Limiter | Feature::Gate => "Dynamics",
+        Feature::Distortion => "Distortion",
+        Feature::Reverb => "Reverb",
+        Feature::Delay => "Delay",
+        Feature::Chorus | Feature::Flanger | Feature::Phaser => "Modulation",
+        Feature::PitchShifter => "Pitch Shift",
+        Feature::Utility => "Tools",
+        Feature::Mono => "Mono",
+        Feature::Stereo => "Stereo",
+        Feature::Surround => "Surround",
+        Feature::Custom(name) => name,
+    }
+}
+
+// e.g. "Fx|Reverb|Stereo"
+fn subcategories(info: &PluginInfo) -> String {
+    let mut categories = vec![match info.category {
+        Category::Instrument => "Instrument",
+        Category::Effect | Category::NoteEffect | Category::Analyzer => "Fx",
+    }];
+    if info.category == Category::Analyzer {
+        categories.push("Analyzer");
+    }
+
+    for feature in &info.features {
+        let name = subcategory(feature);
+        if !categories.contains(&name) {
+            categories.push(name);
+        }
+    }
+
+    categories.join("|")
+}
+
+#[doc(hidden)]
+pub struct FactoryEntry {
+    info: fn() -> PluginInfo,
+    vst3_info: fn() -> Vst3Info,
+    create: unsafe fn(info: &Arc<PluginInfo>, iid: FIDString, obj: *mut *mut c_void) -> tresult,
+    create_processor:
+        unsafe fn(info: &Arc<PluginInfo>, iid: FIDString, obj: *mut *mut c_void) -> tresult,
+    create_controller:
+        unsafe fn(info: &Arc<PluginInfo>, iid: FIDString, obj: *mut *mut c_void) -> tresult,
+}
+
+impl FactoryEntry {
+    pub const fn new<P: Plugin + Vst3Plugin>() -> FactoryEntry {
+        FactoryEntry {
+            info: P::info,
+            vst3_info: P::vst3_info,
+            create: Self::create::<P, Combined>,
+            create_processor: Self::create::<P, Processor>,
+            create_controller: Self::create::<P, Controller>,
+        }
+    }
+
+    unsafe fn create<P: Plugin + Vst3Plugin, M: Mode>(
+        info: &Arc<PluginInfo>,
+        iid: FIDString,
+        obj: *mut *mut c_void,
+    ) -> tresult
+    where
+        Component<P, M>: Class,
+    {
+        let controller_id = P::vst3_info().controller_id.as_ref().map(uuid_to_tuid);
+        let component = ComWrapper::new(Component::<P, M>::new(info, controller_id));
+        let unknown = component.as_com_ref::<FUnknown>().unwrap();
+        let ptr = unknown.as_ptr();
+        ((*(*ptr).vtbl).queryInterface)(ptr, iid as *const TUID, obj)
+    }
+}
+
+struct ClassState {
     info: Arc<PluginInfo>,
-    vst3_info: Vst3Info,
-    _marker: PhantomData<P>,
+    class_id: TUID,
+    category: &'static str,
+    subcategories: String,
+    create: unsafe fn(&Arc<PluginInfo>, FIDString, *mut *mut c_void) -> tresult,
 }
 
-impl<P: Plugin + Vst3Plugin> Factory<P> {
-    pub fn new() -> Factory<P> {
-        Factory {
-            info: Arc::new(P::info()),
-            vst3_info: P::vst3_info(),
-            _marker: PhantomData,
+pub struct Factory {
+    classes: Vec<ClassState>,
+}
+
+impl Factory {
+    pub fn new(entries: &[FactoryEntry]) -> Factory {
+        assert!(!entries.is_empty());
+
+        let mut classes = Vec::new();
+        for entry in entries {
+            let info = (entry.info)();
+            let vst3_info = (entry.vst3_info)();
+            let info = Arc::new(info);
+
+            if let Some(controller_id) = &vst3_info.controller_id {
+                classes.push(ClassState {
+                    info: info.clone(),
+                    class_id: uuid_to_tuid(&vst3_info.class_id),
+                    category: AUDIO_MODULE_CLASS,
+                    subcategories: subcategories(&info),
+                    create: entry.create_processor,
+                });
+                classes.push(ClassState {
+                    info: info.clone(),
+                    class_id: uuid_to_tuid(controller_id),
+                    category: CONTROLLER_CLASS,
+                    subcategories: String::new(),
+                    create: entry.create_controller,
+                });
+            } else {
+                classes.push(ClassState {
+                    subcategories: subcategories(&info),
+                    info,
+                    class_id: uuid_to_tuid(&vst3_info.class_id),
+                    category: AUDIO_MODULE_CLASS,
+                    create: entry.create,
+                });
+            }
         }
+
+        Factory { classes }
+    }
+
+    fn class(&self, index: int32) -> Option<&ClassState> {
+        usize::try_from(index).ok().and_then(|index| self.classes.get(index))
     }
 }
 
-impl<P: Plugin> Class for Factory<P> {
+impl Class for Factory {
     type Interfaces = (IPluginFactory3,);
 }
 
-impl<P: Plugin> IPluginFactoryTrait for Factory<P> {
+impl IPluginFactoryTrait for Factory {
     unsafe fn getFactoryInfo(&self, info: *mut PFactoryInfo) -> tresult {
         let info = &mut *info;
 
-        copy_cstring(&self.info.vendor, &mut info.vendor);
-        copy_cstring(&self.info.url, &mut info.url);
-        copy_cstring(&self.info.email, &mut info.email);
+        // VST3 has a single vendor per factory, so use the first plugin's
+        let plugin_info = &self.classes[0].info;
+        copy_cstring(&plugin_info.vendor, &mut info.vendor);
+        copy_cstring(&plugin_info.url, &mut info.url);
+        copy_cstring(&plugin_info.email, &mut info.email);
         info.flags = PFactoryInfo_::FactoryFlags_::kUnicode as int32;
 
         kResultOk
     }
 
     unsafe fn countClasses(&self) -> int32 {
-        1
+        self.classes.len() as int32
     }
 
     unsafe fn getClassInfo(&self, index: int32, info: *mut PClassInfo) -> tresult {
-        if index == 0 {
+        if let Some(class) = self.class(index) {
             let info = &mut *info;
 
-            info.cid = uuid_to_tuid(&self.vst3_info.class_id);
+            info.cid = class.class_id;
             info.cardinality = PClassInfo_::ClassCardinality_::kManyInstances as int32;
-            copy_cstring("Audio Module Class", &mut info.category);
-            copy_cstring(&self.info.name, &mut info.name);
+            copy_cstring(class.category, &mut info.category);
+            copy_cstring(&class.info.name, &mut info.name);
 
             return kResultOk;
         }
@@ -72,31 +195,29 @@ impl<P: Plugin> IPluginFactoryTrait for Factory<P> {
         obj: *mut *mut c_void,
     ) -> tresult {
         let cid = &*(cid as *const TUID);
-        let class_id = uuid_to_tuid(&self.vst3_info.class_id);
-        if cid == &class_id {
-            let component = ComWrapper::new(Component::<P>::new(&self.info));
-            let unknown = component.as_com_ref::<FUnknown>().unwrap();
-            let ptr = unknown.as_ptr();
-            return ((*(*ptr).vtbl).queryInterface)(ptr, iid as *const TUID, obj);
+        for class in &self.classes {
+            if cid == &class.class_id {
+                return (class.create)(&class.info, iid, obj);
+            }
         }
 
         kInvalidArgument
     }
 }
 
-impl<P: Plugin> IPluginFactory2Trait for Factory<P> {
+impl IPluginFactory2Trait for Factory {
     unsafe fn getClassInfo2(&self, index: int32, info: *mut PClassInfo2) -> tresult {
-        if index == 0 {
+        if let Some(class) = self.class(index) {
             let info = &mut *info;
 
-            info.cid = uuid_to_tuid(&self.vst3_info.class_id);
+            info.cid = class.class_id;
             info.cardinality = PClassInfo_::ClassCardinality_::kManyInstances as int32;
-            copy_cstring("Audio Module Class", &mut info.category);
-            copy_cstring(&self.info.name, &mut info.name);
+            copy_cstring(class.category, &mut info.category);
+            copy_cstring(&class.info.name, &mut info.name);
             info.classFlags = 0;
-            copy_cstring("Fx", &mut info.subCategories);
-            copy_cstring(&self.info.vendor, &mut info.vendor);
-            copy_cstring(&self.info.version, &mut info.version);
+            copy_cstring(&class.subcategories, &mut info.subCategories);
+            copy_cstring(&class.info.vendor, &mut info.vendor);
+            copy_cstring(&class.info.version, &mut info.version);
             let version_str = CStr::from_ptr(SDKVersionString).to_str().unwrap();
             copy_cstring(version_str, &mut info.sdkVersion);
 
@@ -107,19 +228,19 @@ impl<P: Plugin> IPluginFactory2Trait for Factory<P> {
     }
 }
 
-impl<P: Plugin> IPluginFactory3Trait for Factory<P> {
+impl IPluginFactory3Trait for Factory {
     unsafe fn getClassInfoUnicode(&self, index: int32, info: *mut PClassInfoW) -> tresult {
-        if index == 0 {
+        if let Some(class) = self.class(index) {
             let info = &mut *info;
 
-            info.cid = uuid_to_tuid(&self.vst3_info.class_id);
+            info.cid = class.class_id;
             info.cardinality = PClassInfo_::ClassCardinality_::kManyInstances as int32;
-            copy_cstring("Audio Module Class", &mut info.category);
-            copy_wstring(&self.info.name, &mut info.name);
+            copy_cstring(class.category, &mut info.category);
+            copy_wstring(&class.info.name, &mut info.name);
             info.classFlags = 0;
-            copy_cstring("Fx", &mut info.subCategories);
-            copy_wstring(&self.info.vendor, &mut info.vendor);
-            copy_wstring(&self.info.version, &mut info.version);
+            copy_cstring(&class.subcategories, &mut info.subCategories);
+            copy_wstring(&class.info.vendor, &mut info.vendor);
+            copy_wstring(&class.info.version, &mut info.version);
             let version_str = CStr::from_ptr(SDKVersionString).to_str().unwrap();
             copy_wstring(version_str, &mut info.sdkVersion);
 
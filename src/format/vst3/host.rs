@@ -1,11 +1,136 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::ptr;
+use std::sync::Arc;
+
+use vst3::Steinberg::Vst::{
+    IAttributeList, IAttributeListTrait, IComponentHandler, IComponentHandlerTrait,
+    IConnectionPoint, IConnectionPointTrait, IHostApplication, IHostApplicationTrait, IMessage,
+    IMessageTrait, RestartFlags_,
+};
+use vst3::Steinberg::{int32, int64, kResultOk};
+use vst3::{ComPtr, ComRef, Interface};
+
 use crate::host::HostInner;
+use crate::params::{ParamId, ParamValue};
+use crate::sync::params::ParamValues;
+
+// Messages exchanged between the processor and the edit controller when they are separate objects.
+pub const STATE_MESSAGE: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"coupler.state\0") };
+pub const PARAM_MESSAGE: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"coupler.param\0") };
+
+pub const DATA_ATTR: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"data\0") };
+pub const ID_ATTR: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"id\0") };
+pub const VALUE_ATTR: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"value\0") };
+
+pub struct Vst3Host {
+    pub handler: RefCell<Option<ComPtr<IComponentHandler>>>,
+    pub host_application: RefCell<Option<ComPtr<IHostApplication>>>,
+    pub peer: RefCell<Option<ComPtr<IConnectionPoint>>>,
+    param_map: Arc<HashMap<ParamId, usize>>,
+    plugin_params: Arc<ParamValues>,
+    engine_params: Arc<ParamValues>,
+}
 
-pub struct Vst3Host {}
+// The component handler, host application, and peer are only accessed from the main thread.
+unsafe impl Send for Vst3Host {}
+unsafe impl Sync for Vst3Host {}
 
 impl Vst3Host {
-    pub fn new() -> Vst3Host {
-        Vst3Host {}
+    pub fn new(
+        param_map: Arc<HashMap<ParamId, usize>>,
+        plugin_params: Arc<ParamValues>,
+        engine_params: Arc<ParamValues>,
+    ) -> Vst3Host {
+        Vst3Host {
+            handler: RefCell::new(None),
+            host_application: RefCell::new(None),
+            peer: RefCell::new(None),
+            param_map,
+            plugin_params,
+            engine_params,
+        }
+    }
+
+    pub fn rescan_param_values(&self) {
+        let handler = self.handler.borrow();
+        if let Some(handler) = &*handler {
+            unsafe {
+                handler.restartComponent(RestartFlags_::kParamValuesChanged as int32);
+            }
+        }
+    }
+
+    pub fn perform_edit(&self, id: ParamId, value: ParamValue) {
+        let handler = self.handler.borrow();
+        if let Some(handler) = &*handler {
+            unsafe {
+                handler.beginEdit(id);
+                handler.performEdit(id, value);
+                handler.endEdit(id);
+            }
+        }
+    }
+
+    /// Sends a message to the connected processor or edit controller, if there is one.
+    pub fn send_message(&self, id: &CStr, attributes: impl FnOnce(ComRef<IAttributeList>)) {
+        let host_application = self.host_application.borrow().clone();
+        let peer = self.peer.borrow().clone();
+        let (Some(host_application), Some(peer)) = (host_application, peer) else {
+            return;
+        };
+
+        unsafe {
+            let iid = IMessage::IID.as_ptr() as *mut _;
+            let mut obj = ptr::null_mut();
+            if host_application.createInstance(iid, iid, &mut obj) != kResultOk {
+                return;
+            }
+            let Some(message) = ComPtr::from_raw(obj as *mut IMessage) else {
+                return;
+            };
+
+            message.setMessageID(id.as_ptr());
+            if let Some(list) = ComRef::from_raw(message.getAttributes()) {
+                attributes(list);
+            }
+
+            peer.notify(message.as_ptr());
+        }
     }
 }
 
-impl HostInner for Vst3Host {}
+impl HostInner for Vst3Host {
+    fn set_param(&self, id: ParamId, value: ParamValue) {
+        if let Some(&index) = self.param_map.get(&id) {
+            self.plugin_params.set(index, value);
+            self.engine_params.set(index, value);
+
+            // A separate processor has no component handler, so it forwards edits to the
+            // controller instead.
+            if self.handler.borrow().is_some() {
+                self.perform_edit(id, value);
+            } else {
+                self.send_message(PARAM_MESSAGE, |attributes| unsafe {
+                    attributes.setInt(ID_ATTR.as_ptr(), id as int64);
+                    attributes.setFloat(VALUE_ATTR.as_ptr(), value);
+                });
+            }
+        }
+    }
+
+    // VST3 has no equivalent of CLAP's voice info or remote controls extensions.
+    fn voice_info_changed(&self) {}
+
+    fn remote_controls_changed(&self) {}
+
+    fn audio_ports_changed(&self) {
+        let handler = self.handler.borrow();
+        if let Some(handler) = &*handler {
+            unsafe {
+                handler.restartComponent(RestartFlags_::kIoChanged as int32);
+            }
+        }
+    }
+}
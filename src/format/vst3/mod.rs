@@ -8,15 +8,20 @@ mod buffers;
 mod component;
 mod factory;
 mod host;
+mod preset;
 mod util;
 mod view;
 
 #[cfg(test)]
 mod tests;
 
-use crate::plugin::Plugin;
 use factory::Factory;
 
+pub use preset::{read_vstpreset, write_vstpreset};
+
+#[doc(hidden)]
+pub use factory::FactoryEntry;
+
 pub struct Uuid(pub u32, pub u32, pub u32, pub u32);
 
 impl Uuid {
@@ -39,6 +44,10 @@ impl Uuid {
 
 pub struct Vst3Info {
     pub class_id: Uuid,
+    /// If set, the edit controller is registered as a separate class with this id, and kept in
+    /// sync with the processor through `IConnectionPoint` messages. Some hosts and remote
+    /// processing setups require this.
+    pub controller_id: Option<Uuid>,
 }
 
 pub trait Vst3Plugin {
@@ -46,8 +55,8 @@ pub trait Vst3Plugin {
 }
 
 #[doc(hidden)]
-pub fn get_plugin_factory<P: Plugin + Vst3Plugin>() -> *mut c_void {
-    ComWrapper::new(Factory::<P>::new())
+pub fn get_plugin_factory(entries: &[FactoryEntry]) -> *mut c_void {
+    ComWrapper::new(Factory::new(entries))
         .to_com_ptr::<IPluginFactory>()
         .unwrap()
         .into_raw() as *mut c_void
@@ -55,7 +64,7 @@ pub fn get_plugin_factory<P: Plugin + Vst3Plugin>() -> *mut c_void {
 
 #[macro_export]
 macro_rules! vst3 {
-    ($plugin:ty) => {
+    ($($plugin:ty),+ $(,)?) => {
         #[cfg(target_os = "windows")]
         #[no_mangle]
         extern "system" fn InitDll() -> bool {
@@ -65,6 +74,7 @@ macro_rules! vst3 {
         #[cfg(target_os = "windows")]
         #[no_mangle]
         extern "system" fn ExitDll() -> bool {
+            ::coupler::logging::shutdown();
             true
         }
 
@@ -77,6 +87,7 @@ macro_rules! vst3 {
         #[cfg(target_os = "macos")]
         #[no_mangle]
         extern "system" fn BundleExit() -> bool {
+            ::coupler::logging::shutdown();
             true
         }
 
@@ -89,12 +100,15 @@ macro_rules! vst3 {
         #[cfg(target_os = "linux")]
         #[no_mangle]
         extern "system" fn ModuleExit() -> bool {
+            ::coupler::logging::shutdown();
             true
         }
 
         #[no_mangle]
         extern "system" fn GetPluginFactory() -> *mut ::std::ffi::c_void {
-            ::coupler::format::vst3::get_plugin_factory::<$plugin>()
+            ::coupler::format::vst3::get_plugin_factory(&[
+                $(::coupler::format::vst3::FactoryEntry::new::<$plugin>()),+
+            ])
         }
     };
 }
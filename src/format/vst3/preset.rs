@@ -0,0 +1,112 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use super::{Uuid, Vst3Plugin};
+use crate::plugin::Plugin;
+use crate::state::StateContext;
+
+const HEADER_ID: [u8; 4] = *b"VST3";
+const HEADER_VERSION: i32 = 1;
+const HEADER_SIZE: usize = 48;
+const LIST_ID: [u8; 4] = *b"List";
+const LIST_ENTRY_SIZE: usize = 20;
+const COMPONENT_STATE_ID: [u8; 4] = *b"Comp";
+
+// Class ids are stored as 32 hex digits, in the same order as they are passed to `uid`.
+fn class_id_string(class_id: &Uuid) -> String {
+    format!(
+        "{:08X}{:08X}{:08X}{:08X}",
+        class_id.0, class_id.1, class_id.2, class_id.3
+    )
+}
+
+fn read_i64(bytes: &[u8], offset: usize) -> Option<i64> {
+    let bytes = bytes.get(offset..offset + 8)?;
+    Some(i64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn invalid_preset() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "invalid vstpreset file")
+}
+
+/// Writes the current state of `plugin` to `output` as a Steinberg `.vstpreset` file.
+///
+/// The output of [`Plugin::save`] is stored as the component state chunk, tagged with
+/// [`Vst3Info::class_id`](super::Vst3Info::class_id).
+pub fn write_vstpreset<P>(plugin: &P, output: &mut impl Write) -> io::Result<()>
+where
+    P: Plugin + Vst3Plugin,
+{
+    let mut state = Vec::new();
+    plugin.save(&mut state, StateContext::Preset)?;
+
+    let class_id = class_id_string(&P::vst3_info().class_id);
+    let list_offset = HEADER_SIZE + state.len();
+
+    output.write_all(&HEADER_ID)?;
+    output.write_all(&HEADER_VERSION.to_le_bytes())?;
+    output.write_all(class_id.as_bytes())?;
+    output.write_all(&(list_offset as i64).to_le_bytes())?;
+
+    output.write_all(&state)?;
+
+    output.write_all(&LIST_ID)?;
+    output.write_all(&1i32.to_le_bytes())?;
+    output.write_all(&COMPONENT_STATE_ID)?;
+    output.write_all(&(HEADER_SIZE as i64).to_le_bytes())?;
+    output.write_all(&(state.len() as i64).to_le_bytes())
+}
+
+/// Reads a `.vstpreset` file and passes its component state to [`Plugin::load`]. Fails without
+/// touching the plugin if the preset was saved for a different class id.
+pub fn read_vstpreset<P>(plugin: &mut P, input: &mut impl Read) -> io::Result<()>
+where
+    P: Plugin + Vst3Plugin,
+{
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+
+    let header = bytes.get(..HEADER_SIZE).ok_or_else(invalid_preset)?;
+    if header[0..4] != HEADER_ID {
+        return Err(invalid_preset());
+    }
+
+    let class_id = class_id_string(&P::vst3_info().class_id);
+    if !header[8..40].eq_ignore_ascii_case(class_id.as_bytes()) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "preset was saved by a different plugin",
+        ));
+    }
+
+    let list_offset = read_i64(header, 40).ok_or_else(invalid_preset)?;
+    let list = usize::try_from(list_offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..))
+        .ok_or_else(invalid_preset)?;
+    if list.get(0..4) != Some(&LIST_ID[..]) {
+        return Err(invalid_preset());
+    }
+
+    let count = list.get(4..8).ok_or_else(invalid_preset)?;
+    let count = i32::from_le_bytes(count.try_into().unwrap()).max(0) as usize;
+
+    for entry in list[8..].chunks_exact(LIST_ENTRY_SIZE).take(count) {
+        if entry[0..4] != COMPONENT_STATE_ID {
+            continue;
+        }
+
+        let offset = read_i64(entry, 4).and_then(|offset| usize::try_from(offset).ok());
+        let size = read_i64(entry, 12).and_then(|size| usize::try_from(size).ok());
+        let chunk = offset
+            .zip(size)
+            .and_then(|(offset, size)| bytes.get(offset..offset.checked_add(size)?))
+            .ok_or_else(invalid_preset)?;
+
+        return plugin.load(&mut &chunk[..], StateContext::Preset);
+    }
+
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        "vstpreset file has no component state",
+    ))
+}
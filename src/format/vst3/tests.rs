@@ -1,60 +1,164 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::error::Error;
-use std::ffi::CStr;
+use std::ffi::{c_void, CStr, CString};
 use std::fmt::{self, Formatter};
 use std::io::{self, Read, Write};
+use std::sync::Mutex;
 use std::{ptr, slice};
 
 use crate::buffers::Buffers;
 use crate::engine::{Config, Engine};
-use crate::events::Events;
+use crate::events::{Data, Events};
 use crate::host::Host;
-use crate::params::{ParamId, ParamValue};
-use crate::plugin::{Plugin, PluginInfo};
+use crate::midi::{MidiController, MidiMapping};
+use crate::params::{ParamId, ParamInfo, ParamValue};
+use crate::plugin::{Category, Feature, Plugin, PluginInfo};
+use crate::preset::PresetInfo;
+use crate::state::{State, StateContext};
 use crate::view::{ParentWindow, Size, View, ViewHost};
 
-use vst3::Steinberg::Vst::{IComponent, SDKVersionString};
-use vst3::Steinberg::{char16, char8, int32};
+use super::component::program_change_id;
+
+use vst3::Steinberg::Vst::{
+    BusDirection, BusDirections_, BusInfo, ControllerNumbers_, CtrlNumber, IAttributeList,
+    IAttributeListTrait, IAttributeList_, IAudioProcessor, IAudioProcessorTrait, IComponent,
+    IComponentHandler, IComponentHandlerTrait, IComponentTrait, IConnectionPoint,
+    IConnectionPointTrait, IEditController, IEditControllerTrait, IHostApplication,
+    IHostApplicationTrait, IMessage, IMessageTrait, IMidiMapping, IMidiMappingTrait,
+    IParamValueQueue, IParamValueQueueTrait, IParameterChanges, IParameterChangesTrait, IUnitInfo,
+    IUnitInfoTrait, MediaType, MediaTypes_, ParamID, ParameterInfo, ParameterInfo_, ProcessData,
+    ProcessModes_, ProcessSetup, ProgramListInfo, RestartFlags_, SDKVersionString, String128,
+    SymbolicSampleSizes_, TChar, UnitInfo,
+};
+use vst3::Steinberg::{char16, char8, int16, int32, int64, kResultFalse, tresult, uint32, TUID};
 use vst3::Steinberg::{
-    kInvalidArgument, kResultOk, FIDString, IPluginFactory, IPluginFactory2, IPluginFactory2Trait,
-    IPluginFactory3, IPluginFactory3Trait, IPluginFactoryTrait, PClassInfo, PClassInfo2,
-    PClassInfoW, PClassInfo_, PFactoryInfo, PFactoryInfo_,
+    kInvalidArgument, kNotImplemented, kResultOk, FIDString, FUnknown, IBStream, IBStreamTrait,
+    IPluginBaseTrait, IPluginFactory, IPluginFactory2, IPluginFactory2Trait, IPluginFactory3,
+    IPluginFactory3Trait, IPluginFactoryTrait, PClassInfo, PClassInfo2, PClassInfoW, PClassInfo_,
+    PFactoryInfo, PFactoryInfo_,
 };
-use vst3::{uid, ComPtr, Interface};
+use vst3::{uid, Class, ComPtr, ComWrapper, Interface};
 
-use super::{get_plugin_factory, Uuid, Vst3Info, Vst3Plugin};
+use super::{
+    get_plugin_factory, read_vstpreset, write_vstpreset, FactoryEntry, Uuid, Vst3Info, Vst3Plugin,
+};
 
-const NAME: &str = "test plugin";
+const NAMES: [&str; 3] = ["test plugin", "other test plugin", "split test plugin"];
 const VERSION: &str = "1.2.3";
 const VENDOR: &str = "test vendor";
 const URL: &str = "https://example.com/";
 const EMAIL: &str = "example@example.com";
-const CLASS_ID: [u32; 4] = [0x11111111, 0x22222222, 0x33333333, 0x44444444];
+const IDS: [&str; 3] = [
+    "com.example.plugin",
+    "com.example.other-plugin",
+    "com.example.split-plugin",
+];
+const OLD_VERSION: &str = "1.0.0";
+const PARAM: ParamId = 0;
+const OLD_PARAM: ParamId = 1;
+const OTHER_PARAM: ParamId = 2;
+const PRESETS: [(&str, Option<&str>, ParamValue); 2] =
+    [("soft", None, 0.25), ("loud", Some("bright"), 1.0)];
+const CLASS_IDS: [[u32; 4]; 3] = [
+    [0x11111111, 0x22222222, 0x33333333, 0x44444444],
+    [0x55555555, 0x66666666, 0x77777777, 0x88888888],
+    [0x99999999, 0xAAAAAAAA, 0xBBBBBBBB, 0xCCCCCCCC],
+];
+const CLASS_ID: [u32; 4] = CLASS_IDS[0];
+// Only the third plugin has a separate controller class.
+const CONTROLLER_ID: [u32; 4] = [0xDDDDDDDD, 0xEEEEEEEE, 0xFFFFFFFF, 0x00000000];
 
-struct TestPlugin;
+struct TestPlugin<const INDEX: usize> {
+    value: ParamValue,
+    data: Vec<u8>,
+}
 
-impl Plugin for TestPlugin {
+impl<const INDEX: usize> Plugin for TestPlugin<INDEX> {
     type Engine = TestEngine;
     type View = TestView;
 
     fn info() -> PluginInfo {
         PluginInfo {
-            name: NAME.to_string(),
+            id: IDS[INDEX].to_string(),
+            name: NAMES[INDEX].to_string(),
             version: VERSION.to_string(),
             vendor: VENDOR.to_string(),
             url: URL.to_string(),
             email: EMAIL.to_string(),
+            category: Category::Effect,
+            features: vec![
+                Feature::Reverb,
+                Feature::Stereo,
+                Feature::Custom("Custom".to_string()),
+            ],
             buses: Vec::new(),
             layouts: vec![],
-            params: Vec::new(),
+            params: if INDEX == 1 {
+                vec![
+                    ParamInfo {
+                        id: PARAM,
+                        name: "param".to_string(),
+                        default: 0.5,
+                        steps: None,
+                    },
+                    ParamInfo {
+                        id: OTHER_PARAM,
+                        name: "other param".to_string(),
+                        default: 0.5,
+                        steps: None,
+                    },
+                ]
+            } else {
+                vec![ParamInfo {
+                    id: PARAM,
+                    name: "param".to_string(),
+                    default: 0.5,
+                    steps: None,
+                }]
+            },
+            // The second plugin has no factory presets.
+            presets: if INDEX != 1 {
+                PRESETS
+                    .iter()
+                    .map(|&(name, category, _)| PresetInfo {
+                        name: name.to_string(),
+                        category: category.map(str::to_string),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            midi_mappings: if INDEX == 0 {
+                vec![
+                    MidiMapping {
+                        controller: MidiController::ControlChange(1),
+                        channel: None,
+                        param: PARAM,
+                    },
+                    MidiMapping {
+                        controller: MidiController::PitchBend,
+                        channel: Some(0),
+                        param: PARAM,
+                    },
+                ]
+            } else {
+                Vec::new()
+            },
             has_view: false,
         }
     }
     fn new(_host: Host) -> Self {
-        TestPlugin
+        TestPlugin {
+            value: 0.5,
+            data: Vec::new(),
+        }
+    }
+    fn set_param(&mut self, _id: ParamId, value: ParamValue) {
+        self.value = value;
     }
-    fn set_param(&mut self, _id: ParamId, _value: ParamValue) {}
     fn get_param(&self, _id: ParamId) -> ParamValue {
-        0.0
+        self.value
     }
     fn parse_param(&self, _id: ParamId, _text: &str) -> Option<ParamValue> {
         None
@@ -67,10 +171,19 @@ impl Plugin for TestPlugin {
     ) -> Result<(), fmt::Error> {
         Ok(())
     }
-    fn save(&self, _output: &mut impl Write) -> io::Result<()> {
+    fn save(&self, output: &mut impl Write, _context: StateContext) -> io::Result<()> {
+        let mut state = State::capture(self);
+        state.data = self.data.clone();
+        state.write(output)
+    }
+    fn load(&mut self, input: &mut impl Read, _context: StateContext) -> io::Result<()> {
+        let mut state = State::read(input)?;
+        state.restore(self)?;
+        self.data = state.data;
         Ok(())
     }
-    fn load(&mut self, _input: &mut impl Read) -> io::Result<()> {
+    fn load_preset(&mut self, index: usize) -> io::Result<()> {
+        self.value = PRESETS[index].2;
         Ok(())
     }
     fn engine(&mut self, _config: &Config) -> Self::Engine {
@@ -84,22 +197,58 @@ impl Plugin for TestPlugin {
     fn latency(&self, _config: &Config) -> u64 {
         0
     }
+
+    fn migrate(&mut self, from_version: &str, state: &mut State) -> io::Result<()> {
+        if from_version == OLD_VERSION {
+            if let Some(value) = state.remove_param(OLD_PARAM) {
+                state.set_param(PARAM, value);
+            }
+        }
+        Ok(())
+    }
 }
 
-impl Vst3Plugin for TestPlugin {
+impl<const INDEX: usize> Vst3Plugin for TestPlugin<INDEX> {
     fn vst3_info() -> Vst3Info {
+        let [a, b, c, d] = CLASS_IDS[INDEX];
         Vst3Info {
-            class_id: Uuid(CLASS_ID[0], CLASS_ID[1], CLASS_ID[2], CLASS_ID[3]),
+            class_id: Uuid(a, b, c, d),
+            controller_id: (INDEX == 2).then(|| {
+                let [a, b, c, d] = CONTROLLER_ID;
+                Uuid(a, b, c, d)
+            }),
         }
     }
 }
 
+// Parameter events received by engines, as (time, id, value, ramp length).
+static ENGINE_EVENTS: Mutex<Vec<(i64, ParamId, ParamValue, Option<u32>)>> = Mutex::new(Vec::new());
+
 struct TestEngine;
 
+impl TestEngine {
+    fn record(events: Events) {
+        let mut recorded = ENGINE_EVENTS.lock().unwrap();
+        for event in events {
+            match event.data {
+                Data::ParamChange { id, value } => recorded.push((event.time, id, value, None)),
+                Data::ParamRamp { id, value, len } => {
+                    recorded.push((event.time, id, value, Some(len)))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 impl Engine for TestEngine {
     fn reset(&mut self) {}
-    fn flush(&mut self, _events: Events) {}
-    fn process(&mut self, _buffers: Buffers, _events: Events) {}
+    fn flush(&mut self, events: Events) {
+        Self::record(events);
+    }
+    fn process(&mut self, _buffers: Buffers, events: Events) {
+        Self::record(events);
+    }
 }
 
 struct TestView;
@@ -126,7 +275,10 @@ fn string_from_wchars(wchars: &[char16]) -> Result<String, std::char::DecodeUtf1
 
 #[test]
 fn factory() {
-    let ptr = get_plugin_factory::<TestPlugin>() as *mut IPluginFactory;
+    let ptr = get_plugin_factory(&[
+        FactoryEntry::new::<TestPlugin<0>>(),
+        FactoryEntry::new::<TestPlugin<1>>(),
+    ]) as *mut IPluginFactory;
     let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
 
     let mut factory_info = PFactoryInfo {
@@ -147,7 +299,7 @@ fn factory() {
     );
 
     let class_count = unsafe { factory.countClasses() };
-    assert_eq!(class_count, 1);
+    assert_eq!(class_count, 2);
 
     let mut class_info = PClassInfo {
         cid: [0; 16],
@@ -170,9 +322,16 @@ fn factory() {
         str_from_chars(&class_info.category).unwrap(),
         "Audio Module Class"
     );
-    assert_eq!(str_from_chars(&class_info.name).unwrap(), NAME);
+    assert_eq!(str_from_chars(&class_info.name).unwrap(), NAMES[0]);
 
     let result = unsafe { factory.getClassInfo(1, &mut class_info) };
+    assert_eq!(result, kResultOk);
+
+    let [a, b, c, d] = CLASS_IDS[1];
+    assert_eq!(class_info.cid, uid(a, b, c, d));
+    assert_eq!(str_from_chars(&class_info.name).unwrap(), NAMES[1]);
+
+    let result = unsafe { factory.getClassInfo(2, &mut class_info) };
     assert_eq!(result, kInvalidArgument);
 
     let factory_2 = factory.cast::<IPluginFactory2>().unwrap();
@@ -205,9 +364,12 @@ fn factory() {
         str_from_chars(&class_info_2.category).unwrap(),
         "Audio Module Class"
     );
-    assert_eq!(str_from_chars(&class_info_2.name).unwrap(), NAME);
+    assert_eq!(str_from_chars(&class_info_2.name).unwrap(), NAMES[0]);
     assert_eq!(class_info_2.classFlags, 0);
-    assert_eq!(str_from_chars(&class_info_2.subCategories).unwrap(), "Fx");
+    assert_eq!(
+        str_from_chars(&class_info_2.subCategories).unwrap(),
+        "Fx|Reverb|Stereo|Custom"
+    );
     assert_eq!(str_from_chars(&class_info_2.vendor).unwrap(), VENDOR);
     assert_eq!(str_from_chars(&class_info_2.version).unwrap(), VERSION);
     assert_eq!(
@@ -215,7 +377,7 @@ fn factory() {
         sdk_version
     );
 
-    let result = unsafe { factory_2.getClassInfo(1, &mut class_info) };
+    let result = unsafe { factory_2.getClassInfo(2, &mut class_info) };
     assert_eq!(result, kInvalidArgument);
 
     let factory_3 = factory.cast::<IPluginFactory3>().unwrap();
@@ -246,9 +408,12 @@ fn factory() {
         str_from_chars(&class_info_w.category).unwrap(),
         "Audio Module Class"
     );
-    assert_eq!(string_from_wchars(&class_info_w.name).unwrap(), NAME);
+    assert_eq!(string_from_wchars(&class_info_w.name).unwrap(), NAMES[0]);
     assert_eq!(class_info_w.classFlags, 0);
-    assert_eq!(str_from_chars(&class_info_w.subCategories).unwrap(), "Fx");
+    assert_eq!(
+        str_from_chars(&class_info_w.subCategories).unwrap(),
+        "Fx|Reverb|Stereo|Custom"
+    );
     assert_eq!(string_from_wchars(&class_info_w.vendor).unwrap(), VENDOR);
     assert_eq!(string_from_wchars(&class_info_w.version).unwrap(), VERSION);
     assert_eq!(
@@ -256,7 +421,7 @@ fn factory() {
         sdk_version
     );
 
-    let result = unsafe { factory_3.getClassInfoUnicode(1, &mut class_info_w) };
+    let result = unsafe { factory_3.getClassInfoUnicode(2, &mut class_info_w) };
     assert_eq!(result, kInvalidArgument);
 
     let mut obj = ptr::null_mut();
@@ -270,4 +435,759 @@ fn factory() {
     assert_eq!(result, kResultOk);
 
     unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap();
+
+    let mut obj = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            uid(a, b, c, d).as_ptr(),
+            IComponent::IID.as_ptr() as FIDString,
+            &mut obj,
+        )
+    };
+    assert_eq!(result, kResultOk);
+
+    unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap();
+}
+
+struct Stream {
+    data: RefCell<Vec<u8>>,
+    pos: Cell<usize>,
+}
+
+impl Class for Stream {
+    type Interfaces = (IBStream,);
+}
+
+#[allow(non_snake_case)]
+impl IBStreamTrait for Stream {
+    unsafe fn read(
+        &self,
+        buffer: *mut c_void,
+        numBytes: int32,
+        numBytesRead: *mut int32,
+    ) -> tresult {
+        let data = self.data.borrow();
+        let pos = self.pos.get();
+        let len = (numBytes.max(0) as usize).min(data.len() - pos);
+        ptr::copy_nonoverlapping(data[pos..].as_ptr(), buffer as *mut u8, len);
+        self.pos.set(pos + len);
+
+        if !numBytesRead.is_null() {
+            *numBytesRead = len as int32;
+        }
+
+        kResultOk
+    }
+
+    unsafe fn write(
+        &self,
+        buffer: *mut c_void,
+        numBytes: int32,
+        numBytesWritten: *mut int32,
+    ) -> tresult {
+        let bytes = slice::from_raw_parts(buffer as *const u8, numBytes.max(0) as usize);
+        self.data.borrow_mut().extend_from_slice(bytes);
+
+        if !numBytesWritten.is_null() {
+            *numBytesWritten = bytes.len() as int32;
+        }
+
+        kResultOk
+    }
+
+    unsafe fn seek(&self, _pos: int64, _mode: int32, _result: *mut int64) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn tell(&self, pos: *mut int64) -> tresult {
+        *pos = self.pos.get() as int64;
+        kResultOk
+    }
+}
+
+#[derive(Default)]
+struct TestHandler {
+    restarts: Cell<u32>,
+}
+
+impl Class for TestHandler {
+    type Interfaces = (IComponentHandler,);
+}
+
+#[allow(non_snake_case)]
+impl IComponentHandlerTrait for TestHandler {
+    unsafe fn beginEdit(&self, _id: ParamID) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn performEdit(&self, _id: ParamID, _valueNormalized: ParamValue) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn endEdit(&self, _id: ParamID) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn restartComponent(&self, flags: int32) -> tresult {
+        assert_eq!(flags, RestartFlags_::kParamValuesChanged as int32);
+        self.restarts.set(self.restarts.get() + 1);
+        kResultOk
+    }
+}
+
+fn save_state(component: &ComPtr<IComponent>) -> Option<Vec<u8>> {
+    let stream = ComWrapper::new(Stream {
+        data: RefCell::new(Vec::new()),
+        pos: Cell::new(0),
+    });
+    let ptr = stream.to_com_ptr::<IBStream>().unwrap();
+
+    let result = unsafe { component.getState(ptr.as_ptr()) };
+    (result == kResultOk).then(|| stream.data.take())
+}
+
+fn load_state(component: &ComPtr<IComponent>, bytes: &[u8]) -> bool {
+    let stream = ComWrapper::new(Stream {
+        data: RefCell::new(bytes.to_vec()),
+        pos: Cell::new(0),
+    });
+    let ptr = stream.to_com_ptr::<IBStream>().unwrap();
+
+    unsafe { component.setState(ptr.as_ptr()) == kResultOk }
+}
+
+#[test]
+fn state() {
+    let ptr = get_plugin_factory(&[FactoryEntry::new::<TestPlugin<0>>()]) as *mut IPluginFactory;
+    let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
+
+    let create = || {
+        let mut obj = ptr::null_mut();
+        let result = unsafe {
+            factory.createInstance(
+                uid(CLASS_ID[0], CLASS_ID[1], CLASS_ID[2], CLASS_ID[3]).as_ptr(),
+                IComponent::IID.as_ptr() as FIDString,
+                &mut obj,
+            )
+        };
+        assert_eq!(result, kResultOk);
+
+        unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap()
+    };
+
+    let mut state = State::new(IDS[0], VERSION);
+    state.set_param(PARAM, 0.25);
+    state.data = vec![1, 2, 3];
+    let mut bytes = Vec::new();
+    state.write(&mut bytes).unwrap();
+
+    let component = create();
+    let controller = component.cast::<IEditController>().unwrap();
+    let handler = ComWrapper::new(TestHandler::default());
+    let handler_ptr = handler.to_com_ptr::<IComponentHandler>().unwrap();
+    let result = unsafe { controller.setComponentHandler(handler_ptr.as_ptr()) };
+    assert_eq!(result, kResultOk);
+
+    // Loading state asks the host to rescan parameter values.
+    assert!(load_state(&component, &bytes));
+    assert_eq!(unsafe { controller.getParamNormalized(PARAM) }, 0.25);
+    assert_eq!(handler.restarts.get(), 1);
+    let saved = State::read(&mut &save_state(&component).unwrap()[..]).unwrap();
+    assert_eq!(saved, state);
+
+    let component = create();
+    let controller = component.cast::<IEditController>().unwrap();
+    let result = unsafe { controller.setParamNormalized(PARAM, 0.25) };
+    assert_eq!(result, kResultOk);
+    let saved = save_state(&component).unwrap();
+    assert_eq!(
+        State::read(&mut &saved[..]).unwrap().params,
+        [(PARAM, 0.25)]
+    );
+
+    // A state from an older version is migrated, and unknown params are ignored.
+    let mut old = State::new(IDS[0], OLD_VERSION);
+    old.set_param(OLD_PARAM, 0.75);
+    old.set_param(99, 1.0);
+    old.data = vec![4, 5];
+    let mut bytes = Vec::new();
+    old.write(&mut bytes).unwrap();
+    assert!(load_state(&component, &bytes));
+    assert_eq!(unsafe { controller.getParamNormalized(PARAM) }, 0.75);
+
+    let saved = State::read(&mut &save_state(&component).unwrap()[..]).unwrap();
+    assert_eq!(saved.plugin_version, VERSION);
+    assert_eq!(saved.params, [(PARAM, 0.75)]);
+    assert_eq!(saved.data, [4, 5]);
+
+    // A state from a different plugin is rejected.
+    let mut bytes = Vec::new();
+    State::new(IDS[1], VERSION).write(&mut bytes).unwrap();
+    assert!(!load_state(&component, &bytes));
+    assert_eq!(unsafe { controller.getParamNormalized(PARAM) }, 0.75);
+}
+
+#[test]
+fn presets() {
+    let ptr = get_plugin_factory(&[FactoryEntry::new::<TestPlugin<0>>()]) as *mut IPluginFactory;
+    let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
+
+    let mut obj = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            uid(CLASS_ID[0], CLASS_ID[1], CLASS_ID[2], CLASS_ID[3]).as_ptr(),
+            IComponent::IID.as_ptr() as FIDString,
+            &mut obj,
+        )
+    };
+    assert_eq!(result, kResultOk);
+
+    let component = unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap();
+    let controller = component.cast::<IEditController>().unwrap();
+    let unit_info = component.cast::<IUnitInfo>().unwrap();
+    let handler = ComWrapper::new(TestHandler::default());
+    let handler_ptr = handler.to_com_ptr::<IComponentHandler>().unwrap();
+    unsafe { controller.setComponentHandler(handler_ptr.as_ptr()) };
+
+    // The root unit exposes the factory presets as a program list.
+    assert_eq!(unsafe { unit_info.getUnitCount() }, 1);
+    let mut unit: UnitInfo = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { unit_info.getUnitInfo(0, &mut unit) }, kResultOk);
+    assert_eq!(unsafe { unit_info.getProgramListCount() }, 1);
+    let mut list: ProgramListInfo = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { unit_info.getProgramListInfo(0, &mut list) },
+        kResultOk
+    );
+    assert_eq!(unit.programListId, list.id);
+    assert_eq!(list.programCount, PRESETS.len() as int32);
+
+    for (index, &(name, category, _)) in PRESETS.iter().enumerate() {
+        let mut string: String128 = [0; 128];
+        let result = unsafe { unit_info.getProgramName(list.id, index as int32, &mut string) };
+        assert_eq!(result, kResultOk);
+        assert_eq!(string_from_wchars(&string).unwrap(), name);
+
+        let attribute = b"MusicalInstrument\0".as_ptr() as *const char8;
+        let result =
+            unsafe { unit_info.getProgramInfo(list.id, index as int32, attribute, &mut string) };
+        if let Some(category) = category {
+            assert_eq!(result, kResultOk);
+            assert_eq!(string_from_wchars(&string).unwrap(), category);
+        } else {
+            assert_eq!(result, kResultFalse);
+        }
+    }
+
+    // The last parameter selects a program.
+    let count = unsafe { controller.getParameterCount() };
+    assert_eq!(count, 2);
+    let mut info: ParameterInfo = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { controller.getParameterInfo(count - 1, &mut info) },
+        kResultOk
+    );
+    assert_ne!(
+        info.flags & ParameterInfo_::ParameterFlags_::kIsProgramChange as int32,
+        0
+    );
+    assert_eq!(info.stepCount, PRESETS.len() as int32 - 1);
+
+    let result = unsafe { controller.setParamNormalized(info.id, 1.0) };
+    assert_eq!(result, kResultOk);
+    assert_eq!(unsafe { controller.getParamNormalized(info.id) }, 1.0);
+    assert_eq!(
+        unsafe { controller.getParamNormalized(PARAM) },
+        PRESETS[1].2
+    );
+    assert_eq!(handler.restarts.get(), 1);
+
+    let mut string: String128 = [0; 128];
+    let result = unsafe { controller.getParamStringByValue(info.id, 0.0, &mut string) };
+    assert_eq!(result, kResultOk);
+    assert_eq!(string_from_wchars(&string).unwrap(), PRESETS[0].0);
+}
+
+#[test]
+fn program_change_param() {
+    let mut param_map = HashMap::new();
+    param_map.insert(PARAM, 0);
+    assert_eq!(program_change_id(&param_map), 0x7FFF_FFFF);
+
+    // Ids used by the plugin are skipped.
+    param_map.insert(0x7FFF_FFFF, 1);
+    param_map.insert(0x7FFF_FFFE, 2);
+    assert_eq!(program_change_id(&param_map), 0x7FFF_FFFD);
+}
+
+#[derive(Default)]
+struct TestAttributes {
+    ints: RefCell<HashMap<CString, int64>>,
+    floats: RefCell<HashMap<CString, f64>>,
+    binaries: RefCell<HashMap<CString, Vec<u8>>>,
+}
+
+impl Class for TestAttributes {
+    type Interfaces = (IAttributeList,);
+}
+
+#[allow(non_snake_case)]
+impl IAttributeListTrait for TestAttributes {
+    unsafe fn setInt(&self, id: IAttributeList_::AttrID, value: int64) -> tresult {
+        self.ints.borrow_mut().insert(CStr::from_ptr(id).to_owned(), value);
+        kResultOk
+    }
+
+    unsafe fn getInt(&self, id: IAttributeList_::AttrID, value: *mut int64) -> tresult {
+        if let Some(&int) = self.ints.borrow().get(CStr::from_ptr(id)) {
+            *value = int;
+            return kResultOk;
+        }
+
+        kResultFalse
+    }
+
+    unsafe fn setFloat(&self, id: IAttributeList_::AttrID, value: f64) -> tresult {
+        self.floats.borrow_mut().insert(CStr::from_ptr(id).to_owned(), value);
+        kResultOk
+    }
+
+    unsafe fn getFloat(&self, id: IAttributeList_::AttrID, value: *mut f64) -> tresult {
+        if let Some(&float) = self.floats.borrow().get(CStr::from_ptr(id)) {
+            *value = float;
+            return kResultOk;
+        }
+
+        kResultFalse
+    }
+
+    unsafe fn setString(&self, _id: IAttributeList_::AttrID, _string: *const TChar) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn getString(
+        &self,
+        _id: IAttributeList_::AttrID,
+        _string: *mut TChar,
+        _sizeInBytes: uint32,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn setBinary(
+        &self,
+        id: IAttributeList_::AttrID,
+        data: *const c_void,
+        sizeInBytes: uint32,
+    ) -> tresult {
+        let data = slice::from_raw_parts(data as *const u8, sizeInBytes as usize);
+        self.binaries.borrow_mut().insert(CStr::from_ptr(id).to_owned(), data.to_vec());
+        kResultOk
+    }
+
+    unsafe fn getBinary(
+        &self,
+        id: IAttributeList_::AttrID,
+        data: *mut *const c_void,
+        sizeInBytes: *mut uint32,
+    ) -> tresult {
+        if let Some(binary) = self.binaries.borrow().get(CStr::from_ptr(id)) {
+            *data = binary.as_ptr() as *const c_void;
+            *sizeInBytes = binary.len() as uint32;
+            return kResultOk;
+        }
+
+        kResultFalse
+    }
+}
+
+struct TestMessage {
+    id: RefCell<CString>,
+    attributes: ComWrapper<TestAttributes>,
+}
+
+impl Class for TestMessage {
+    type Interfaces = (IMessage,);
+}
+
+#[allow(non_snake_case)]
+impl IMessageTrait for TestMessage {
+    unsafe fn getMessageID(&self) -> FIDString {
+        self.id.borrow().as_ptr()
+    }
+
+    unsafe fn setMessageID(&self, id: FIDString) {
+        *self.id.borrow_mut() = CStr::from_ptr(id).to_owned();
+    }
+
+    unsafe fn getAttributes(&self) -> *mut IAttributeList {
+        self.attributes.as_com_ref::<IAttributeList>().unwrap().as_ptr()
+    }
+}
+
+struct TestHostApplication;
+
+impl Class for TestHostApplication {
+    type Interfaces = (IHostApplication,);
+}
+
+#[allow(non_snake_case)]
+impl IHostApplicationTrait for TestHostApplication {
+    unsafe fn getName(&self, _name: *mut String128) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn createInstance(
+        &self,
+        cid: *mut TUID,
+        _iid: *mut TUID,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        if *cid != IMessage::IID.map(|byte| byte as char8) {
+            *obj = ptr::null_mut();
+            return kResultFalse;
+        }
+
+        let message = ComWrapper::new(TestMessage {
+            id: RefCell::new(CString::default()),
+            attributes: ComWrapper::new(TestAttributes::default()),
+        });
+        *obj = message.to_com_ptr::<IMessage>().unwrap().into_raw() as *mut c_void;
+
+        kResultOk
+    }
+}
+
+#[test]
+fn midi_mapping() {
+    let ptr = get_plugin_factory(&[
+        FactoryEntry::new::<TestPlugin<0>>(),
+        FactoryEntry::new::<TestPlugin<1>>(),
+    ]) as *mut IPluginFactory;
+    let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
+
+    let create = |class_id: [u32; 4]| {
+        let mut obj = ptr::null_mut();
+        let result = unsafe {
+            factory.createInstance(
+                uid(class_id[0], class_id[1], class_id[2], class_id[3]).as_ptr(),
+                IComponent::IID.as_ptr() as FIDString,
+                &mut obj,
+            )
+        };
+        assert_eq!(result, kResultOk);
+
+        unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap()
+    };
+
+    let event = MediaTypes_::kEvent as MediaType;
+    let input = BusDirections_::kInput as BusDirection;
+
+    // Plugins without mappings have no event input.
+    let component = create(CLASS_IDS[1]);
+    assert_eq!(unsafe { component.getBusCount(event, input) }, 0);
+
+    let component = create(CLASS_IDS[0]);
+    assert_eq!(unsafe { component.getBusCount(event, input) }, 1);
+    let mut bus: BusInfo = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { component.getBusInfo(event, input, 0, &mut bus) },
+        kResultOk
+    );
+    assert_eq!(bus.channelCount, 16);
+
+    let midi_mapping = component.cast::<IMidiMapping>().unwrap();
+    let assignment = |bus: int32, channel: int16, controller: CtrlNumber| {
+        let mut id = ParamID::MAX;
+        let result =
+            unsafe { midi_mapping.getMidiControllerAssignment(bus, channel, controller, &mut id) };
+        (result == kResultOk).then_some(id)
+    };
+
+    let pitch_bend = ControllerNumbers_::kPitchBend as CtrlNumber;
+    assert_eq!(assignment(0, 5, 1), Some(PARAM));
+    assert_eq!(assignment(0, 0, pitch_bend), Some(PARAM));
+    assert_eq!(assignment(0, 1, pitch_bend), None);
+    assert_eq!(assignment(0, 0, 2), None);
+    assert_eq!(assignment(1, 0, 1), None);
+}
+
+#[test]
+fn split_controller() {
+    let ptr = get_plugin_factory(&[FactoryEntry::new::<TestPlugin<2>>()]) as *mut IPluginFactory;
+    let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
+    let factory_2 = factory.cast::<IPluginFactory2>().unwrap();
+
+    let [a, b, c, d] = CLASS_IDS[2];
+    let processor_cid = uid(a, b, c, d);
+    let [a, b, c, d] = CONTROLLER_ID;
+    let controller_cid = uid(a, b, c, d);
+
+    // The processor and the controller are listed as separate classes.
+    assert_eq!(unsafe { factory.countClasses() }, 2);
+    let mut class_info: PClassInfo2 = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { factory_2.getClassInfo2(1, &mut class_info) },
+        kResultOk
+    );
+    assert_eq!(class_info.cid, controller_cid);
+    assert_eq!(
+        str_from_chars(&class_info.category).unwrap(),
+        "Component Controller Class"
+    );
+
+    let mut obj = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            processor_cid.as_ptr(),
+            IComponent::IID.as_ptr() as FIDString,
+            &mut obj,
+        )
+    };
+    assert_eq!(result, kResultOk);
+    let processor = unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap();
+    assert!(processor.cast::<IEditController>().is_none());
+
+    let mut class_id: TUID = [0; 16];
+    assert_eq!(
+        unsafe { processor.getControllerClassId(&mut class_id) },
+        kResultOk
+    );
+    assert_eq!(class_id, controller_cid);
+
+    let mut obj = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            controller_cid.as_ptr(),
+            IEditController::IID.as_ptr() as FIDString,
+            &mut obj,
+        )
+    };
+    assert_eq!(result, kResultOk);
+    let controller = unsafe { ComPtr::from_raw(obj as *mut IEditController) }.unwrap();
+    assert!(controller.cast::<IComponent>().is_none());
+
+    let host_application = ComWrapper::new(TestHostApplication);
+    let context = host_application.to_com_ptr::<IHostApplication>().unwrap();
+    let context = context.as_ptr() as *mut FUnknown;
+    assert_eq!(unsafe { processor.initialize(context) }, kResultOk);
+    assert_eq!(unsafe { controller.initialize(context) }, kResultOk);
+
+    let handler = ComWrapper::new(TestHandler::default());
+    let handler_ptr = handler.to_com_ptr::<IComponentHandler>().unwrap();
+    unsafe { controller.setComponentHandler(handler_ptr.as_ptr()) };
+
+    let processor_point = processor.cast::<IConnectionPoint>().unwrap();
+    let controller_point = controller.cast::<IConnectionPoint>().unwrap();
+    unsafe {
+        processor_point.connect(controller_point.as_ptr());
+        controller_point.connect(processor_point.as_ptr());
+    }
+
+    // The host passes the processor's state on to the controller.
+    let mut state = State::new(IDS[2], VERSION);
+    state.set_param(PARAM, 0.25);
+    let mut bytes = Vec::new();
+    state.write(&mut bytes).unwrap();
+    assert!(load_state(&processor, &bytes));
+
+    let stream = ComWrapper::new(Stream {
+        data: RefCell::new(save_state(&processor).unwrap()),
+        pos: Cell::new(0),
+    });
+    let stream_ptr = stream.to_com_ptr::<IBStream>().unwrap();
+    let result = unsafe { controller.setComponentState(stream_ptr.as_ptr()) };
+    assert_eq!(result, kResultOk);
+    assert_eq!(unsafe { controller.getParamNormalized(PARAM) }, 0.25);
+    assert_eq!(handler.restarts.get(), 1);
+
+    // A program change on the controller is sent to the processor.
+    let count = unsafe { controller.getParameterCount() };
+    let mut info: ParameterInfo = unsafe { std::mem::zeroed() };
+    unsafe { controller.getParameterInfo(count - 1, &mut info) };
+    let result = unsafe { controller.setParamNormalized(info.id, 1.0) };
+    assert_eq!(result, kResultOk);
+    assert_eq!(handler.restarts.get(), 2);
+
+    let saved = State::read(&mut &save_state(&processor).unwrap()[..]).unwrap();
+    assert_eq!(saved.params, [(PARAM, PRESETS[1].2)]);
+
+    unsafe {
+        processor_point.disconnect(controller_point.as_ptr());
+        controller_point.disconnect(processor_point.as_ptr());
+        assert_eq!(processor.terminate(), kResultOk);
+        assert_eq!(controller.terminate(), kResultOk);
+    }
+}
+
+struct TestParamQueue {
+    id: ParamID,
+    points: Vec<(int32, ParamValue)>,
+}
+
+impl Class for TestParamQueue {
+    type Interfaces = (IParamValueQueue,);
+}
+
+#[allow(non_snake_case)]
+impl IParamValueQueueTrait for TestParamQueue {
+    unsafe fn getParameterId(&self) -> ParamID {
+        self.id
+    }
+
+    unsafe fn getPointCount(&self) -> int32 {
+        self.points.len() as int32
+    }
+
+    unsafe fn getPoint(
+        &self,
+        index: int32,
+        sampleOffset: *mut int32,
+        value: *mut ParamValue,
+    ) -> tresult {
+        let Some(&(offset, point)) = self.points.get(index as usize) else {
+            return kInvalidArgument;
+        };
+
+        *sampleOffset = offset;
+        *value = point;
+        kResultOk
+    }
+
+    unsafe fn addPoint(
+        &self,
+        _sampleOffset: int32,
+        _value: ParamValue,
+        _index: *mut int32,
+    ) -> tresult {
+        kNotImplemented
+    }
+}
+
+struct TestParamChanges {
+    queues: Vec<ComWrapper<TestParamQueue>>,
+}
+
+impl Class for TestParamChanges {
+    type Interfaces = (IParameterChanges,);
+}
+
+#[allow(non_snake_case)]
+impl IParameterChangesTrait for TestParamChanges {
+    unsafe fn getParameterCount(&self) -> int32 {
+        self.queues.len() as int32
+    }
+
+    unsafe fn getParameterData(&self, index: int32) -> *mut IParamValueQueue {
+        match self.queues.get(index as usize) {
+            Some(queue) => queue.as_com_ref::<IParamValueQueue>().unwrap().as_ptr(),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn addParameterData(
+        &self,
+        _id: *const ParamID,
+        _index: *mut int32,
+    ) -> *mut IParamValueQueue {
+        ptr::null_mut()
+    }
+}
+
+#[test]
+fn param_ramps() {
+    let ptr = get_plugin_factory(&[FactoryEntry::new::<TestPlugin<1>>()]) as *mut IPluginFactory;
+    let factory = unsafe { ComPtr::from_raw(ptr) }.unwrap();
+
+    let [a, b, c, d] = CLASS_IDS[1];
+    let mut obj = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            uid(a, b, c, d).as_ptr(),
+            IComponent::IID.as_ptr() as FIDString,
+            &mut obj,
+        )
+    };
+    assert_eq!(result, kResultOk);
+    let component = unsafe { ComPtr::from_raw(obj as *mut IComponent) }.unwrap();
+    let processor = component.cast::<IAudioProcessor>().unwrap();
+
+    let mut setup = ProcessSetup {
+        processMode: ProcessModes_::kRealtime as int32,
+        symbolicSampleSize: SymbolicSampleSizes_::kSample32 as int32,
+        maxSamplesPerBlock: 64,
+        sampleRate: 44100.0,
+    };
+    assert_eq!(unsafe { processor.setupProcessing(&mut setup) }, kResultOk);
+    assert_eq!(unsafe { component.setActive(1) }, kResultOk);
+
+    let changes = ComWrapper::new(TestParamChanges {
+        queues: vec![
+            ComWrapper::new(TestParamQueue {
+                id: PARAM,
+                points: vec![(0, 0.0), (63, 1.0)],
+            }),
+            ComWrapper::new(TestParamQueue {
+                id: OTHER_PARAM,
+                points: vec![(16, 0.5), (32, 0.25)],
+            }),
+        ],
+    });
+    let changes_ptr = changes.to_com_ptr::<IParameterChanges>().unwrap();
+
+    let mut data: ProcessData = unsafe { std::mem::zeroed() };
+    data.processMode = ProcessModes_::kRealtime as int32;
+    data.symbolicSampleSize = SymbolicSampleSizes_::kSample32 as int32;
+    data.numSamples = 64;
+    data.inputParameterChanges = changes_ptr.as_ptr();
+
+    ENGINE_EVENTS.lock().unwrap().clear();
+    assert_eq!(unsafe { processor.process(&mut data) }, kResultOk);
+
+    // Points from both queues are merged in time order, and each ramp ends in a change.
+    assert_eq!(
+        *ENGINE_EVENTS.lock().unwrap(),
+        [
+            (0, PARAM, 0.0, None),
+            (0, PARAM, 1.0, Some(63)),
+            (0, OTHER_PARAM, 0.5, Some(16)),
+            (16, OTHER_PARAM, 0.5, None),
+            (16, OTHER_PARAM, 0.25, Some(16)),
+            (32, OTHER_PARAM, 0.25, None),
+            (63, PARAM, 1.0, None),
+        ]
+    );
+
+    assert_eq!(unsafe { component.setActive(0) }, kResultOk);
+}
+
+#[test]
+fn vstpreset() {
+    let plugin = TestPlugin::<0> {
+        value: 0.25,
+        data: vec![1, 2, 3],
+    };
+    let mut bytes = Vec::new();
+    write_vstpreset(&plugin, &mut bytes).unwrap();
+
+    assert_eq!(&bytes[0..4], b"VST3");
+    assert_eq!(&bytes[8..40], b"11111111222222223333333344444444");
+
+    let mut loaded = TestPlugin::<0> {
+        value: 0.5,
+        data: Vec::new(),
+    };
+    read_vstpreset(&mut loaded, &mut &bytes[..]).unwrap();
+    assert_eq!(loaded.value, 0.25);
+    assert_eq!(loaded.data, [1, 2, 3]);
+
+    // A preset for a different class id is rejected without touching the plugin.
+    let mut other = TestPlugin::<1> {
+        value: 0.5,
+        data: Vec::new(),
+    };
+    assert!(read_vstpreset(&mut other, &mut &bytes[..]).is_err());
+    assert_eq!(other.value, 0.5);
+
+    assert!(read_vstpreset(&mut loaded, &mut &bytes[..bytes.len() - 1]).is_err());
 }
@@ -1,30 +1,130 @@
-use std::cell::{RefCell, UnsafeCell};
+use std::cell::{Cell, UnsafeCell};
 use std::ffi::{c_void, CStr};
-use std::sync::Arc;
+use std::ptr;
+use std::sync::{Arc, Weak};
 
-use vst3::Steinberg::Vst::{IComponentHandler, IComponentHandlerTrait};
-use vst3::{Class, ComPtr, Steinberg::*};
+use vst3::Steinberg::Vst::{
+    IComponentHandler3, IComponentHandler3Trait, IComponentHandlerTrait, IContextMenu,
+    IContextMenuItem, IContextMenuItem_, IContextMenuTarget, IContextMenuTargetTrait,
+    IContextMenuTrait,
+};
+use vst3::{Class, ComPtr, ComWrapper, Steinberg::*};
 
 use super::component::MainThreadState;
+use super::host::Vst3Host;
+use super::util::copy_wstring;
+use crate::logging;
 use crate::params::{ParamId, ParamValue};
 use crate::plugin::Plugin;
-use crate::view::{ParentWindow, RawParent, View, ViewHost, ViewHostInner};
+use crate::thread_check;
+use crate::view::{MenuItem, ParentWindow, RawParent, View, ViewHost, ViewHostInner};
+
+unsafe fn add_menu_items(
+    menu: &ComPtr<IContextMenu>,
+    items: &[MenuItem],
+    target: *mut IContextMenuTarget,
+) {
+    for item in items {
+        let mut menu_item = IContextMenuItem {
+            name: [0; 128],
+            tag: 0,
+            flags: 0,
+        };
+
+        match item {
+            MenuItem::Entry {
+                label,
+                enabled,
+                action,
+            } => {
+                copy_wstring(label, &mut menu_item.name);
+                menu_item.tag = *action as int32;
+                if !enabled {
+                    menu_item.flags |= IContextMenuItem_::Flags_::kIsDisabled as int32;
+                }
+            }
+            MenuItem::CheckEntry {
+                label,
+                enabled,
+                checked,
+                action,
+            } => {
+                copy_wstring(label, &mut menu_item.name);
+                menu_item.tag = *action as int32;
+                if !enabled {
+                    menu_item.flags |= IContextMenuItem_::Flags_::kIsDisabled as int32;
+                }
+                if *checked {
+                    menu_item.flags |= IContextMenuItem_::Flags_::kIsChecked as int32;
+                }
+            }
+            MenuItem::Separator => {
+                menu_item.flags = IContextMenuItem_::Flags_::kIsSeparator as int32;
+            }
+            MenuItem::Submenu { label, items, .. } => {
+                copy_wstring(label, &mut menu_item.name);
+                menu_item.flags = IContextMenuItem_::Flags_::kIsGroupStart as int32;
+                menu.addItem(&menu_item, target);
+
+                add_menu_items(menu, items, target);
+
+                menu_item.name = [0; 128];
+                menu_item.flags = IContextMenuItem_::Flags_::kIsGroupEnd as int32;
+            }
+        }
 
-pub struct Vst3ViewHost {
-    pub handler: RefCell<Option<ComPtr<IComponentHandler>>>,
+        menu.addItem(&menu_item, target);
+    }
+}
+
+struct MenuTarget<P: Plugin> {
+    main_thread_state: Weak<UnsafeCell<MainThreadState<P>>>,
+    id: ParamId,
 }
 
-impl Vst3ViewHost {
-    pub fn new() -> Vst3ViewHost {
+impl<P: Plugin> Class for MenuTarget<P> {
+    type Interfaces = (IContextMenuTarget,);
+}
+
+impl<P: Plugin> IContextMenuTargetTrait for MenuTarget<P> {
+    unsafe fn executeMenuItem(&self, tag: int32) -> tresult {
+        if let Some(main_thread_state) = self.main_thread_state.upgrade() {
+            let _log = logging::enter_instance(main_thread_state.get() as usize);
+            let main_thread_state = &mut *main_thread_state.get();
+            main_thread_state.plugin.param_context_menu_action(self.id, tag as u32);
+        }
+
+        kResultOk
+    }
+}
+
+pub struct Vst3ViewHost<P: Plugin> {
+    host: Arc<Vst3Host>,
+    main_thread_state: Weak<UnsafeCell<MainThreadState<P>>>,
+    // The most recently created editor, which host context menus are attached to.
+    view: Cell<*mut IPlugView>,
+}
+
+impl<P: Plugin> Vst3ViewHost<P> {
+    pub fn new(
+        host: Arc<Vst3Host>,
+        main_thread_state: Weak<UnsafeCell<MainThreadState<P>>>,
+    ) -> Vst3ViewHost<P> {
         Vst3ViewHost {
-            handler: RefCell::new(None),
+            host,
+            main_thread_state,
+            view: Cell::new(ptr::null_mut()),
         }
     }
+
+    pub fn set_view(&self, view: *mut IPlugView) {
+        self.view.set(view);
+    }
 }
 
-impl ViewHostInner for Vst3ViewHost {
+impl<P: Plugin> ViewHostInner for Vst3ViewHost<P> {
     fn begin_gesture(&self, id: ParamId) {
-        let handler = self.handler.borrow();
+        let handler = self.host.handler.borrow();
         if let Some(handler) = &*handler {
             unsafe {
                 handler.beginEdit(id);
@@ -33,7 +133,7 @@ impl ViewHostInner for Vst3ViewHost {
     }
 
     fn end_gesture(&self, id: ParamId) {
-        let handler = self.handler.borrow();
+        let handler = self.host.handler.borrow();
         if let Some(handler) = &*handler {
             unsafe {
                 handler.endEdit(id);
@@ -42,13 +142,41 @@ impl ViewHostInner for Vst3ViewHost {
     }
 
     fn set_param(&self, id: ParamId, value: ParamValue) {
-        let handler = self.handler.borrow();
+        let handler = self.host.handler.borrow();
         if let Some(handler) = &*handler {
             unsafe {
                 handler.performEdit(id, value);
             }
         }
     }
+
+    fn show_param_context_menu(&self, id: ParamId, x: f64, y: f64) -> bool {
+        let handler = self.host.handler.borrow();
+        let Some(handler) = handler.as_ref().and_then(|h| h.cast::<IComponentHandler3>()) else {
+            return false;
+        };
+
+        unsafe {
+            let menu = handler.createContextMenu(self.view.get(), &id);
+            let Some(menu) = ComPtr::from_raw(menu) else {
+                return false;
+            };
+
+            if let Some(main_thread_state) = self.main_thread_state.upgrade() {
+                let items = (*main_thread_state.get()).plugin.param_context_menu(id);
+                if !items.is_empty() {
+                    let target = ComWrapper::new(MenuTarget {
+                        main_thread_state: Arc::downgrade(&main_thread_state),
+                        id,
+                    });
+                    let target = target.to_com_ptr::<IContextMenuTarget>().unwrap();
+                    add_menu_items(&menu, &items, target.as_ptr());
+                }
+            }
+
+            menu.popup(x.round() as UCoord, y.round() as UCoord) == kResultOk
+        }
+    }
 }
 
 pub struct PlugView<P: Plugin> {
@@ -101,6 +229,8 @@ impl<P: Plugin> IPlugViewTrait for PlugView<P> {
         #[cfg(target_os = "linux")]
         let raw_parent = RawParent::X11(parent as std::ffi::c_ulong);
 
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
 
         let host = ViewHost::from_inner(main_thread_state.view_host.clone());
@@ -112,9 +242,12 @@ impl<P: Plugin> IPlugViewTrait for PlugView<P> {
     }
 
     unsafe fn removed(&self) -> tresult {
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &mut *self.main_thread_state.get();
 
         main_thread_state.view = None;
+        main_thread_state.view_host.set_view(ptr::null_mut());
 
         kResultOk
     }
@@ -136,6 +269,8 @@ impl<P: Plugin> IPlugViewTrait for PlugView<P> {
             return kResultFalse;
         }
 
+        let _log = logging::enter_instance(self.main_thread_state.get() as usize);
+        thread_check::check_main_thread(|| None);
         let main_thread_state = &*self.main_thread_state.get();
 
         if let Some(view) = &main_thread_state.view {
@@ -1,14 +1,55 @@
 use std::sync::Arc;
 
-pub trait HostInner {}
+use crate::params::{ParamId, ParamValue};
+
+pub trait HostInner {
+    fn set_param(&self, id: ParamId, value: ParamValue);
+    fn voice_info_changed(&self);
+    fn remote_controls_changed(&self);
+    fn audio_ports_changed(&self);
+}
 
 #[derive(Clone)]
 pub struct Host {
-    _inner: Arc<dyn HostInner + Send + Sync>,
+    inner: Arc<dyn HostInner + Send + Sync>,
 }
 
 impl Host {
     pub fn from_inner(inner: Arc<dyn HostInner + Send + Sync>) -> Host {
-        Host { _inner: inner }
+        Host { inner }
+    }
+
+    /// Changes the value of a parameter on behalf of the plugin, e.g. for randomization, and
+    /// reports the change to the host. The new value reaches the engine and
+    /// [`Plugin::set_param`](crate::plugin::Plugin::set_param) asynchronously.
+    ///
+    /// Must be called from the main thread.
+    pub fn set_param(&self, id: ParamId, value: ParamValue) {
+        self.inner.set_param(id, value);
+    }
+
+    /// Tells the host that the value returned by
+    /// [`Plugin::voice_info`](crate::plugin::Plugin::voice_info) has changed.
+    ///
+    /// Must be called from the main thread.
+    pub fn voice_info_changed(&self) {
+        self.inner.voice_info_changed();
+    }
+
+    /// Tells the host that the pages returned by
+    /// [`Plugin::remote_controls`](crate::plugin::Plugin::remote_controls) have changed.
+    ///
+    /// Must be called from the main thread.
+    pub fn remote_controls_changed(&self) {
+        self.inner.remote_controls_changed();
+    }
+
+    /// Tells the host that the plugin's audio ports have changed, e.g. their names or channel
+    /// counts, so that it can query them again. If the plugin is active, the host may need to
+    /// deactivate it first, so the change can take effect later.
+    ///
+    /// Must be called from the main thread.
+    pub fn audio_ports_changed(&self) {
+        self.inner.audio_ports_changed();
     }
 }
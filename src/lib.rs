@@ -6,9 +6,22 @@ pub mod engine;
 pub mod events;
 pub mod format;
 pub mod host;
+pub mod logging;
+pub mod midi;
 pub mod params;
 pub mod plugin;
+pub mod preset;
+pub mod state;
+pub mod track;
 pub mod view;
 
+#[cfg(feature = "dsp")]
+pub use coupler_dsp as dsp;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
 mod sync;
+mod thread_check;
 mod util;
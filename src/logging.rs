@@ -0,0 +1,359 @@
+use std::cell::Cell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::sync::queue::Queue;
+use crate::thread_check::{self, ThreadRole};
+
+const QUEUE_SIZE: usize = 256;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct LogConfig {
+    pub level: LevelFilter,
+    /// Name of a file in the user's config directory to write messages to when the host doesn't
+    /// provide a log. If this is `None`, messages are written to stderr instead.
+    pub file_name: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            level: LevelFilter::Info,
+            file_name: None,
+        }
+    }
+}
+
+/// Installs a [`log`] backend which routes messages to the host's log where it has one.
+///
+/// Messages are written out by a background thread. Messages without format arguments, such as
+/// `info!("starting")`, are queued as-is, which never blocks or allocates. Other messages are
+/// formatted on the calling thread, except on the audio thread, where running the arguments'
+/// `Display` and `Debug` impls could block: from
+/// [`Engine::process`](crate::engine::Engine::process), messages with arguments are dropped and
+/// only counted. Messages are also dropped if the queue fills up faster than it can be written out.
+///
+/// Messages logged while the host is calling into a plugin instance go to that instance's host
+/// log. Other messages go to the file or stderr.
+///
+/// This can safely be called more than once, e.g. from [`Plugin::new`](crate::plugin::Plugin::new).
+/// The most recent configuration takes effect. Does nothing if another logger is already
+/// installed.
+pub fn init(config: LogConfig) {
+    if log::set_logger(&LOGGER).is_err() && !INSTALLED.load(Ordering::Relaxed) {
+        return;
+    }
+    INSTALLED.store(true, Ordering::Relaxed);
+
+    log::set_max_level(config.level);
+
+    let mut state = state();
+    if state.file_name != config.file_name {
+        state.file_name = config.file_name;
+        state.file = None;
+    }
+
+    if state.thread.is_none() {
+        RUNNING.store(true, Ordering::Release);
+        state.thread = thread::Builder::new()
+            .name("coupler-log".to_string())
+            .spawn(|| {
+                while RUNNING.load(Ordering::Acquire) {
+                    flush();
+                    thread::sleep(FLUSH_INTERVAL);
+                }
+            })
+            .ok();
+    }
+}
+
+/// Stops the background thread after writing out any queued messages. Called when the plugin
+/// binary is about to be unloaded.
+#[doc(hidden)]
+pub fn shutdown() {
+    let thread = state().thread.take();
+    if let Some(thread) = thread {
+        RUNNING.store(false, Ordering::Release);
+        let _ = thread.join();
+    }
+
+    flush();
+}
+
+pub(crate) trait HostLog: Send {
+    fn log(&self, level: Level, message: &str);
+}
+
+pub(crate) fn add_host_log(id: usize, log: Box<dyn HostLog>) {
+    state().host_logs.push((id, log));
+}
+
+pub(crate) fn remove_host_log(id: usize) {
+    state().host_logs.retain(|(log_id, _)| *log_id != id);
+}
+
+thread_local! {
+    static INSTANCE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Tags messages logged on the current thread with the instance `id` until the returned guard is
+/// dropped, so that they are written to the host log registered with [`add_host_log`].
+pub(crate) fn enter_instance(id: usize) -> InstanceGuard {
+    InstanceGuard {
+        prev: INSTANCE.replace(Some(id)),
+    }
+}
+
+pub(crate) struct InstanceGuard {
+    prev: Option<usize>,
+}
+
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        INSTANCE.set(self.prev);
+    }
+}
+
+enum Text {
+    Static(&'static str),
+    Formatted(String),
+}
+
+struct Message {
+    level: Level,
+    instance: Option<usize>,
+    text: Text,
+}
+
+impl Message {
+    fn as_str(&self) -> &str {
+        match &self.text {
+            Text::Static(text) => text,
+            Text::Formatted(text) => text,
+        }
+    }
+}
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static QUEUE: Queue<Message, QUEUE_SIZE> = Queue::new();
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED_AUDIO_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let text = if let Some(text) = record.args().as_str() {
+            Text::Static(text)
+        } else if thread_check::role() == Some(ThreadRole::Audio) {
+            DROPPED_AUDIO_THREAD.fetch_add(1, Ordering::Relaxed);
+            return;
+        } else {
+            Text::Formatted(record.args().to_string())
+        };
+
+        let message = Message {
+            level: record.level(),
+            // Thread-locals are unavailable while the thread is being torn down.
+            instance: INSTANCE.try_with(Cell::get).ok().flatten(),
+            text,
+        };
+
+        if QUEUE.push(message).is_err() {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+struct State {
+    thread: Option<JoinHandle<()>>,
+    host_logs: Vec<(usize, Box<dyn HostLog>)>,
+    file_name: Option<String>,
+    file: Option<File>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    thread: None,
+    host_logs: Vec::new(),
+    file_name: None,
+    file: None,
+});
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn flush() {
+    let mut state = state();
+
+    // Only one thread drains the queue at a time, since the state lock is held.
+    while let Some(message) = unsafe { QUEUE.pop() } {
+        state.write(message.instance, message.level, message.as_str());
+    }
+
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let message = format!("{dropped} log messages were dropped");
+        state.write(None, Level::Warn, &message);
+    }
+
+    let dropped = DROPPED_AUDIO_THREAD.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let message = format!(
+            "{dropped} log messages with format arguments were dropped on the audio thread"
+        );
+        state.write(None, Level::Warn, &message);
+    }
+}
+
+impl State {
+    fn write(&mut self, instance: Option<usize>, level: Level, message: &str) {
+        let host_log =
+            instance.and_then(|id| self.host_logs.iter().find(|(log_id, _)| *log_id == id));
+        if let Some((_, host_log)) = host_log {
+            host_log.log(level, message);
+            return;
+        }
+
+        if self.file.is_none() {
+            if let Some(file_name) = &self.file_name {
+                self.file = open_log_file(file_name).ok();
+            }
+        }
+
+        let line = format!("[{level}] {message}\n");
+        if let Some(file) = &mut self.file {
+            if file.write_all(line.as_bytes()).is_ok() {
+                return;
+            }
+        }
+
+        let _ = io::stderr().write_all(line.as_bytes());
+    }
+}
+
+fn open_log_file(file_name: &str) -> io::Result<File> {
+    let dir = config_dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+
+    OpenOptions::new().create(true).append(true).open(dir.join(file_name))
+}
+
+#[cfg(target_os = "windows")]
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
+
+#[cfg(target_os = "macos")]
+fn config_dir() -> Option<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(home.join("Library").join("Application Support"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir));
+    }
+
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(home.join(".config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    struct TestLog(Arc<Mutex<Vec<String>>>);
+
+    impl HostLog for TestLog {
+        fn log(&self, _level: Level, message: &str) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn route_to_instance() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        add_host_log(1, Box::new(TestLog(first.clone())));
+        add_host_log(2, Box::new(TestLog(second.clone())));
+
+        log::set_max_level(LevelFilter::Info);
+        let log = |text: &str| {
+            LOGGER.log(&Record::builder().level(Level::Info).args(format_args!("{text}")).build());
+        };
+
+        let guard = enter_instance(2);
+        log("second");
+        drop(guard);
+        let guard = enter_instance(1);
+        log("first");
+        drop(guard);
+
+        // Messages from an instance without a host log don't go to another instance's log.
+        let guard = enter_instance(3);
+        log("no host log");
+        drop(guard);
+
+        // Untagged messages go to the file or stderr rather than an arbitrary instance's log.
+        log("untagged");
+        flush();
+
+        remove_host_log(1);
+        remove_host_log(2);
+
+        assert_eq!(*first.lock().unwrap(), ["first"]);
+        assert_eq!(*second.lock().unwrap(), ["second"]);
+    }
+
+    #[test]
+    fn audio_thread() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        add_host_log(4, Box::new(TestLog(messages.clone())));
+
+        log::set_max_level(LevelFilter::Info);
+        thread::spawn(|| {
+            let _instance = enter_instance(4);
+            let _audio_thread = thread_check::enter_audio_thread();
+
+            // Only messages which don't need formatting are queued.
+            let value = 0.5;
+            LOGGER.log(&Record::builder().level(Level::Info).args(format_args!("static")).build());
+            LOGGER.log(
+                &Record::builder()
+                    .level(Level::Info)
+                    .args(format_args!("value = {value}"))
+                    .build(),
+            );
+        })
+        .join()
+        .unwrap();
+        flush();
+
+        remove_host_log(4);
+
+        assert_eq!(*messages.lock().unwrap(), ["static"]);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::params::ParamId;
+
+/// A MIDI controller which a host can route to a parameter.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MidiController {
+    /// A control change message, with a controller number from 0 to 127.
+    ControlChange(u8),
+    /// Channel pressure (aftertouch).
+    ChannelPressure,
+    PitchBend,
+}
+
+/// Routes a MIDI controller to a parameter.
+///
+/// VST3 hosts don't pass MIDI controller messages to plugins. Instead, they convert them to
+/// parameter changes according to these mappings, which reach the engine as
+/// [`Data::ParamChange`](crate::events::Data::ParamChange) events.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MidiMapping {
+    pub controller: MidiController,
+    /// Restricts the mapping to a single channel from 0 to 15. `None` maps every channel.
+    pub channel: Option<u8>,
+    pub param: ParamId,
+}
+
+impl MidiMapping {
+    pub(crate) fn matches(&self, controller: MidiController, channel: u8) -> bool {
+        self.controller == controller && (self.channel.is_none() || self.channel == Some(channel))
+    }
+}
@@ -5,8 +5,14 @@ use std::str::FromStr;
 pub use coupler_derive::{Enum, Params};
 
 mod range;
+#[cfg(feature = "serde")]
+pub mod serialize;
+mod smooth;
+#[cfg(test)]
+pub(crate) mod test_params;
 
 pub use range::{Encode, Log, Range};
+pub use smooth::{SmoothParams, SmoothStyle, Smoother, Smoothers, DEFAULT_TIME_MS};
 
 pub type ParamId = u32;
 pub type ParamValue = f64;
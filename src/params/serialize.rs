@@ -0,0 +1,71 @@
+use std::fmt::{self, Formatter};
+use std::marker::PhantomData;
+
+use serde::de::{Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+
+use super::{ParamId, ParamValue, Params};
+
+/// Serializes `params` as a map from parameter ids to normalized values. Usable as
+/// `#[serde(with = "coupler::params::serialize")]`, and used by `#[derive(Params)]` for structs
+/// marked with `#[params(serde)]`.
+pub fn serialize<P: Params, S: Serializer>(params: &P, serializer: S) -> Result<S::Ok, S::Error> {
+    let infos = P::params();
+
+    let mut map = serializer.serialize_map(Some(infos.len()))?;
+    for info in &infos {
+        map.serialize_entry(&info.id, &params.get_param(info.id))?;
+    }
+    map.end()
+}
+
+/// Values for unknown ids are ignored, and parameters missing from the input keep their defaults.
+pub fn deserialize<'de, P, D>(deserializer: D) -> Result<P, D::Error>
+where
+    P: Params + Default,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(ParamsVisitor(PhantomData))
+}
+
+struct ParamsVisitor<P>(PhantomData<P>);
+
+impl<'de, P: Params + Default> Visitor<'de> for ParamsVisitor<P> {
+    type Value = P;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a map from parameter ids to values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<P, A::Error> {
+        let infos = P::params();
+
+        let mut params = P::default();
+        while let Some(id) = map.next_key::<ParamId>()? {
+            if infos.iter().any(|info| info.id == id) {
+                let value = map.next_value::<ParamValue>()?;
+                params.set_param(id, value.clamp(0.0, 1.0));
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{Error, MapDeserializer};
+
+    use super::*;
+    use crate::params::test_params::TestParams;
+
+    #[test]
+    fn unknown_and_missing() {
+        let input = MapDeserializer::<_, Error>::new([(1u32, 0.5f64), (7, 1.0)].into_iter());
+        let params: TestParams = deserialize(input).unwrap();
+        assert_eq!(params.a, 0.0);
+        assert_eq!(params.b, 0.5);
+    }
+}
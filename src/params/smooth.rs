@@ -0,0 +1,346 @@
+use super::{ParamId, ParamValue, Params};
+use crate::events::{Data, Event, Events};
+
+pub const DEFAULT_TIME_MS: f64 = 10.0;
+
+// Distance from the target at which an exponential smoother snaps to it.
+const EPSILON: f32 = 1e-5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SmoothStyle {
+    /// Moves toward the target at a constant rate, reaching it after the smoothing time.
+    Linear,
+    /// One-pole lowpass, where the smoothing time is the time constant.
+    Exponential,
+    /// Moves toward the target at a constant ratio, for gains and frequencies. Falls back to a
+    /// linear ramp if either endpoint is not positive.
+    Logarithmic,
+}
+
+#[derive(Clone, Debug)]
+pub struct Smoother {
+    style: SmoothStyle,
+    time_ms: f64,
+    sample_rate: f64,
+    coeff: f32,
+    current: f32,
+    target: f32,
+    step: f32,
+    logarithmic: bool,
+    remaining: u32,
+}
+
+impl Smoother {
+    pub fn new(style: SmoothStyle, time_ms: f64, sample_rate: f64, value: f32) -> Smoother {
+        let mut smoother = Smoother {
+            style,
+            time_ms,
+            sample_rate,
+            coeff: 1.0,
+            current: value,
+            target: value,
+            step: 0.0,
+            logarithmic: false,
+            remaining: 0,
+        };
+        smoother.update_coeff();
+
+        smoother
+    }
+
+    pub fn style(&self) -> SmoothStyle {
+        self.style
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.update_coeff();
+    }
+
+    pub fn set_time(&mut self, time_ms: f64) {
+        self.time_ms = time_ms;
+        self.update_coeff();
+    }
+
+    fn time_samples(&self) -> f64 {
+        (self.time_ms * 0.001 * self.sample_rate).max(0.0)
+    }
+
+    fn update_coeff(&mut self) {
+        let time = self.time_samples();
+        self.coeff = if time < 1.0 {
+            1.0
+        } else {
+            (1.0 - (-1.0 / time).exp()) as f32
+        };
+    }
+
+    /// Jumps to `value` immediately.
+    pub fn reset(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        match self.style {
+            SmoothStyle::Exponential => {
+                self.target = target;
+                self.remaining = 0;
+            }
+            SmoothStyle::Linear | SmoothStyle::Logarithmic => {
+                self.ramp(target, self.time_samples().round() as u32);
+            }
+        }
+    }
+
+    /// Moves to `target` over exactly `len` samples, regardless of the smoothing style and time.
+    pub fn ramp(&mut self, target: f32, len: u32) {
+        self.target = target;
+        self.remaining = len;
+
+        if len == 0 {
+            self.current = target;
+            return;
+        }
+
+        self.logarithmic =
+            self.style == SmoothStyle::Logarithmic && self.current > 0.0 && target > 0.0;
+        self.step = if self.logarithmic {
+            (target / self.current).powf(1.0 / len as f32)
+        } else {
+            (target - self.current) / len as f32
+        };
+    }
+
+    #[inline]
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    #[inline]
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    #[inline]
+    pub fn is_smoothing(&self) -> bool {
+        self.current != self.target
+    }
+
+    #[inline]
+    pub fn process(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.current = self.target;
+            } else if self.logarithmic {
+                self.current *= self.step;
+            } else {
+                self.current += self.step;
+            }
+        } else if self.current != self.target {
+            // Only exponential smoothers approach their target without a ramp length.
+            let next = self.current + self.coeff * (self.target - self.current);
+            if next == self.current || (self.target - next).abs() < EPSILON {
+                self.current = self.target;
+            } else {
+                self.current = next;
+            }
+        }
+
+        self.current
+    }
+
+    #[inline]
+    pub fn fill(&mut self, output: &mut [f32]) {
+        for sample in output {
+            *sample = self.process();
+        }
+    }
+}
+
+pub trait SmoothParams: Params {
+    /// Creates smoothers for all smoothed parameters, starting at their current values.
+    fn smoothers(&self, sample_rate: f64) -> Smoothers;
+
+    /// Sets the target of the smoother for `id` (if any) to the current value of the parameter.
+    fn update_smoother(&self, id: ParamId, smoothers: &mut Smoothers);
+
+    /// Ramps the smoother for `id` (if any) to the plain value corresponding to the normalized
+    /// `value` over `len` samples.
+    fn ramp_smoother(&self, id: ParamId, value: ParamValue, len: u32, smoothers: &mut Smoothers);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Smoothers {
+    smoothers: Vec<(ParamId, Smoother)>,
+}
+
+impl Smoothers {
+    pub fn new() -> Smoothers {
+        Smoothers {
+            smoothers: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, id: ParamId, smoother: Smoother) {
+        if let Some(existing) = self.get_mut(id) {
+            *existing = smoother;
+        } else {
+            self.smoothers.push((id, smoother));
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, id: ParamId) -> Option<&Smoother> {
+        self.smoothers.iter().find(|(i, _)| *i == id).map(|(_, smoother)| smoother)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, id: ParamId) -> Option<&mut Smoother> {
+        self.smoothers.iter_mut().find(|(i, _)| *i == id).map(|(_, smoother)| smoother)
+    }
+
+    /// Advances the smoother for `id` by one sample and returns its value.
+    ///
+    /// Panics if there is no smoother for `id`.
+    #[inline]
+    pub fn process(&mut self, id: ParamId) -> f32 {
+        self.get_mut(id).expect("no smoother for parameter").process()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        for (_, smoother) in &mut self.smoothers {
+            smoother.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Jumps all smoothers to their targets.
+    pub fn reset(&mut self) {
+        for (_, smoother) in &mut self.smoothers {
+            let target = smoother.target();
+            smoother.reset(target);
+        }
+    }
+
+    /// Applies a parameter change to `params` and retargets the corresponding smoother. Ramps
+    /// only affect the smoother, `params` is updated by the `ParamChange` at the end of the ramp.
+    pub fn handle_event<P: SmoothParams>(&mut self, params: &mut P, event: &Event) {
+        match event.data {
+            Data::ParamChange { id, value } => {
+                params.set_param(id, value);
+                params.update_smoother(id, self);
+            }
+            Data::ParamRamp { id, value, len } => params.ramp_smoother(id, value, len, self),
+            _ => {}
+        }
+    }
+
+    /// Retargets smoothers for parameter changes that have already been applied to `params`, e.g.
+    /// by [`process_blocks`](crate::buffers::iter::BlockIterator::process_blocks).
+    pub fn update<P: SmoothParams>(&mut self, params: &P, events: Events) {
+        for event in events {
+            match event.data {
+                Data::ParamChange { id, .. } => params.update_smoother(id, self),
+                Data::ParamRamp { id, value, len } => params.ramp_smoother(id, value, len, self),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::test_params::TestParams;
+
+    #[test]
+    fn linear() {
+        let mut smoother = Smoother::new(SmoothStyle::Linear, 1.0, 4000.0, 0.0);
+        smoother.set_target(1.0);
+
+        let mut output = [0.0; 5];
+        smoother.fill(&mut output);
+        assert_eq!(output, [0.25, 0.5, 0.75, 1.0, 1.0]);
+        assert!(!smoother.is_smoothing());
+    }
+
+    #[test]
+    fn exponential() {
+        let mut smoother = Smoother::new(SmoothStyle::Exponential, 1.0, 48000.0, 0.0);
+        smoother.set_target(1.0);
+
+        let mut prev = 0.0;
+        for _ in 0..48 {
+            let value = smoother.process();
+            assert!(value > prev && value < 1.0);
+            prev = value;
+        }
+
+        // After one time constant, the smoother should have covered 1 - 1/e of the distance.
+        assert!((prev - (1.0 - (-1.0f32).exp())).abs() < 1e-3);
+
+        for _ in 0..48000 {
+            smoother.process();
+        }
+        assert_eq!(smoother.value(), 1.0);
+    }
+
+    #[test]
+    fn logarithmic() {
+        let mut smoother = Smoother::new(SmoothStyle::Logarithmic, 1.0, 3000.0, 1.0);
+        smoother.set_target(8.0);
+
+        let mut output = [0.0; 3];
+        smoother.fill(&mut output);
+        for (value, expected) in output.iter().zip([2.0, 4.0, 8.0]) {
+            assert!((value - expected).abs() < 1e-5);
+        }
+        assert_eq!(output[2], 8.0);
+
+        // Falls back to a linear ramp when crossing zero.
+        smoother.reset(0.0);
+        smoother.set_target(3.0);
+        smoother.fill(&mut output);
+        assert_eq!(output, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn ramp() {
+        let mut smoother = Smoother::new(SmoothStyle::Exponential, 20.0, 48000.0, 1.0);
+        smoother.ramp(0.0, 2);
+        assert_eq!(smoother.process(), 0.5);
+        assert_eq!(smoother.process(), 0.0);
+        assert_eq!(smoother.process(), 0.0);
+    }
+
+    #[test]
+    fn param_ramp() {
+        let mut params = TestParams::default();
+        let mut smoothers = params.smoothers(48000.0);
+
+        let ramp = Event {
+            time: 0,
+            data: Data::ParamRamp {
+                id: 0,
+                value: 1.0,
+                len: 4,
+            },
+        };
+        smoothers.handle_event(&mut params, &ramp);
+        assert_eq!(params.a, 0.0);
+
+        let mut output = [0.0; 4];
+        smoothers.get_mut(0).unwrap().fill(&mut output);
+        assert_eq!(output, [0.25, 0.5, 0.75, 1.0]);
+
+        let change = Event {
+            time: 4,
+            data: Data::ParamChange { id: 0, value: 1.0 },
+        };
+        smoothers.handle_event(&mut params, &change);
+        assert_eq!(params.a, 1.0);
+        assert_eq!(smoothers.process(0), 1.0);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::{self, Formatter};
+
+use super::{
+    ParamId, ParamInfo, ParamValue, Params, SmoothParams, SmoothStyle, Smoother, Smoothers,
+};
+
+/// Two parameters with ids 0 and 1, stored as normalized values. Parameter 0 is smoothed.
+#[derive(Default)]
+pub(crate) struct TestParams {
+    pub a: ParamValue,
+    pub b: ParamValue,
+}
+
+impl Params for TestParams {
+    fn params() -> Vec<ParamInfo> {
+        [0, 1]
+            .into_iter()
+            .map(|id| ParamInfo {
+                id,
+                name: id.to_string(),
+                default: 0.0,
+                steps: None,
+            })
+            .collect()
+    }
+
+    fn set_param(&mut self, id: ParamId, value: ParamValue) {
+        match id {
+            0 => self.a = value,
+            1 => self.b = value,
+            _ => {}
+        }
+    }
+
+    fn get_param(&self, id: ParamId) -> ParamValue {
+        match id {
+            0 => self.a,
+            1 => self.b,
+            _ => 0.0,
+        }
+    }
+
+    fn parse_param(&self, _id: ParamId, _text: &str) -> Option<ParamValue> {
+        None
+    }
+
+    fn display_param(
+        &self,
+        _id: ParamId,
+        _value: ParamValue,
+        _fmt: &mut Formatter,
+    ) -> Result<(), fmt::Error> {
+        Ok(())
+    }
+}
+
+impl SmoothParams for TestParams {
+    fn smoothers(&self, sample_rate: f64) -> Smoothers {
+        let mut smoothers = Smoothers::new();
+        let smoother = Smoother::new(SmoothStyle::Exponential, 10.0, sample_rate, self.a as f32);
+        smoothers.insert(0, smoother);
+        smoothers
+    }
+
+    fn update_smoother(&self, id: ParamId, smoothers: &mut Smoothers) {
+        if let Some(smoother) = smoothers.get_mut(id) {
+            smoother.set_target(self.a as f32);
+        }
+    }
+
+    fn ramp_smoother(&self, id: ParamId, value: ParamValue, len: u32, smoothers: &mut Smoothers) {
+        if let Some(smoother) = smoothers.get_mut(id) {
+            smoother.ramp(value as f32, len);
+        }
+    }
+}
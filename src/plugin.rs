@@ -4,18 +4,64 @@ use std::io::{self, Read, Write};
 use crate::bus::{BusInfo, Layout};
 use crate::engine::{Config, Engine};
 use crate::host::Host;
+use crate::midi::MidiMapping;
 use crate::params::{ParamId, ParamInfo, ParamValue};
-use crate::view::{ParentWindow, View, ViewHost};
+use crate::preset::PresetInfo;
+use crate::state::{State, StateContext, LEGACY_VERSION};
+use crate::track::TrackInfo;
+use crate::view::{MenuItem, ParentWindow, View, ViewHost};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Category {
+    Instrument,
+    Effect,
+    NoteEffect,
+    Analyzer,
+}
+
+/// Tags describing what a plugin does, used by hosts to sort plugins into browser folders. Each
+/// format has its own vocabulary, so a tag may be dropped or merged with others for some formats.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Feature {
+    Synthesizer,
+    Sampler,
+    Drum,
+    Filter,
+    Equalizer,
+    Compressor,
+    Limiter,
+    Gate,
+    Distortion,
+    Reverb,
+    Delay,
+    Chorus,
+    Flanger,
+    Phaser,
+    PitchShifter,
+    Utility,
+    Mono,
+    Stereo,
+    Surround,
+    /// Passed to the host as-is.
+    Custom(String),
+}
 
 pub struct PluginInfo {
+    /// Stable identifier, e.g. in reverse domain name notation. Stored in saved [`State`] and
+    /// preset files.
+    pub id: String,
     pub name: String,
     pub version: String,
     pub vendor: String,
     pub url: String,
     pub email: String,
+    pub category: Category,
+    pub features: Vec<Feature>,
     pub buses: Vec<BusInfo>,
     pub layouts: Vec<Layout>,
     pub params: Vec<ParamInfo>,
+    pub presets: Vec<PresetInfo>,
+    pub midi_mappings: Vec<MidiMapping>,
     pub has_view: bool,
 }
 
@@ -23,19 +69,46 @@ pub struct PluginInfo {
 impl Default for PluginInfo {
     fn default() -> PluginInfo {
         PluginInfo {
+            id: String::new(),
             name: String::new(),
             version: String::new(),
             vendor: String::new(),
             url: String::new(),
             email: String::new(),
+            category: Category::Effect,
+            features: Vec::new(),
             buses: Vec::new(),
             layouts: Vec::new(),
             params: Vec::new(),
+            presets: Vec::new(),
+            midi_mappings: Vec::new(),
             has_view: false,
         }
     }
 }
 
+/// The polyphony of an instrument, used by hosts for polyphonic modulation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VoiceInfo {
+    /// The number of voices the instrument is currently set to.
+    pub voice_count: u32,
+    /// The largest number of voices the instrument can be set to.
+    pub voice_capacity: u32,
+    /// Whether voices are addressed by note id, so that several voices can play the same key at
+    /// once.
+    pub note_id_addressable: bool,
+}
+
+/// A page of parameters for hosts to map to the knobs of a hardware controller.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteControlsPage {
+    /// Groups related pages, e.g. "Oscillators".
+    pub section: String,
+    pub name: String,
+    /// Up to eight parameters, in knob order. `None` leaves a knob unassigned.
+    pub params: Vec<Option<ParamId>>,
+}
+
 pub trait Plugin: Send + Sized + 'static {
     type Engine: Engine;
     type View: View;
@@ -51,13 +124,74 @@ pub trait Plugin: Send + Sized + 'static {
         value: ParamValue,
         fmt: &mut Formatter,
     ) -> Result<(), fmt::Error>;
-    fn save(&self, output: &mut impl Write) -> io::Result<()>;
-    fn load(&mut self, input: &mut impl Read) -> io::Result<()>;
     fn engine(&mut self, config: &Config) -> Self::Engine;
     fn view(&mut self, host: ViewHost, parent: &ParentWindow) -> Self::View;
 
+    /// Saves the values of all parameters as a [`State`]. Plugins with state beyond their
+    /// parameters should override this along with [`load`](Plugin::load).
+    #[allow(unused_variables)]
+    fn save(&self, output: &mut impl Write, context: StateContext) -> io::Result<()> {
+        State::capture(self).write(output)
+    }
+
+    #[allow(unused_variables)]
+    fn load(&mut self, input: &mut impl Read, context: StateContext) -> io::Result<()> {
+        State::read_or_legacy(input, &Self::info().id)?.restore(self)
+    }
+
+    /// Loads the preset at `index` in [`PluginInfo::presets`].
+    #[allow(unused_variables)]
+    fn load_preset(&mut self, index: usize) -> io::Result<()> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
     #[allow(unused_variables)]
     fn latency(&self, config: &Config) -> u64 {
         0
     }
+
+    /// Reports the polyphony of an instrument. Call [`Host::voice_info_changed`] when this changes.
+    fn voice_info(&self) -> Option<VoiceInfo> {
+        None
+    }
+
+    /// Pages of parameters for hardware controllers. Call [`Host::remote_controls_changed`] when
+    /// these change.
+    fn remote_controls(&self) -> Vec<RemoteControlsPage> {
+        Vec::new()
+    }
+
+    /// Called on the main thread when the host reports the track the plugin is placed on, and
+    /// again whenever it changes.
+    #[allow(unused_variables)]
+    fn track_info_changed(&mut self, info: &TrackInfo) {}
+
+    /// Items to add to the host's context menu for a parameter.
+    #[allow(unused_variables)]
+    fn param_context_menu(&self, id: ParamId) -> Vec<MenuItem> {
+        Vec::new()
+    }
+
+    /// Called when the user picks one of the items from
+    /// [`param_context_menu`](Plugin::param_context_menu).
+    #[allow(unused_variables)]
+    fn param_context_menu_action(&mut self, id: ParamId, action: u32) {}
+
+    /// Upgrades a [`State`] saved by version `from_version` of the plugin, e.g. by renaming
+    /// parameter ids or rewriting the user data. Called by [`State::restore`] before parameter
+    /// values are applied.
+    ///
+    /// A `from_version` of [`LEGACY_VERSION`] means the saved data wasn't in the `State` format at
+    /// all; it is passed unparsed in `state.data`. The default implementation rejects it.
+    #[allow(unused_variables)]
+    fn migrate(&mut self, from_version: &str, state: &mut State) -> io::Result<()> {
+        if from_version == LEGACY_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a coupler state",
+            ));
+        }
+
+        Ok(())
+    }
 }
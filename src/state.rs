@@ -0,0 +1,275 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::params::{ParamId, ParamValue};
+use crate::plugin::Plugin;
+
+const MAGIC: [u8; 4] = *b"CPLR";
+
+/// Version of the binary layout written by [`State::write`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The `plugin_version` given by [`State::read_or_legacy`] to data that isn't in the `State`
+/// format, e.g. a session saved before the plugin switched to the default [`Plugin::save`].
+pub const LEGACY_VERSION: &str = "";
+
+/// Why a plugin's state is being saved or loaded.
+///
+/// Plugins can use this to leave out things that don't belong in a preset, such as sample paths
+/// or editor layout.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum StateContext {
+    /// Saving or loading a project. This is also used when the host doesn't say.
+    #[default]
+    Project,
+    /// Saving or loading a preset, which may be applied to other instances.
+    Preset,
+    /// Copying the state to another instance in the same project.
+    Duplicate,
+}
+
+/// A standard container for plugin state.
+///
+/// Plugins are free to use any format in [`Plugin::save`] and [`Plugin::load`]. `State` provides a
+/// self-describing one: a header identifying the plugin and the version that saved it, parameter
+/// values keyed by [`ParamId`], and an opaque blob for anything else. When loading a state saved
+/// by a different plugin version, [`State::restore`] gives the plugin a chance to rewrite it via
+/// [`Plugin::migrate`] before any parameters are applied.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct State {
+    pub plugin_id: String,
+    pub plugin_version: String,
+    pub params: Vec<(ParamId, ParamValue)>,
+    pub data: Vec<u8>,
+}
+
+impl State {
+    pub fn new(plugin_id: &str, plugin_version: &str) -> State {
+        State {
+            plugin_id: plugin_id.to_string(),
+            plugin_version: plugin_version.to_string(),
+            params: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Captures the current values of all of `plugin`'s parameters. `data` is left empty.
+    pub fn capture<P: Plugin>(plugin: &P) -> State {
+        let info = P::info();
+
+        let mut state = State::new(&info.id, &info.version);
+        for param in &info.params {
+            state.params.push((param.id, plugin.get_param(param.id)));
+        }
+
+        state
+    }
+
+    /// Applies this state to `plugin`, calling [`Plugin::migrate`] first if it was saved by a
+    /// different version. Values for parameters the plugin doesn't have are ignored, and
+    /// parameters missing from the state are reset to their defaults.
+    pub fn restore<P: Plugin>(&mut self, plugin: &mut P) -> io::Result<()> {
+        let info = P::info();
+
+        if self.plugin_id != info.id {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "state was saved by a different plugin",
+            ));
+        }
+
+        if self.plugin_version != info.version {
+            let from_version = std::mem::replace(&mut self.plugin_version, info.version.clone());
+            plugin.migrate(&from_version, self)?;
+        }
+
+        for param in &info.params {
+            let value = self.param(param.id).unwrap_or(param.default);
+            plugin.set_param(param.id, value.clamp(0.0, 1.0));
+        }
+
+        Ok(())
+    }
+
+    pub fn param(&self, id: ParamId) -> Option<ParamValue> {
+        self.params.iter().find(|(i, _)| *i == id).map(|(_, value)| *value)
+    }
+
+    pub fn set_param(&mut self, id: ParamId, value: ParamValue) {
+        if let Some((_, existing)) = self.params.iter_mut().find(|(i, _)| *i == id) {
+            *existing = value;
+        } else {
+            self.params.push((id, value));
+        }
+    }
+
+    pub fn remove_param(&mut self, id: ParamId) -> Option<ParamValue> {
+        let index = self.params.iter().position(|(i, _)| *i == id)?;
+        Some(self.params.remove(index).1)
+    }
+
+    pub fn write(&self, output: &mut impl Write) -> io::Result<()> {
+        output.write_all(&MAGIC)?;
+        output.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+        write_bytes(output, self.plugin_id.as_bytes())?;
+        write_bytes(output, self.plugin_version.as_bytes())?;
+
+        output.write_all(&(self.params.len() as u32).to_le_bytes())?;
+        for &(id, value) in &self.params {
+            output.write_all(&id.to_le_bytes())?;
+            output.write_all(&value.to_le_bytes())?;
+        }
+
+        write_bytes(output, &self.data)
+    }
+
+    /// Like [`State::read`], but input that doesn't start with a `State` header is returned whole
+    /// in `data`, with no parameters and a `plugin_version` of [`LEGACY_VERSION`].
+    /// [`State::restore`] then hands it to [`Plugin::migrate`] to convert.
+    pub fn read_or_legacy(input: &mut impl Read, plugin_id: &str) -> io::Result<State> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+
+        if bytes.starts_with(&MAGIC) {
+            return State::read(&mut &bytes[..]);
+        }
+
+        let mut state = State::new(plugin_id, LEGACY_VERSION);
+        state.data = bytes;
+        Ok(state)
+    }
+
+    pub fn read(input: &mut impl Read) -> io::Result<State> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a coupler state",
+            ));
+        }
+
+        let format_version = read_u32(input)?;
+        if format_version > FORMAT_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "unsupported state format version",
+            ));
+        }
+
+        let plugin_id = read_string(input)?;
+        let plugin_version = read_string(input)?;
+
+        let count = read_u32(input)?;
+        let mut params = Vec::new();
+        for _ in 0..count {
+            let id = read_u32(input)?;
+            let mut value = [0; 8];
+            input.read_exact(&mut value)?;
+            params.push((id, f64::from_le_bytes(value)));
+        }
+
+        let data = read_bytes(input)?;
+
+        Ok(State {
+            plugin_id,
+            plugin_version,
+            params,
+            data,
+        })
+    }
+}
+
+pub(crate) fn write_bytes(output: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    output.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    output.write_all(bytes)
+}
+
+pub(crate) fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(input)? as u64;
+
+    // Don't trust the length prefix for preallocation, since the stream may be truncated.
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+pub(crate) fn read_string(input: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(input)?)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid utf-8 string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut state = State::new("com.example.plugin", "1.0.0");
+        state.set_param(0, 0.25);
+        state.set_param(7, 1.0);
+        state.data = vec![1, 2, 3];
+
+        let mut bytes = Vec::new();
+        state.write(&mut bytes).unwrap();
+
+        assert_eq!(State::read(&mut &bytes[..]).unwrap(), state);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes = Vec::new();
+        State::new("com.example.plugin", "1.0.0").write(&mut bytes).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(State::read(&mut &bad_magic[..]).is_err());
+
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(State::read(&mut &future[..]).is_err());
+
+        assert!(State::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn legacy() {
+        let mut bytes = Vec::new();
+        let mut state = State::new("com.example.plugin", "1.0.0");
+        state.set_param(0, 0.25);
+        state.write(&mut bytes).unwrap();
+        assert_eq!(
+            State::read_or_legacy(&mut &bytes[..], "com.example.plugin").unwrap(),
+            state
+        );
+
+        let legacy = State::read_or_legacy(&mut &b"{\"gain\":0.5}"[..], "com.example.plugin");
+        let legacy = legacy.unwrap();
+        assert_eq!(legacy.plugin_id, "com.example.plugin");
+        assert_eq!(legacy.plugin_version, LEGACY_VERSION);
+        assert!(legacy.params.is_empty());
+        assert_eq!(legacy.data, b"{\"gain\":0.5}");
+    }
+
+    #[test]
+    fn params() {
+        let mut state = State::new("com.example.plugin", "1.0.0");
+        state.set_param(1, 0.5);
+        state.set_param(1, 0.75);
+        assert_eq!(state.params, [(1, 0.75)]);
+
+        assert_eq!(state.remove_param(1), Some(0.75));
+        assert_eq!(state.param(1), None);
+        assert_eq!(state.remove_param(1), None);
+    }
+}
@@ -2,3 +2,4 @@ pub mod bitset;
 pub mod float;
 pub mod param_gestures;
 pub mod params;
+pub mod queue;
@@ -0,0 +1,144 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Slot<T> {
+    // Even while the slot is empty for lap `stamp / 2`, odd once it holds that lap's value.
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    const fn new() -> Slot<T> {
+        Slot {
+            stamp: AtomicUsize::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+/// A fixed-capacity, lock-free queue with any number of producers and a single consumer.
+/// Pushing never allocates or blocks, so it is safe to call from the audio thread.
+pub struct Queue<T, const N: usize> {
+    slots: [Slot<T>; N],
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Queue<T, N> {}
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T, const N: usize> Queue<T, N> {
+    pub const fn new() -> Queue<T, N> {
+        Queue {
+            slots: [const { Slot::new() }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the value back if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % N];
+            let lap = pos / N;
+
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if stamp == 2 * lap {
+                match self.head.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.stamp.store(2 * lap + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if stamp < 2 * lap {
+                // The slot still holds a value from the previous lap.
+                return Err(value);
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Must not be called from more than one thread at a time.
+    pub unsafe fn pop(&self) -> Option<T> {
+        let pos = self.tail.load(Ordering::Relaxed);
+        let slot = &self.slots[pos % N];
+        let lap = pos / N;
+
+        if slot.stamp.load(Ordering::Acquire) != 2 * lap + 1 {
+            return None;
+        }
+
+        let value = (*slot.value.get()).assume_init_read();
+        slot.stamp.store(2 * (lap + 1), Ordering::Release);
+        self.tail.store(pos + 1, Ordering::Relaxed);
+
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        while unsafe { self.pop() }.is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn push_pop() {
+        let queue = Queue::<u32, 4>::new();
+
+        for lap in 0..3 {
+            for value in 0..4 {
+                assert_eq!(queue.push(lap * 4 + value), Ok(()));
+            }
+            assert_eq!(queue.push(100), Err(100));
+
+            for value in 0..4 {
+                assert_eq!(unsafe { queue.pop() }, Some(lap * 4 + value));
+            }
+            assert_eq!(unsafe { queue.pop() }, None);
+        }
+    }
+
+    #[test]
+    fn multiple_producers() {
+        let queue = Arc::new(Queue::<usize, 64>::new());
+
+        let producers = (0..4)
+            .map(|thread| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for value in 0..8 {
+                        queue.push(thread * 8 + value).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        let mut values = Vec::new();
+        while let Some(value) = unsafe { queue.pop() } {
+            values.push(value);
+        }
+        values.sort();
+        assert_eq!(values, (0..32).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,126 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ThreadRole {
+    Main,
+    Audio,
+}
+
+thread_local! {
+    static ROLE: Cell<Option<ThreadRole>> = const { Cell::new(None) };
+}
+
+pub fn role() -> Option<ThreadRole> {
+    ROLE.get()
+}
+
+/// Marks the current thread as the main thread, so that it isn't treated as an audio thread after
+/// processing offline. Called when a plugin instance is created, which formats require to happen
+/// on the main thread.
+pub fn set_main_thread() {
+    ROLE.set(Some(ThreadRole::Main));
+}
+
+/// Marks the current thread as an audio thread until the returned guard is dropped. Threads which
+/// aren't the main thread stay marked as audio threads afterwards.
+pub fn enter_audio_thread() -> AudioThreadGuard {
+    AudioThreadGuard {
+        prev: ROLE.replace(Some(ThreadRole::Audio)),
+    }
+}
+
+pub struct AudioThreadGuard {
+    prev: Option<ThreadRole>,
+}
+
+impl Drop for AudioThreadGuard {
+    fn drop(&mut self) {
+        ROLE.set(Some(self.prev.unwrap_or(ThreadRole::Audio)));
+    }
+}
+
+static MAIN_THREAD_REPORTED: AtomicBool = AtomicBool::new(false);
+static AUDIO_THREAD_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Checks that the current thread is the main thread. `is_main_thread` asks the host, if it can
+/// tell; otherwise only threads known to be audio threads are rejected, since some hosts create
+/// plugins on a different thread than the one they later call them from.
+///
+/// A violation panics in debug builds. Since the checks run inside the host's calls into the
+/// plugin, where a panic aborts the host, release builds log the first violation instead.
+#[track_caller]
+pub fn check_main_thread(is_main_thread: impl FnOnce() -> Option<bool>) {
+    let is_main_thread = is_main_thread().unwrap_or_else(|| role() != Some(ThreadRole::Audio));
+    if !is_main_thread {
+        if cfg!(debug_assertions) {
+            panic!("called from outside the main thread");
+        } else if !MAIN_THREAD_REPORTED.swap(true, Ordering::Relaxed) {
+            log::error!("called from outside the main thread");
+        }
+    }
+}
+
+/// Checks that the host reports the current thread as an audio thread. Without help from the host
+/// this can't be checked, since hosts may process on the main thread when rendering offline.
+///
+/// Violations are handled like in [`check_main_thread`].
+#[track_caller]
+pub fn check_audio_thread(is_audio_thread: impl FnOnce() -> Option<bool>) {
+    let is_audio_thread = is_audio_thread().unwrap_or(true);
+    if !is_audio_thread {
+        if cfg!(debug_assertions) {
+            panic!("called from outside an audio thread");
+        } else if !AUDIO_THREAD_REPORTED.swap(true, Ordering::Relaxed) {
+            log::error!("called from outside an audio thread");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn roles() {
+        thread::spawn(|| {
+            assert_eq!(role(), None);
+
+            set_main_thread();
+            check_main_thread(|| None);
+
+            let guard = enter_audio_thread();
+            assert_eq!(role(), Some(ThreadRole::Audio));
+            drop(guard);
+            assert_eq!(role(), Some(ThreadRole::Main));
+        })
+        .join()
+        .unwrap();
+
+        thread::spawn(|| {
+            drop(enter_audio_thread());
+            assert_eq!(role(), Some(ThreadRole::Audio));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "called from outside the main thread")]
+    fn audio_thread_calls_main_thread() {
+        set_main_thread();
+
+        let _guard = enter_audio_thread();
+        check_main_thread(|| None);
+    }
+
+    #[test]
+    #[should_panic(expected = "called from outside the main thread")]
+    fn host_overrides_role() {
+        set_main_thread();
+
+        check_main_thread(|| Some(false));
+    }
+}
@@ -0,0 +1,31 @@
+/// An sRGB color with alpha.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TrackKind {
+    /// A regular track, or a track of a kind the host doesn't report.
+    #[default]
+    Regular,
+    /// A group or bus track.
+    Bus,
+    /// A return or effect track.
+    Return,
+    Master,
+}
+
+/// Information about the track a plugin instance is placed on, passed to
+/// [`Plugin::track_info_changed`](crate::plugin::Plugin::track_info_changed). Fields the host
+/// doesn't provide are left as `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackInfo {
+    pub name: Option<String>,
+    pub color: Option<Color>,
+    pub channel_count: Option<u32>,
+    pub kind: TrackKind,
+}
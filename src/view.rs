@@ -3,11 +3,13 @@ use std::marker::PhantomData;
 use std::rc::Rc;
 
 use crate::params::{ParamId, ParamValue};
+use crate::track::Color;
 
 pub trait ViewHostInner {
     fn begin_gesture(&self, id: ParamId);
     fn end_gesture(&self, id: ParamId);
     fn set_param(&self, id: ParamId, value: ParamValue);
+    fn show_param_context_menu(&self, id: ParamId, x: f64, y: f64) -> bool;
 }
 
 #[derive(Clone)]
@@ -36,6 +38,13 @@ impl ViewHost {
     pub fn set_param(&self, id: ParamId, value: ParamValue) {
         self.inner.set_param(id, value);
     }
+
+    /// Asks the host to show its context menu for a parameter at a position relative to the view,
+    /// including any items from [`Plugin::param_context_menu`](crate::plugin::Plugin::param_context_menu). Returns
+    /// `false` if the host can't show the menu.
+    pub fn show_param_context_menu(&self, id: ParamId, x: f64, y: f64) -> bool {
+        self.inner.show_param_context_menu(id, x, y)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -64,9 +73,65 @@ pub struct Size {
     pub height: f64,
 }
 
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AutomationState {
+    #[default]
+    None,
+    /// The parameter has automation, but it isn't playing.
+    Present,
+    Playing,
+    Recording,
+    /// The parameter has automation, but it is currently overridden by the user.
+    Overriding,
+}
+
+/// A mapping from a hardware controller to a parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamMapping {
+    pub color: Option<Color>,
+    /// A short label for the controller, e.g. "CC 21".
+    pub label: Option<String>,
+    pub description: Option<String>,
+}
+
+/// How the host is controlling a parameter, so that views can display it like native devices do.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParamIndication {
+    pub automation: AutomationState,
+    pub automation_color: Option<Color>,
+    pub mapping: Option<ParamMapping>,
+}
+
+/// An item contributed by the plugin to a host context menu.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuItem {
+    Entry {
+        label: String,
+        enabled: bool,
+        action: u32,
+    },
+    CheckEntry {
+        label: String,
+        enabled: bool,
+        checked: bool,
+        action: u32,
+    },
+    Separator,
+    Submenu {
+        label: String,
+        enabled: bool,
+        items: Vec<MenuItem>,
+    },
+}
+
 pub trait View: Sized + 'static {
     fn size(&self) -> Size;
     fn param_changed(&mut self, id: ParamId, value: ParamValue);
+
+    /// Called when the host's indication for a parameter changes. When the view is created, this
+    /// is called for every parameter which already has an indication.
+    #[allow(unused_variables)]
+    fn param_indication_changed(&mut self, id: ParamId, indication: &ParamIndication) {}
 }
 
 pub struct NoView;